@@ -0,0 +1,28 @@
+use saba_nogtk_core::cache::HttpCache;
+use saba_nogtk_core::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+// Loads an HTTP cache saved by `save_http_cache`. A missing file is treated as an empty cache.
+pub fn load_http_cache(path: &Path) -> Result<HttpCache, Error> {
+    match fs::read(path) {
+        Ok(data) => HttpCache::deserialize(&data),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(HttpCache::new()),
+        Err(e) => Err(Error::Other(format!(
+            "Failed to read http cache {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+pub fn save_http_cache(path: &Path, http_cache: &HttpCache) -> Result<(), Error> {
+    fs::write(path, http_cache.serialize()).map_err(|e| {
+        Error::Other(format!(
+            "Failed to write http cache {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
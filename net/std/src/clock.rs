@@ -0,0 +1,25 @@
+use saba_nogtk_core::clock::Clock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct SystemClock {}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,28 @@
+use saba_nogtk_core::cookie::CookieJar;
+use saba_nogtk_core::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+// Loads a cookie jar saved by `save_cookie_jar`. A missing file is treated as an empty jar.
+pub fn load_cookie_jar(path: &Path) -> Result<CookieJar, Error> {
+    match fs::read_to_string(path) {
+        Ok(s) => CookieJar::deserialize(&s),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(CookieJar::new()),
+        Err(e) => Err(Error::Other(format!(
+            "Failed to read cookie jar {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+pub fn save_cookie_jar(path: &Path, cookie_jar: &CookieJar) -> Result<(), Error> {
+    fs::write(path, cookie_jar.serialize()).map_err(|e| {
+        Error::Other(format!(
+            "Failed to write cookie jar {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
@@ -0,0 +1,94 @@
+use crate::clock::SystemClock;
+use crate::random::OsRandom;
+use crate::transport::StdTransport;
+use saba_nogtk_core::dns::{
+    CachingResolver, DatagramSocket, DnsResolver, HostsResolver, HostsTable,
+};
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::transport::ResolvingTransport;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct UdpDatagramSocket {
+    socket: UdpSocket,
+}
+
+impl UdpDatagramSocket {
+    // Opens a socket that exchanges datagrams with `server` only.
+    pub fn connect(server: SocketAddr) -> Result<Self, Error> {
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(server).map(|_| socket))
+            .and_then(|socket| socket.set_read_timeout(Some(QUERY_TIMEOUT)).map(|_| socket))
+            .map_err(|e| Error::Network(format!("Failed to open a UDP socket: {}", e)))?;
+        Ok(Self { socket })
+    }
+}
+
+impl DatagramSocket for UdpDatagramSocket {
+    fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.socket
+            .send(data)
+            .map(|_| ())
+            .map_err(|e| Error::Network(format!("Failed to send a DNS query: {}", e)))
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<Option<usize>, Error> {
+        match self.socket.recv(buf) {
+            Ok(n) => Ok(Some(n)),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(Error::Network(format!(
+                "Failed to receive a DNS response: {}",
+                e
+            ))),
+        }
+    }
+}
+
+pub type StdResolver =
+    HostsResolver<CachingResolver<DnsResolver<UdpDatagramSocket, OsRandom>, SystemClock>>;
+
+pub type ResolvingStdTransport = ResolvingTransport<StdTransport, StdResolver>;
+
+pub fn load_hosts_table(path: &str) -> Result<HostsTable, Error> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| Error::Other(format!("Failed to read {}: {}", path, e)))?;
+    Ok(HostsTable::parse(&text))
+}
+
+// The first name server listed in resolv.conf-formatted text.
+pub fn parse_nameserver(text: &str) -> Option<SocketAddr> {
+    text.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("nameserver") {
+            return None;
+        }
+        let address = fields.next()?.parse::<IpAddr>().ok()?;
+        Some(SocketAddr::new(address, 53))
+    })
+}
+
+pub fn system_nameserver() -> Result<SocketAddr, Error> {
+    let text = fs::read_to_string("/etc/resolv.conf")
+        .map_err(|e| Error::Other(format!("Failed to read /etc/resolv.conf: {}", e)))?;
+    parse_nameserver(&text).ok_or_else(|| Error::Other("No name server is configured".to_string()))
+}
+
+// A TCP transport that looks names up in `hosts` first and otherwise asks `nameserver`.
+pub fn resolving_transport(
+    nameserver: SocketAddr,
+    hosts: HostsTable,
+) -> Result<ResolvingStdTransport, Error> {
+    let dns = DnsResolver::new(UdpDatagramSocket::connect(nameserver)?, OsRandom::new());
+    Ok(ResolvingTransport::new(
+        StdTransport::new(),
+        HostsResolver::new(hosts, CachingResolver::new(dns, SystemClock::new())),
+    ))
+}
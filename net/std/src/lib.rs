@@ -0,0 +1,8 @@
+pub mod cache_store;
+pub mod clock;
+pub mod cookie_store;
+pub mod dns;
+pub mod proxy_env;
+pub mod random;
+pub mod root_store;
+pub mod transport;
@@ -0,0 +1,32 @@
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::proxy::{Proxy, ProxyConfig};
+use std::env;
+
+// Reads the conventional http_proxy, https_proxy and no_proxy variables. The lowercase names
+// take precedence over the uppercase ones, and empty values are ignored.
+pub fn proxy_config_from_env() -> Result<ProxyConfig, Error> {
+    proxy_config_from_vars(|name| env::var(name).ok())
+}
+
+pub fn proxy_config_from_vars<F>(var: F) -> Result<ProxyConfig, Error>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let lookup = |name: &str| {
+        var(name)
+            .or_else(|| var(&name.to_ascii_uppercase()))
+            .filter(|value| !value.trim().is_empty())
+    };
+
+    let mut proxy_config = ProxyConfig::new();
+    if let Some(proxy) = lookup("http_proxy") {
+        proxy_config.set_http_proxy(Some(Proxy::parse(&proxy)?));
+    }
+    if let Some(proxy) = lookup("https_proxy") {
+        proxy_config.set_https_proxy(Some(Proxy::parse(&proxy)?));
+    }
+    if let Some(no_proxy) = lookup("no_proxy") {
+        proxy_config.set_no_proxy(&no_proxy);
+    }
+    Ok(proxy_config)
+}
@@ -0,0 +1,27 @@
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::random::RandomSource;
+use std::fs::File;
+use std::io::Read;
+
+// Reads from the kernel's random number generator.
+pub struct OsRandom {}
+
+impl OsRandom {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for OsRandom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomSource for OsRandom {
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        File::open("/dev/urandom")
+            .and_then(|mut file| file.read_exact(buf))
+            .map_err(|e| Error::Other(format!("Failed to read /dev/urandom: {}", e)))
+    }
+}
@@ -0,0 +1,32 @@
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::tls::RootStore;
+use std::fs;
+
+// Where common Linux distributions keep their bundle of trusted certificates.
+const SYSTEM_BUNDLES: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+pub fn load_root_store(path: &str) -> Result<RootStore, Error> {
+    let pem = fs::read_to_string(path)
+        .map_err(|e| Error::Other(format!("Failed to read {}: {}", path, e)))?;
+    let mut root_store = RootStore::new();
+    root_store.add_pem(&pem)?;
+    Ok(root_store)
+}
+
+// Loads the first system certificate bundle that exists.
+pub fn system_root_store() -> Result<RootStore, Error> {
+    match SYSTEM_BUNDLES
+        .iter()
+        .find(|path| fs::metadata(path).is_ok())
+    {
+        Some(path) => load_root_store(path),
+        None => Err(Error::Other(
+            "No system certificate bundle was found".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,74 @@
+use crate::clock::SystemClock;
+use crate::random::OsRandom;
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::tls::{RootStore, TlsClient, TlsTransport};
+use saba_nogtk_core::transport::{Connector, Stream, Transport};
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+
+pub struct StdStream {
+    stream: TcpStream,
+}
+
+impl Stream for StdStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.stream
+            .read(buf)
+            .map_err(|e| Error::Network(format!("Failed to read from TCP stream: {}", e)))
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.stream
+            .write(buf)
+            .map_err(|e| Error::Network(format!("Failed to write to TCP stream: {}", e)))
+    }
+}
+
+pub struct StdTransport {}
+
+impl StdTransport {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for StdTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for StdTransport {
+    fn connect(&mut self, host: &str, port: u16) -> Result<Box<dyn Stream>, Error> {
+        match TcpStream::connect((host, port)) {
+            Ok(stream) => Ok(Box::new(StdStream { stream })),
+            Err(e) => Err(Error::Network(format!(
+                "Failed to connect to {}:{}: {}",
+                host, port, e
+            ))),
+        }
+    }
+}
+
+impl Connector for StdTransport {
+    fn connect_addr(&mut self, address: IpAddr, port: u16) -> Result<Box<dyn Stream>, Error> {
+        match TcpStream::connect((address, port)) {
+            Ok(stream) => Ok(Box::new(StdStream { stream })),
+            Err(e) => Err(Error::Network(format!(
+                "Failed to connect to {}: {}",
+                SocketAddr::new(address, port),
+                e
+            ))),
+        }
+    }
+}
+
+pub type StdTlsTransport = TlsTransport<StdTransport, OsRandom, SystemClock>;
+
+// A TCP transport that can also speak TLS to servers whose certificates chain to `root_store`.
+pub fn tls_transport(root_store: RootStore) -> StdTlsTransport {
+    TlsTransport::new(
+        StdTransport::new(),
+        TlsClient::new(root_store, OsRandom::new(), SystemClock::new()),
+    )
+}
@@ -0,0 +1,728 @@
+use net_std::cache_store::{load_http_cache, save_http_cache};
+use net_std::clock::SystemClock;
+use net_std::cookie_store::{load_cookie_jar, save_cookie_jar};
+use net_std::dns::resolving_transport;
+use net_std::proxy_env::proxy_config_from_vars;
+use net_std::random::OsRandom;
+use net_std::root_store::load_root_store;
+use net_std::transport::{tls_transport, StdTransport};
+use saba_nogtk_core::auth::{CredentialStore, Credentials};
+use saba_nogtk_core::browser::Browser;
+use saba_nogtk_core::clock::Clock;
+use saba_nogtk_core::dns::HostsTable;
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::fetch::{
+    AuthFetcher, CachingFetcher, CookieFetcher, Fetcher, HttpFetcher, ResponseSink,
+};
+use saba_nogtk_core::fixture::painted_texts;
+use saba_nogtk_core::http::{HttpRequest, HttpResponse, Method};
+use saba_nogtk_core::renderer::page::Page;
+use saba_nogtk_core::tls::RootStore;
+use saba_nogtk_core::url::Url;
+use saba_nogtk_core::websocket::{
+    accept_key, Frame, FrameReader, Message, Opcode, WebSocket, CLOSE_GOING_AWAY,
+};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn serve(responses: Vec<&'static str>) -> (SocketAddr, thread::JoinHandle<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a local port");
+    let addr = listener.local_addr().unwrap();
+
+    let handle = thread::spawn(move || {
+        let mut accepted = 0;
+        let mut responses = responses.into_iter();
+        while let Some(response) = responses.next() {
+            let (stream, _) = listener.accept().unwrap();
+            accepted += 1;
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut response = Some(response);
+            while let Some(r) = response.take() {
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                }
+                writer.write_all(r.as_bytes()).unwrap();
+                if !r.contains("Connection: close") {
+                    response = responses.next();
+                }
+            }
+        }
+        accepted
+    });
+
+    (addr, handle)
+}
+
+fn url(addr: SocketAddr, path: &str) -> Url {
+    Url::new(format!("http://{}{}", addr, path))
+        .parse()
+        .expect("failed to parse url")
+}
+
+fn get(addr: SocketAddr, path: &str) -> HttpRequest {
+    HttpRequest::new(Method::Get, url(addr, path))
+}
+
+#[test]
+fn test_fetch_parse_layout_paint() {
+    let (addr, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+         1c\r\n<html><body><p>hello</p></bo\r\n\
+         b\r\ndy></html>\n\r\n0\r\n\r\n",
+    ]);
+
+    let mut fetcher = HttpFetcher::new(StdTransport::new());
+    let response = fetcher
+        .fetch(&get(addr, "/index.html"))
+        .expect("failed to fetch");
+    assert_eq!(response.status_code(), 200);
+
+    let browser = Browser::new();
+    let page = browser.borrow().current_page();
+    page.borrow_mut().set_url(url(addr, "/index.html"));
+    page.borrow_mut().receive_response(response);
+
+    assert_eq!(painted_texts(&page.borrow()), vec!["hello".to_string()]);
+    assert_eq!(server.join().unwrap(), 1);
+}
+
+// Passes the response on to the page and tells the server once the first paragraph is shown.
+struct PaintWatcher {
+    page: Rc<RefCell<Page>>,
+    painted: Option<Sender<()>>,
+}
+
+impl ResponseSink for PaintWatcher {
+    fn receive_head(&mut self, response: &HttpResponse) {
+        self.page.borrow_mut().receive_head(response);
+    }
+
+    fn receive_body(&mut self, bytes: &[u8]) {
+        self.page.borrow_mut().receive_body(bytes);
+        if painted_texts(&self.page.borrow()) == vec!["first".to_string()] {
+            if let Some(painted) = self.painted.take() {
+                painted.send(()).unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_page_is_painted_while_the_body_arrives() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a local port");
+    let addr = listener.local_addr().unwrap();
+    let (painted, shown) = channel();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                break;
+            }
+        }
+        writer
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+                  Connection: close\r\n\r\n<html><body><p>first</p>",
+            )
+            .unwrap();
+        // The rest of the document is held back until the client shows the first part.
+        let was_shown = shown.recv_timeout(Duration::from_secs(10)).is_ok();
+        writer.write_all(b"<p>second</p></body></html>").unwrap();
+        was_shown
+    });
+
+    let browser = Browser::new();
+    let page = browser.borrow().current_page();
+    let mut watcher = PaintWatcher {
+        page: page.clone(),
+        painted: Some(painted),
+    };
+    let mut fetcher = HttpFetcher::new(StdTransport::new());
+    let response = fetcher
+        .fetch_streaming(&get(addr, "/index.html"), &mut watcher)
+        .expect("failed to fetch");
+    page.borrow_mut().finish_response(response);
+
+    assert!(server.join().unwrap());
+    assert_eq!(
+        painted_texts(&page.borrow()),
+        vec!["first".to_string(), "second".to_string()]
+    );
+}
+
+#[test]
+fn test_keep_alive_connection_is_reused() {
+    let (addr, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst",
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecond",
+    ]);
+
+    let mut fetcher = HttpFetcher::new(StdTransport::new());
+    let first = fetcher.fetch(&get(addr, "/a")).expect("failed to fetch");
+    let second = fetcher.fetch(&get(addr, "/b")).expect("failed to fetch");
+    assert_eq!(first.body_text(), "first");
+    assert_eq!(second.body_text(), "second");
+    assert_eq!(server.join().unwrap(), 1);
+}
+
+#[test]
+fn test_login_session_survives_restart() {
+    let (addr, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nSet-Cookie: session=s3cr3t; Path=/; HttpOnly\r\nSet-Cookie: tmp=1; Max-Age=0\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+    ]);
+
+    let browser = Browser::new();
+    let mut fetcher = CookieFetcher::new(
+        HttpFetcher::new(StdTransport::new()),
+        browser.borrow().cookie_jar(),
+        SystemClock::new(),
+    );
+    fetcher
+        .fetch(&HttpRequest::new(Method::Post, url(addr, "/login")))
+        .expect("failed to fetch");
+    server.join().unwrap();
+
+    let path = std::env::temp_dir().join(format!("saba-nogtk-cookies-{}.txt", addr.port()));
+    save_cookie_jar(&path, &browser.borrow().cookie_jar().borrow()).expect("failed to save");
+
+    let restarted = Browser::new();
+    restarted
+        .borrow_mut()
+        .set_cookie_jar(load_cookie_jar(&path).expect("failed to load"));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        restarted.borrow().cookie_jar().borrow().cookie_header(
+            &url(addr, "/account"),
+            None,
+            SystemClock::new().now()
+        ),
+        Some("session=s3cr3t".to_string())
+    );
+}
+
+#[test]
+fn test_reload_is_served_from_cache() {
+    let (addr, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nCache-Control: max-age=3600\r\nContent-Length: 26\r\nConnection: close\r\n\r\n<html><p>cached</p></html>",
+    ]);
+
+    let browser = Browser::new();
+    let mut fetcher = CachingFetcher::new(
+        HttpFetcher::new(StdTransport::new()),
+        browser.borrow().http_cache(),
+        SystemClock::new(),
+    );
+    let first = fetcher
+        .fetch(&get(addr, "/index.html"))
+        .expect("failed to fetch");
+    assert!(!first.from_cache());
+    server.join().unwrap();
+
+    // The server has gone away, so the reload can only be answered by the cache.
+    let reload = fetcher
+        .fetch(&get(addr, "/index.html"))
+        .expect("failed to fetch");
+    let page = browser.borrow().current_page();
+    page.borrow_mut().receive_response(reload);
+    assert!(page.borrow().from_cache());
+
+    let path = std::env::temp_dir().join(format!("saba-nogtk-cache-{}.bin", addr.port()));
+    save_http_cache(&path, &browser.borrow().http_cache().borrow()).expect("failed to save");
+    let restored = load_http_cache(&path).expect("failed to load");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(restored.len(), 1);
+}
+
+#[test]
+fn test_digest_protected_page() {
+    let (addr, server) = serve(vec![
+        "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"docs\", Digest realm=\"docs\", qop=\"auth\", algorithm=SHA-256, nonce=\"abc\"\r\nContent-Length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 16\r\nConnection: close\r\n\r\n<p>internal</p>\n",
+    ]);
+
+    let browser = Browser::new();
+    let mut fetcher = AuthFetcher::new(
+        HttpFetcher::new(StdTransport::new()),
+        browser.borrow().credential_provider(),
+    );
+
+    // A provider set on the browser later is seen by the fetcher built before it.
+    let mut store = CredentialStore::new();
+    store.add(
+        &url(addr, "/"),
+        Some("docs"),
+        Credentials::new("alice", "s3cret"),
+    );
+    browser
+        .borrow_mut()
+        .set_credential_provider(Box::new(store));
+
+    let response = fetcher
+        .fetch(&get(addr, "/docs/index.html"))
+        .expect("failed to fetch");
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.body_text(), "<p>internal</p>\n");
+    assert_eq!(server.join().unwrap(), 1);
+}
+
+// A stand-in forward proxy for one connection. It answers plain requests itself and relays
+// CONNECT tunnels to the requested authority. The request line it received is returned.
+fn serve_proxy() -> (SocketAddr, thread::JoinHandle<(String, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a local port");
+    let addr = listener.local_addr().unwrap();
+
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut authorization = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Proxy-Authorization: ") {
+                authorization = value.trim().to_string();
+            }
+        }
+
+        if let Some(authority) = request_line
+            .strip_prefix("CONNECT ")
+            .and_then(|rest| rest.split(' ').next())
+        {
+            let origin = TcpStream::connect(authority).unwrap();
+            writer
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .unwrap();
+            let mut origin_reader = origin.try_clone().unwrap();
+            let mut origin_writer = origin;
+            let upstream = thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                while let Ok(n) = reader.read(&mut buf) {
+                    if n == 0 || origin_writer.write_all(&buf[..n]).is_err() {
+                        break;
+                    }
+                }
+            });
+            std::io::copy(&mut origin_reader, &mut writer).unwrap();
+            writer.shutdown(Shutdown::Write).unwrap();
+            upstream.join().unwrap();
+        } else {
+            writer
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 20\r\nConnection: close\r\n\r\n<p>via proxy</p>\n\n\n\n")
+                .unwrap();
+        }
+        (request_line.trim_end().to_string(), authorization)
+    });
+
+    (addr, handle)
+}
+
+#[test]
+fn test_fetch_through_proxy() {
+    let (proxy_addr, proxy) = serve_proxy();
+    let proxy_config = proxy_config_from_vars(|name| match name {
+        "http_proxy" => Some(format!("http://lab:p%40ss@{}", proxy_addr)),
+        "NO_PROXY" => Some("localhost,.internal".to_string()),
+        _ => None,
+    })
+    .expect("failed to read the proxy configuration");
+
+    let mut fetcher = HttpFetcher::new(StdTransport::new());
+    fetcher.set_proxy_config(proxy_config);
+    let request = HttpRequest::new(
+        Method::Get,
+        Url::new("http://outside.example/page?q=1".to_string())
+            .parse()
+            .expect("failed to parse url"),
+    );
+    let response = fetcher.fetch(&request).expect("failed to fetch");
+
+    let browser = Browser::new();
+    let page = browser.borrow().current_page();
+    page.borrow_mut().receive_response(response);
+    assert_eq!(painted_texts(&page.borrow()), vec!["via proxy".to_string()]);
+    assert_eq!(
+        proxy.join().unwrap(),
+        (
+            "GET http://outside.example/page?q=1 HTTP/1.1".to_string(),
+            "Basic bGFiOnBAc3M=".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_connect_tunnel_through_proxy() {
+    let (origin_addr, origin) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\ntunneled",
+    ]);
+    let (proxy_addr, proxy) = serve_proxy();
+    let proxy_config = proxy_config_from_vars(|name| match name {
+        "HTTPS_PROXY" => Some(proxy_addr.to_string()),
+        _ => None,
+    })
+    .expect("failed to read the proxy configuration");
+
+    let mut fetcher = HttpFetcher::new(StdTransport::new());
+    fetcher.set_proxy_config(proxy_config);
+    let target = Url::new(format!("https://{}/", origin_addr))
+        .parse()
+        .expect("failed to parse url");
+    let mut stream = fetcher.connect(&target).expect("failed to open a tunnel");
+
+    // The tunnel carries raw bytes, so a plain request exercises it end to end.
+    stream
+        .write_all(format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", origin_addr).as_bytes())
+        .expect("failed to write");
+    let mut received = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let n = stream.read(&mut buf).expect("failed to read");
+        if n == 0 {
+            break;
+        }
+        received.extend_from_slice(&buf[..n]);
+    }
+    drop(stream);
+    assert!(String::from_utf8(received)
+        .unwrap()
+        .ends_with("\r\n\r\ntunneled"));
+    assert_eq!(origin.join().unwrap(), 1);
+    assert_eq!(
+        proxy.join().unwrap(),
+        (format!("CONNECT {} HTTP/1.1", origin_addr), String::new())
+    );
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// An `openssl s_server` serving files from a scratch directory, stopped when dropped.
+struct TlsServer {
+    child: Child,
+    port: u16,
+    root: PathBuf,
+}
+
+impl Drop for TlsServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+// Starts a TLS 1.3 server. The tests that use it need an `openssl` binary on PATH, so they are
+// ignored by default and run with:
+//
+//   cargo test -p net_std --test pipeline -- --ignored
+fn serve_tls(name: &str, cert_args: &[String], files: &[(&str, &str)]) -> TlsServer {
+    let root = std::env::temp_dir().join(format!("saba-tls-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    for (path, contents) in files {
+        fs::write(root.join(path), contents).unwrap();
+    }
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let child = Command::new("openssl")
+        .arg("s_server")
+        .args([
+            "-accept",
+            &format!("127.0.0.1:{}", port),
+            "-tls1_3",
+            "-WWW",
+            "-quiet",
+        ])
+        .args(cert_args)
+        .current_dir(&root)
+        .env("OPENSSL_CONF", "/dev/null")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start openssl; is it installed?");
+    let server = TlsServer { child, port, root };
+
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            return server;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("openssl s_server did not start");
+}
+
+fn test_root_store() -> RootStore {
+    load_root_store(&fixture("ca.pem")).expect("failed to load the test CA")
+}
+
+fn fetch_https(root_store: RootStore, url: &str) -> Result<String, Error> {
+    let mut fetcher = HttpFetcher::new(tls_transport(root_store));
+    let request = HttpRequest::new(
+        Method::Get,
+        Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url"),
+    );
+    fetcher.fetch(&request).map(|response| response.body_text())
+}
+
+#[test]
+#[ignore = "needs openssl"]
+fn test_https_with_ecdsa_certificate() {
+    let server = serve_tls(
+        "ecdsa",
+        &[
+            "-cert".to_string(),
+            fixture("server-ec.pem"),
+            "-key".to_string(),
+            fixture("server-ec.key"),
+        ],
+        &[("hello.html", "<p>hello over tls</p>")],
+    );
+
+    let body = fetch_https(
+        test_root_store(),
+        &format!("https://localhost:{}/hello.html", server.port),
+    )
+    .expect("failed to fetch over TLS");
+    assert_eq!(body, "<p>hello over tls</p>");
+}
+
+#[test]
+#[ignore = "needs openssl"]
+fn test_https_with_rsa_certificate_chain() {
+    let server = serve_tls(
+        "rsa",
+        &[
+            "-cert".to_string(),
+            fixture("server-rsa.pem"),
+            "-key".to_string(),
+            fixture("server-rsa.key"),
+            "-cert_chain".to_string(),
+            fixture("intermediate.pem"),
+        ],
+        &[("page.txt", "chained")],
+    );
+
+    // An address literal is checked against the certificate's IP SAN and sent without SNI.
+    let body = fetch_https(
+        test_root_store(),
+        &format!("https://127.0.0.1:{}/page.txt", server.port),
+    )
+    .expect("failed to fetch over TLS");
+    assert_eq!(body, "chained");
+}
+
+#[test]
+#[ignore = "needs openssl"]
+fn test_https_rejects_untrusted_certificate() {
+    let server = serve_tls(
+        "untrusted",
+        &[
+            "-cert".to_string(),
+            fixture("server-ec.pem"),
+            "-key".to_string(),
+            fixture("server-ec.key"),
+        ],
+        &[("hello.html", "secret")],
+    );
+
+    let result = fetch_https(
+        RootStore::new(),
+        &format!("https://localhost:{}/hello.html", server.port),
+    );
+    assert!(
+        matches!(result, Err(Error::InvalidCertificate(_))),
+        "{:?}",
+        result
+    );
+}
+
+// A WebSocket echo server that mirrors every data frame back as it arrived and returns the
+// frames it received from the client.
+fn serve_websocket_echo() -> (SocketAddr, thread::JoinHandle<Vec<Frame>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a local port");
+    let addr = listener.local_addr().unwrap();
+
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut key = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Sec-WebSocket-Key:") {
+                key = value.trim().to_string();
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+Sec-WebSocket-Accept: {}\r\nSec-WebSocket-Protocol: echo\r\n\r\n",
+            accept_key(&key)
+        );
+        writer.write_all(response.as_bytes()).unwrap();
+
+        let mut frames = Vec::new();
+        let mut decoder = FrameReader::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let Some(frame) = decoder.next_frame().unwrap() else {
+                let n = reader.read(&mut buf).unwrap();
+                if n == 0 {
+                    return frames;
+                }
+                decoder.feed(&buf[..n]);
+                continue;
+            };
+            frames.push(frame.clone());
+            let reply = match frame.opcode() {
+                Opcode::Ping => Frame::new(true, Opcode::Pong, frame.payload()),
+                Opcode::Pong => continue,
+                _ => Frame::new(frame.fin(), frame.opcode(), frame.payload()),
+            };
+            writer.write_all(&reply.encode()).unwrap();
+            if frame.opcode() == Opcode::Close {
+                return frames;
+            }
+        }
+    });
+
+    (addr, handle)
+}
+
+#[test]
+fn test_websocket_echo() {
+    let (addr, server) = serve_websocket_echo();
+    let mut fetcher = HttpFetcher::new(StdTransport::new());
+    let url = Url::new(format!("ws://{}/echo", addr))
+        .parse()
+        .expect("failed to parse url");
+    let mut socket =
+        WebSocket::connect(&mut fetcher, &url, &["echo"], OsRandom::new()).expect("handshake");
+    assert_eq!(socket.protocol(), Some("echo".to_string()));
+
+    socket.send_text("hello").unwrap();
+    assert_eq!(
+        socket.receive().unwrap(),
+        Message::Text("hello".to_string())
+    );
+
+    // A message split into several frames comes back as one.
+    socket.set_max_frame_size(1000);
+    let data: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+    socket.send_binary(&data).unwrap();
+    assert_eq!(socket.receive().unwrap(), Message::Binary(data));
+
+    socket.ping(b"are you there").unwrap();
+    assert_eq!(
+        socket.receive().unwrap(),
+        Message::Pong(b"are you there".to_vec())
+    );
+
+    socket.close(CLOSE_GOING_AWAY, "done").unwrap();
+    assert!(socket.is_closed());
+
+    let frames = server.join().unwrap();
+    assert_eq!(frames.len(), 8);
+    assert!(frames.iter().all(|f| f.mask().is_some()));
+    assert_eq!(frames[7].opcode(), Opcode::Close);
+    assert_eq!(frames[7].payload(), b"\x03\xe9done".to_vec());
+}
+
+// A DNS stub that answers A queries for app.test with two loopback addresses, the first of which
+// has no server behind it. Returns the address and a count of the queries it received.
+fn serve_dns() -> (SocketAddr, Arc<AtomicUsize>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind a local port");
+    let addr = socket.local_addr().unwrap();
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+
+    thread::spawn(move || loop {
+        let mut buf = [0u8; 512];
+        let (n, peer) = socket.recv_from(&mut buf).unwrap();
+        counter.fetch_add(1, Ordering::SeqCst);
+        let query = &buf[..n];
+        let mut reply = query.to_vec();
+        reply[2] = 0x81;
+        reply[3] = 0x80;
+        let is_a = query[n - 4..n - 2] == [0, 1];
+        if query[12..].starts_with(b"\x03app\x04test\x00") && is_a {
+            reply[7] = 2;
+            for last_octet in [2, 1] {
+                reply.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 127, 0, 0]);
+                reply.push(last_octet);
+            }
+        }
+        socket.send_to(&reply, peer).unwrap();
+    });
+
+    (addr, queries)
+}
+
+#[test]
+fn test_dns_resolution_with_fallback_and_cache() {
+    let (dns_addr, queries) = serve_dns();
+    let (addr, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nfirst",
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecond",
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhosts",
+    ]);
+    let hosts = HostsTable::parse("127.0.0.1 pinned.test\n");
+    let mut fetcher = HttpFetcher::new(resolving_transport(dns_addr, hosts).unwrap());
+
+    let request = |host: &str| {
+        let url = Url::new(format!("http://{}:{}/", host, addr.port()))
+            .parse()
+            .expect("failed to parse url");
+        HttpRequest::new(Method::Get, url)
+    };
+    // 127.0.0.2 refuses the connection, so both fetches fall back to 127.0.0.1.
+    let first = fetcher
+        .fetch(&request("app.test"))
+        .expect("failed to fetch");
+    let second = fetcher
+        .fetch(&request("app.test"))
+        .expect("failed to fetch");
+    assert_eq!(first.body_text(), "first");
+    assert_eq!(second.body_text(), "second");
+    // One A and one AAAA query; the second fetch is answered from the cache.
+    assert_eq!(queries.load(Ordering::SeqCst), 2);
+
+    let pinned = fetcher
+        .fetch(&request("pinned.test"))
+        .expect("failed to fetch");
+    assert_eq!(pinned.body_text(), "hosts");
+    assert_eq!(queries.load(Ordering::SeqCst), 2);
+    assert_eq!(server.join().unwrap(), 3);
+
+    assert!(fetcher.fetch(&request("missing.test")).is_err());
+}
@@ -1,81 +1,131 @@
-pub struct HttpClient {}
-extern crate alloc;
+use crate::transport::{wasabi_transport, WasabiTransport};
 use alloc::format;
+use alloc::rc::Rc;
 use alloc::string::String;
-use alloc::string::ToString;
-use alloc::vec::Vec;
-use noli::net::lookup_host;
-use noli::net::SocketAddr;
-use noli::net::TcpStream;
+use core::cell::RefCell;
+use saba_nogtk_core::auth::CredentialProvider;
+use saba_nogtk_core::browser::Browser;
+use saba_nogtk_core::clock::Clock;
+use saba_nogtk_core::cookie::CookieJar;
+use saba_nogtk_core::dns::HostsTable;
 use saba_nogtk_core::error::Error;
-use saba_nogtk_core::http::HttpResponse;
+use saba_nogtk_core::fetch::{
+    AuthFetcher, CachingFetcher, CookieFetcher, Fetcher, HttpFetcher, RedirectFetcher,
+    RedirectPolicy, ResponseSink,
+};
+use saba_nogtk_core::http::{HttpRequest, HttpResponse, Method};
+use saba_nogtk_core::proxy::ProxyConfig;
+use saba_nogtk_core::random::RandomSource;
+use saba_nogtk_core::tls::{RootStore, TlsClient};
+use saba_nogtk_core::url::Url;
 
-impl HttpClient {
-    pub fn new() -> Self {
-        Self {}
-    }
-    pub fn get(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
-        let ips = match lookup_host(&host) {
-            Ok(ips) => ips,
-            Err(e) => {
-                return Err(Error::Network(format!(
-                    "Failed to find IP addresses: {:#?}",
-                    e
-                )))
-            }
-        };
+type ClientFetcher<R, C> = RedirectFetcher<
+    CachingFetcher<CookieFetcher<AuthFetcher<HttpFetcher<WasabiTransport<R, C>>>, C>, C>,
+>;
 
-        if ips.len() < 1 {
-            return Err(Error::Network("No IP addresses found".to_string()));
+pub struct HttpClient<R: RandomSource, C: Clock + Clone> {
+    fetcher: ClientFetcher<R, C>,
+}
+
+impl<R: RandomSource, C: Clock + Clone> HttpClient<R, C> {
+    // Requests carry and store the cookies of `browser`'s jar, are answered from its cache when
+    // they can be, and ask its credential provider when challenged. `clock` is the platform's
+    // wall-clock time, which decides when cookies expire, when cached responses go stale and
+    // whether certificates are valid. https servers must chain to `root_store`, and `random`
+    // supplies the TLS key material.
+    pub fn new(browser: &Browser, clock: C, random: R, root_store: RootStore) -> Self {
+        let tls_client = TlsClient::new(root_store, random, clock.clone());
+        Self {
+            fetcher: RedirectFetcher::new(
+                CachingFetcher::new(
+                    CookieFetcher::new(
+                        AuthFetcher::new(
+                            HttpFetcher::new(wasabi_transport(tls_client)),
+                            browser.credential_provider(),
+                        ),
+                        browser.cookie_jar(),
+                        clock.clone(),
+                    ),
+                    browser.http_cache(),
+                    clock,
+                ),
+                RedirectPolicy::default(),
+            ),
         }
+    }
 
-        let socket_addr: SocketAddr = (ips[0], port).into();
+    pub fn set_user_agent(&mut self, user_agent: &str) {
+        self.fetcher
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .set_user_agent(user_agent);
+    }
 
-        let mut stream = match TcpStream::connect(socket_addr) {
-            Ok(stream) => stream,
-            Err(_) => {
-                return Err(Error::Network(
-                    "Failed to connect to TCP stream".to_string(),
-                ))
-            }
-        };
+    pub fn set_proxy_config(&mut self, proxy_config: ProxyConfig) {
+        self.fetcher
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .set_proxy_config(proxy_config);
+    }
 
-        let mut request = String::from("GET /");
-        request.push_str(&path);
-        request.push_str(" HTTP/1.1\n");
-        request.push_str("Host: ");
-        request.push_str(&host);
-        request.push('\n');
-        request.push_str("Accept: text/html\n");
-        request.push_str("Connection: close\n");
-        request.push('\n');
+    // Names in the table are connected to without asking the OS resolver.
+    pub fn set_hosts_table(&mut self, hosts: HostsTable) {
+        self.fetcher
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .transport_mut()
+            .inner_mut()
+            .resolver_mut()
+            .set_hosts(hosts);
+    }
 
-        let _bytes_written = match stream.write(request.as_bytes()) {
-            Ok(bytes) => bytes,
-            Err(_) => return Err(Error::Network("Failed to write to TCP stream".to_string())),
+    pub fn set_credential_provider(
+        &mut self,
+        credential_provider: Rc<RefCell<dyn CredentialProvider>>,
+    ) {
+        self.fetcher
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .set_credential_provider(credential_provider);
+    }
+
+    pub fn set_cookie_jar(&mut self, cookie_jar: Rc<RefCell<CookieJar>>) {
+        self.fetcher
+            .inner_mut()
+            .inner_mut()
+            .set_cookie_jar(cookie_jar);
+    }
+
+    pub fn get(&mut self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
+        let url = match Url::new(format!("http://{}:{}/{}", host, port, path)).parse() {
+            Ok(url) => url,
+            Err(e) => return Err(Error::Network(format!("Invalid url: {:?}", e))),
         };
 
-        let mut received = Vec::new();
+        let mut request = HttpRequest::new(Method::Get, url);
+        request.add_header("Accept", "text/html")?;
+        self.send(&request)
+    }
 
-        loop {
-            let mut buf = [0u8, 255];
-            let bytes_read = match stream.read(&mut buf) {
-                Ok(bytes) => bytes,
-                Err(_) => {
-                    return Err(Error::Network(
-                        "Failed to receive a request from TCP stream".to_string(),
-                    ))
-                }
-            };
-            if bytes_read == 0 {
-                break;
-            }
-            received.extend_from_slice(&buf[..bytes_read]);
-        }
+    pub fn send(&mut self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        self.fetcher.fetch(request)
+    }
 
-        match core::str::from_utf8(&received) {
-            Ok(response) => HttpResponse::new(response.to_string()),
-            Err(e) => Err(Error::Network(format!("Invalid received response: {}", e))),
-        }
+    // Like `send`, but hands the response to `sink` while it is read from the socket. A `Page`
+    // given as the sink is painted before the last of the document arrives, and then needs
+    // `finish_response` with the returned response.
+    pub fn send_streaming(
+        &mut self,
+        request: &HttpRequest,
+        sink: &mut dyn ResponseSink,
+    ) -> Result<HttpResponse, Error> {
+        self.fetcher.fetch_streaming(request, sink)
     }
 }
@@ -1,3 +1,6 @@
 #![no_std]
 
+extern crate alloc;
+
 pub mod http;
+pub mod transport;
@@ -0,0 +1,125 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr};
+use noli::net::lookup_host;
+use noli::net::IpV4Addr;
+use noli::net::SocketAddr;
+use noli::net::TcpStream;
+use saba_nogtk_core::clock::Clock;
+use saba_nogtk_core::dns::{Answer, HostsResolver, HostsTable, Resolver};
+use saba_nogtk_core::error::Error;
+use saba_nogtk_core::random::RandomSource;
+use saba_nogtk_core::tls::{TlsClient, TlsTransport};
+use saba_nogtk_core::transport::{Connector, ResolvingTransport, Stream};
+
+pub struct WasabiStream {
+    stream: TcpStream,
+}
+
+impl Stream for WasabiStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.stream.read(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to receive a request from TCP stream".to_string(),
+            )),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self.stream.write(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network("Failed to write to TCP stream".to_string())),
+        }
+    }
+}
+
+pub struct WasabiConnector {}
+
+impl WasabiConnector {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for WasabiConnector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Connector for WasabiConnector {
+    fn connect_addr(&mut self, address: IpAddr, port: u16) -> Result<Box<dyn Stream>, Error> {
+        let ip = match address {
+            IpAddr::V4(ip) => IpV4Addr::new(ip.octets()),
+            IpAddr::V6(_) => {
+                return Err(Error::Network(format!(
+                    "IPv6 is not supported: {}",
+                    address
+                )))
+            }
+        };
+        let socket_addr: SocketAddr = (ip, port).into();
+
+        match TcpStream::connect(socket_addr) {
+            Ok(stream) => Ok(Box::new(WasabiStream { stream })),
+            Err(_) => Err(Error::Network(format!(
+                "Failed to connect to {}:{}",
+                address, port
+            ))),
+        }
+    }
+}
+
+// Asks the OS for addresses. It does not report TTLs, so answers are never cached.
+pub struct NoliResolver {}
+
+impl NoliResolver {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for NoliResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver for NoliResolver {
+    fn resolve(&mut self, name: &str) -> Result<Answer, Error> {
+        let ips = match lookup_host(name) {
+            Ok(ips) => ips,
+            Err(e) => {
+                return Err(Error::Network(format!(
+                    "Failed to find IP addresses: {:#?}",
+                    e
+                )))
+            }
+        };
+
+        let addresses: Vec<IpAddr> = ips
+            .iter()
+            .map(|ip| IpAddr::V4(Ipv4Addr::from(ip.bytes())))
+            .collect();
+        Ok(Answer::new(addresses, 0))
+    }
+}
+
+pub type WasabiTransport<R, C> =
+    TlsTransport<ResolvingTransport<WasabiConnector, HostsResolver<NoliResolver>>, R, C>;
+
+// A TCP transport that can also speak TLS through `tls_client`.
+pub fn wasabi_transport<R: RandomSource, C: Clock>(
+    tls_client: TlsClient<R, C>,
+) -> WasabiTransport<R, C> {
+    TlsTransport::new(
+        ResolvingTransport::new(
+            WasabiConnector::new(),
+            HostsResolver::new(HostsTable::new(), NoliResolver::new()),
+        ),
+        tls_client,
+    )
+}
@@ -0,0 +1,76 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Accepts both the standard and the URL-safe alphabet, and ignores padding and line breaks.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' | b'\r' | b'\n' | b' ' => continue,
+            _ => return None,
+        };
+        buf = buf << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "".to_string());
+        assert_eq!(encode(b"f"), "Zg==".to_string());
+        assert_eq!(encode(b"fo"), "Zm8=".to_string());
+        assert_eq!(encode(b"foo"), "Zm9v".to_string());
+        assert_eq!(
+            encode(b"Aladdin:open sesame"),
+            "QWxhZGRpbjpvcGVuIHNlc2FtZQ==".to_string()
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("Zm9vYg=="), Some(b"foob".to_vec()));
+        assert_eq!(decode("Zm9v\r\nYmFy"), Some(b"foobar".to_vec()));
+        assert_eq!(decode("-_8="), Some([0xfb, 0xff].to_vec()));
+        assert_eq!(decode("Zm9v!"), None);
+    }
+}
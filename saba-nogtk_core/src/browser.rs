@@ -1,13 +1,20 @@
 use core::cell::RefCell;
 
-use alloc::{rc::Rc, vec::Vec};
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
 
+use crate::auth::{CredentialProvider, CredentialStore};
+use crate::cache::HttpCache;
+use crate::cookie::CookieJar;
 use crate::renderer::page::Page;
 
 #[derive(Debug, Clone)]
 pub struct Browser {
     active_page_index: usize,
     pages: Vec<Rc<RefCell<Page>>>,
+    cookie_jar: Rc<RefCell<CookieJar>>,
+    http_cache: Rc<RefCell<HttpCache>>,
+    // Boxed so that a provider of another type can be put in place of the current one.
+    credential_provider: Rc<RefCell<Box<dyn CredentialProvider>>>,
 }
 
 impl Browser {
@@ -17,6 +24,9 @@ impl Browser {
         let browser = Rc::new(RefCell::new(Self {
             active_page_index: 0,
             pages: Vec::new(),
+            cookie_jar: Rc::new(RefCell::new(CookieJar::new())),
+            http_cache: Rc::new(RefCell::new(HttpCache::new())),
+            credential_provider: Rc::new(RefCell::new(Box::new(CredentialStore::new()))),
         }));
 
         page.set_browser(Rc::downgrade(&browser));
@@ -28,4 +38,30 @@ impl Browser {
     pub fn current_page(&self) -> Rc<RefCell<Page>> {
         self.pages[self.active_page_index].clone()
     }
+
+    pub fn cookie_jar(&self) -> Rc<RefCell<CookieJar>> {
+        self.cookie_jar.clone()
+    }
+
+    pub fn set_cookie_jar(&mut self, cookie_jar: CookieJar) {
+        *self.cookie_jar.borrow_mut() = cookie_jar;
+    }
+
+    pub fn http_cache(&self) -> Rc<RefCell<HttpCache>> {
+        self.http_cache.clone()
+    }
+
+    pub fn set_http_cache(&mut self, http_cache: HttpCache) {
+        *self.http_cache.borrow_mut() = http_cache;
+    }
+
+    pub fn credential_provider(&self) -> Rc<RefCell<dyn CredentialProvider>> {
+        self.credential_provider.clone()
+    }
+
+    // Like the cookie jar and the cache, the provider is overwritten in place, so fetchers
+    // created earlier ask the new one.
+    pub fn set_credential_provider(&mut self, credential_provider: Box<dyn CredentialProvider>) {
+        *self.credential_provider.borrow_mut() = credential_provider;
+    }
 }
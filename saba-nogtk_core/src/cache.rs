@@ -0,0 +1,585 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::cookie::parse_cookie_date;
+use crate::error::Error;
+use crate::fetch::url_without_fragment;
+use crate::http::{Headers, HttpRequest, HttpResponse, Method};
+use crate::url::Url;
+
+const DEFAULT_MAX_ENTRIES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum CacheLookup {
+    Fresh(HttpResponse),
+    // A stored response that has to be revalidated with the origin server before use.
+    Stale(HttpResponse),
+    Miss,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheControl {
+    directives: Vec<(String, Option<String>)>,
+}
+
+impl CacheControl {
+    fn new(headers: &Headers) -> Self {
+        let mut directives = Vec::new();
+        for value in headers.get_all("Cache-Control") {
+            for directive in value.split(',') {
+                let directive = directive.trim();
+                if directive.is_empty() {
+                    continue;
+                }
+                let (name, value) = match directive.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                    None => (directive, None),
+                };
+                directives.push((name.to_ascii_lowercase(), value.map(|v| v.to_string())));
+            }
+        }
+        Self { directives }
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.directives.iter().any(|(n, _)| n == name)
+    }
+
+    fn seconds(&self, name: &str) -> Option<u64> {
+        self.directives
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| value.as_ref())
+            .and_then(|value| value.parse::<u64>().ok())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    key: String,
+    response: HttpResponse,
+    request_time: u64,
+    response_time: u64,
+}
+
+impl CacheEntry {
+    fn date(&self) -> u64 {
+        self.response
+            .header_value("Date")
+            .and_then(|date| parse_cookie_date(&date))
+            .unwrap_or(self.response_time)
+    }
+
+    // Computes the freshness lifetime as described in RFC 9111 section 4.2.1.
+    fn freshness_lifetime(&self) -> u64 {
+        let headers = self.response.headers();
+        if let Some(max_age) = CacheControl::new(&headers).seconds("max-age") {
+            return max_age;
+        }
+        if let Some(expires) = headers.get("Expires") {
+            // An invalid Expires value such as "0" means the response is already stale.
+            return match parse_cookie_date(&expires) {
+                Some(expires) => expires.saturating_sub(self.date()),
+                None => 0,
+            };
+        }
+        if let Some(last_modified) = headers
+            .get("Last-Modified")
+            .and_then(|date| parse_cookie_date(&date))
+        {
+            if is_heuristically_cacheable(self.response.status_code()) {
+                return self.date().saturating_sub(last_modified) / 10;
+            }
+        }
+        0
+    }
+
+    // Computes the current age as described in RFC 9111 section 4.2.3.
+    fn current_age(&self, now: u64) -> u64 {
+        let apparent_age = self.response_time.saturating_sub(self.date());
+        let age_value = self
+            .response
+            .header_value("Age")
+            .and_then(|age| age.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        let response_delay = self.response_time.saturating_sub(self.request_time);
+        // Age comes from the server, so it may be anything up to u64::MAX.
+        let corrected_initial_age =
+            core::cmp::max(apparent_age, age_value.saturating_add(response_delay));
+        corrected_initial_age.saturating_add(now.saturating_sub(self.response_time))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    entries: Vec<CacheEntry>,
+    max_entries: usize,
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn lookup(&self, request: &HttpRequest, now: u64) -> CacheLookup {
+        if request.method() != Method::Get {
+            return CacheLookup::Miss;
+        }
+        let request_cache_control = CacheControl::new(&request.headers());
+        if request_cache_control.has("no-store") {
+            return CacheLookup::Miss;
+        }
+        let entry = match self.find(&request.url()) {
+            Some(entry) => entry,
+            None => return CacheLookup::Miss,
+        };
+
+        let current_age = entry.current_age(now);
+        let response_cache_control = CacheControl::new(&entry.response.headers());
+        let pragma_no_cache = request
+            .header_value("Pragma")
+            .map_or(false, |v| v.to_ascii_lowercase().contains("no-cache"));
+        let fresh = entry.freshness_lifetime() > current_age
+            && !response_cache_control.has("no-cache")
+            && !request_cache_control.has("no-cache")
+            && !pragma_no_cache
+            && request_cache_control
+                .seconds("max-age")
+                .map_or(true, |max_age| current_age <= max_age);
+
+        let response = entry.response.clone();
+        if fresh {
+            CacheLookup::Fresh(response)
+        } else {
+            CacheLookup::Stale(response)
+        }
+    }
+
+    // Stores a response to a GET request when it may be reused. `request_time` and `now` are the
+    // times the request was sent and the response was received.
+    pub fn store(
+        &mut self,
+        request: &HttpRequest,
+        response: &HttpResponse,
+        request_time: u64,
+        now: u64,
+    ) {
+        if request.method() != Method::Get {
+            return;
+        }
+        let key = url_without_fragment(&request.url());
+        let request_cache_control = CacheControl::new(&request.headers());
+        let response_cache_control = CacheControl::new(&response.headers());
+        if request_cache_control.has("no-store") || response_cache_control.has("no-store") {
+            self.entries.retain(|e| e.key != key);
+            return;
+        }
+        if !is_storable(response, &response_cache_control) {
+            return;
+        }
+
+        let entry = CacheEntry {
+            key: key.clone(),
+            response: response.clone(),
+            request_time,
+            response_time: now,
+        };
+        match self.entries.iter().position(|e| e.key == key) {
+            Some(index) => self.entries[index] = entry,
+            None => self.entries.push(entry),
+        }
+        self.evict();
+    }
+
+    // Updates a stored response with the header fields of a 304 response and returns it.
+    pub fn freshen(
+        &mut self,
+        request: &HttpRequest,
+        not_modified: &HttpResponse,
+        request_time: u64,
+        now: u64,
+    ) -> Option<HttpResponse> {
+        let key = url_without_fragment(&request.url());
+        let entry = self.entries.iter_mut().find(|e| e.key == key)?;
+
+        // The framing of the stored body stays as it is.
+        let updated: Vec<_> = not_modified
+            .headers()
+            .iter()
+            .filter(|h| {
+                let name = h.name();
+                !name.eq_ignore_ascii_case("Content-Length")
+                    && !name.eq_ignore_ascii_case("Content-Encoding")
+                    && !name.eq_ignore_ascii_case("Transfer-Encoding")
+            })
+            .cloned()
+            .collect();
+        let mut headers = entry.response.headers();
+        for header in &updated {
+            headers.remove(&header.name());
+        }
+        for header in &updated {
+            headers.append(&header.name(), &header.value());
+        }
+        entry.response.set_headers(headers);
+        entry.request_time = request_time;
+        entry.response_time = now;
+        Some(entry.response.clone())
+    }
+
+    pub fn invalidate(&mut self, url: &Url) {
+        let key = url_without_fragment(url);
+        self.entries.retain(|e| e.key != key);
+    }
+
+    // Serializes every entry as a tab-separated line of the URL, the request and response times
+    // and the length of the raw response, followed by the raw response itself.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = b"# saba-nogtk http cache\n".to_vec();
+        for entry in &self.entries {
+            let response = &entry.response;
+            let mut raw = format!(
+                "{} {} {}\r\n",
+                response.version(),
+                response.status_code(),
+                response.reason()
+            )
+            .into_bytes();
+            for header in response.headers().iter() {
+                raw.extend_from_slice(
+                    format!("{}: {}\r\n", header.name(), header.value()).as_bytes(),
+                );
+            }
+            raw.extend_from_slice(b"\r\n");
+            raw.extend_from_slice(&response.body());
+
+            out.extend_from_slice(
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    entry.key,
+                    entry.request_time,
+                    entry.response_time,
+                    raw.len()
+                )
+                .as_bytes(),
+            );
+            out.extend_from_slice(&raw);
+            out.push(b'\n');
+        }
+        out
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::UnexpectedInput("invalid http cache data".to_string());
+
+        let mut cache = Self::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let end = match data[pos..].iter().position(|b| *b == b'\n') {
+                Some(i) => pos + i,
+                None => return Err(invalid()),
+            };
+            let line = core::str::from_utf8(&data[pos..end]).map_err(|_| invalid())?;
+            pos = end + 1;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return Err(invalid());
+            }
+            let request_time = fields[1].parse::<u64>().map_err(|_| invalid())?;
+            let response_time = fields[2].parse::<u64>().map_err(|_| invalid())?;
+            let len = fields[3].parse::<usize>().map_err(|_| invalid())?;
+            if data.len() - pos < len {
+                return Err(invalid());
+            }
+
+            let mut response = HttpResponse::new(&data[pos..pos + len])?;
+            if let Ok(url) = Url::new(fields[0].to_string()).parse() {
+                response.set_url(url);
+            }
+            pos += len + 1;
+
+            cache.entries.push(CacheEntry {
+                key: fields[0].to_string(),
+                response,
+                request_time,
+                response_time,
+            });
+        }
+        Ok(cache)
+    }
+
+    fn find(&self, url: &Url) -> Option<&CacheEntry> {
+        let key = url_without_fragment(url);
+        self.entries.iter().find(|e| e.key == key)
+    }
+
+    fn evict(&mut self) {
+        if self.entries.len() > self.max_entries {
+            let excess = self.entries.len() - self.max_entries;
+            self.entries.drain(..excess);
+        }
+    }
+}
+
+impl Default for HttpCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_heuristically_cacheable(status_code: u32) -> bool {
+    matches!(
+        status_code,
+        200 | 203 | 204 | 206 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+fn is_storable(response: &HttpResponse, cache_control: &CacheControl) -> bool {
+    let status_code = response.status_code();
+    if !is_heuristically_cacheable(status_code) && status_code != 302 && status_code != 307 {
+        return false;
+    }
+
+    // Stored responses are not keyed by request headers. Bodies are stored decoded, so only
+    // Accept-Encoding may appear in Vary.
+    let varies = response
+        .headers()
+        .get_all("Vary")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .any(|name| !name.eq_ignore_ascii_case("Accept-Encoding"));
+    if varies {
+        return false;
+    }
+
+    let headers = response.headers();
+    cache_control.has("max-age")
+        || headers.contains("Expires")
+        || headers.contains("ETag")
+        || headers.contains("Last-Modified")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::new(
+            Method::Get,
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    fn response(raw: &str) -> HttpResponse {
+        HttpResponse::new(raw.as_bytes()).expect("failed to parse http response")
+    }
+
+    #[test]
+    fn test_max_age_freshness() {
+        let mut cache = HttpCache::new();
+        let request = get("http://example.com/style.css#top");
+        cache.store(
+            &request,
+            &response(
+                "HTTP/1.1 200 OK\r\nCache-Control: public, max-age=60\r\nAge: 10\r\n\r\nbody",
+            ),
+            100,
+            100,
+        );
+        assert_eq!(cache.len(), 1);
+
+        match cache.lookup(&get("http://example.com/style.css"), 149) {
+            CacheLookup::Fresh(response) => assert_eq!(response.body_text(), "body"),
+            other => panic!("expected a fresh response: {:?}", other),
+        }
+        assert!(matches!(cache.lookup(&request, 150), CacheLookup::Stale(_)));
+        assert!(matches!(
+            cache.lookup(&get("http://example.com/other.css"), 100),
+            CacheLookup::Miss
+        ));
+
+        let mut no_cache = get("http://example.com/style.css");
+        no_cache.add_header("Cache-Control", "no-cache").unwrap();
+        assert!(matches!(
+            cache.lookup(&no_cache, 100),
+            CacheLookup::Stale(_)
+        ));
+    }
+
+    #[test]
+    fn test_huge_age_is_stale() {
+        let mut cache = HttpCache::new();
+        let request = get("http://example.com/");
+        cache.store(
+            &request,
+            &response(
+                "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nAge: 18446744073709551615\r\n\r\nbody",
+            ),
+            100,
+            110,
+        );
+        assert!(matches!(cache.lookup(&request, 120), CacheLookup::Stale(_)));
+
+        let data = b"http://example.com/\t100\t100\t18446744073709551615\nHTTP/1.1 200 OK\r\n\r\n";
+        assert!(HttpCache::deserialize(data).is_err());
+    }
+
+    #[test]
+    fn test_expires_and_heuristic_freshness() {
+        let mut cache = HttpCache::new();
+        let request = get("http://example.com/");
+        cache.store(
+            &request,
+            &response("HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:01:40 GMT\r\nExpires: Thu, 01 Jan 1970 00:02:00 GMT\r\n\r\n"),
+            100,
+            100,
+        );
+        assert!(matches!(cache.lookup(&request, 119), CacheLookup::Fresh(_)));
+        assert!(matches!(cache.lookup(&request, 120), CacheLookup::Stale(_)));
+
+        cache.store(
+            &request,
+            &response("HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:16:40 GMT\r\nLast-Modified: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n"),
+            1000,
+            1000,
+        );
+        assert!(matches!(
+            cache.lookup(&request, 1099),
+            CacheLookup::Fresh(_)
+        ));
+        assert!(matches!(
+            cache.lookup(&request, 1100),
+            CacheLookup::Stale(_)
+        ));
+    }
+
+    #[test]
+    fn test_not_storable() {
+        let mut cache = HttpCache::new();
+        let request = get("http://example.com/");
+        cache.store(
+            &request,
+            &response("HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\r\n"),
+            0,
+            0,
+        );
+        cache.store(
+            &request,
+            &response("HTTP/1.1 200 OK\r\nCache-Control: no-store, max-age=60\r\n\r\n"),
+            0,
+            0,
+        );
+        assert!(cache.is_empty());
+
+        for raw in [
+            "HTTP/1.1 200 OK\r\n\r\n",
+            "HTTP/1.1 500 Internal Server Error\r\nCache-Control: max-age=60\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nVary: Cookie\r\n\r\n",
+        ] {
+            cache.store(&request, &response(raw), 0, 0);
+        }
+        cache.store(
+            &HttpRequest::new(Method::Post, request.url()),
+            &response("HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\r\n"),
+            0,
+            0,
+        );
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_freshen_with_not_modified() {
+        let mut cache = HttpCache::new();
+        let request = get("http://example.com/");
+        cache.store(
+            &request,
+            &response("HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nCache-Control: no-cache\r\nContent-Length: 4\r\n\r\nbody"),
+            0,
+            0,
+        );
+        assert!(matches!(cache.lookup(&request, 0), CacheLookup::Stale(_)));
+
+        let freshened = cache
+            .freshen(
+                &request,
+                &response("HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nCache-Control: max-age=30\r\nContent-Length: 0\r\n\r\n"),
+                10,
+                10,
+            )
+            .expect("failed to freshen");
+        assert_eq!(freshened.body_text(), "body");
+        assert_eq!(freshened.status_code(), 200);
+        assert_eq!(
+            freshened.header_value("Content-Length"),
+            Some("4".to_string())
+        );
+        assert!(matches!(cache.lookup(&request, 39), CacheLookup::Fresh(_)));
+    }
+
+    #[test]
+    fn test_serialize_and_evict() {
+        let mut cache = HttpCache::new();
+        for path in ["/a", "/b", "/c"] {
+            cache.store(
+                &get(&format!("http://example.com{}", path)),
+                &response("HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\r\nline1\nline2"),
+                5,
+                6,
+            );
+        }
+
+        let restored = HttpCache::deserialize(&cache.serialize()).expect("failed to deserialize");
+        assert_eq!(restored.len(), 3);
+        match restored.lookup(&get("http://example.com/b"), 10) {
+            CacheLookup::Fresh(response) => {
+                assert_eq!(response.body_text(), "line1\nline2");
+                assert_eq!(
+                    response.url().map(|u| u.href()),
+                    Some("http://example.com/b".to_string())
+                );
+            }
+            other => panic!("expected a fresh response: {:?}", other),
+        }
+        assert!(HttpCache::deserialize(b"http://example.com/\t0\t0\t100\nshort").is_err());
+
+        cache.set_max_entries(2);
+        assert!(matches!(
+            cache.lookup(&get("http://example.com/a"), 10),
+            CacheLookup::Miss
+        ));
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,21 @@
+pub trait Clock {
+    // Returns the current time in seconds since the Unix epoch.
+    fn now(&self) -> u64;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FixedClock {
+    now: u64,
+}
+
+impl FixedClock {
+    pub fn new(now: u64) -> Self {
+        Self { now }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.now
+    }
+}
@@ -0,0 +1,705 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::http::HttpResponse;
+use crate::url::{Host, Url};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("strict") {
+            Some(SameSite::Strict)
+        } else if value.eq_ignore_ascii_case("lax") {
+            Some(SameSite::Lax)
+        } else if value.eq_ignore_ascii_case("none") {
+            Some(SameSite::None)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    expires: Option<u64>,
+    secure: bool,
+    http_only: bool,
+    same_site: SameSite,
+}
+
+impl Cookie {
+    // Parses a Set-Cookie header value received from `url` as described in RFC 6265 section
+    // 5.2 and 5.3. `now` is used to turn Max-Age into an absolute expiry time.
+    pub fn parse(set_cookie: &str, url: &Url, now: u64) -> Result<Self, Error> {
+        let invalid = |reason: &str| {
+            Error::UnexpectedInput(format!("invalid Set-Cookie {:?}: {}", set_cookie, reason))
+        };
+
+        if set_cookie.chars().any(|c| c.is_ascii_control()) {
+            return Err(invalid("contains a control character"));
+        }
+
+        let mut parts = set_cookie.split(';');
+        let (name, value) = match parts.next().and_then(|pair| pair.split_once('=')) {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(invalid("missing name-value pair")),
+        };
+        if name.is_empty() {
+            return Err(invalid("empty cookie name"));
+        }
+
+        let mut expires = None;
+        let mut max_age = None;
+        let mut domain = None;
+        let mut path = None;
+        let mut secure = false;
+        let mut http_only = false;
+        let mut same_site = SameSite::Lax;
+        for attribute in parts {
+            let (attribute_name, attribute_value) = match attribute.split_once('=') {
+                Some((n, v)) => (n.trim(), v.trim()),
+                None => (attribute.trim(), ""),
+            };
+
+            if attribute_name.eq_ignore_ascii_case("expires") {
+                if let Some(time) = parse_cookie_date(attribute_value) {
+                    expires = Some(time);
+                }
+            } else if attribute_name.eq_ignore_ascii_case("max-age") {
+                if let Ok(delta) = attribute_value.parse::<i64>() {
+                    max_age = Some(if delta <= 0 {
+                        0
+                    } else {
+                        now.saturating_add(delta as u64)
+                    });
+                }
+            } else if attribute_name.eq_ignore_ascii_case("domain") {
+                let value = attribute_value.trim_start_matches('.');
+                if !value.is_empty() {
+                    domain = Some(value.to_ascii_lowercase());
+                }
+            } else if attribute_name.eq_ignore_ascii_case("path") {
+                if attribute_value.starts_with('/') {
+                    path = Some(attribute_value.to_string());
+                }
+            } else if attribute_name.eq_ignore_ascii_case("secure") {
+                secure = true;
+            } else if attribute_name.eq_ignore_ascii_case("httponly") {
+                http_only = true;
+            } else if attribute_name.eq_ignore_ascii_case("samesite") {
+                if let Some(value) = SameSite::parse(attribute_value) {
+                    same_site = value;
+                }
+            }
+        }
+
+        let host = url.host().to_ascii_lowercase();
+        let (domain, host_only) = match domain {
+            Some(domain) => {
+                if !domain_match(&host, &domain) {
+                    return Err(invalid("Domain does not match the request host"));
+                }
+                // Without a public suffix list, at least refuse cookies for a top-level domain.
+                if domain != host && !domain.contains('.') {
+                    return Err(invalid("Domain is a top-level domain"));
+                }
+                (domain, false)
+            }
+            None => (host, true),
+        };
+
+        if secure && !is_secure_scheme(url) {
+            return Err(invalid("Secure cookie from an insecure origin"));
+        }
+        if same_site == SameSite::None && !secure {
+            return Err(invalid("SameSite=None requires Secure"));
+        }
+
+        let path = path.unwrap_or_else(|| default_path(url));
+        if name.starts_with("__Secure-") && !secure {
+            return Err(invalid("__Secure- prefix requires Secure"));
+        }
+        if name.starts_with("__Host-") && (!secure || !host_only || path != "/") {
+            return Err(invalid(
+                "__Host- prefix requires Secure, no Domain and Path=/",
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain,
+            host_only,
+            path,
+            expires: max_age.or(expires),
+            secure,
+            http_only,
+            same_site,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    pub fn domain(&self) -> String {
+        self.domain.clone()
+    }
+
+    pub fn host_only(&self) -> bool {
+        self.host_only
+    }
+
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn same_site(&self) -> SameSite {
+        self.same_site
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now)
+    }
+
+    fn matches(&self, url: &Url, site_for_cookies: Option<&Url>, now: u64) -> bool {
+        let host = url.host().to_ascii_lowercase();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        if !domain_matches || self.is_expired(now) || !path_match(&url.path(), &self.path) {
+            return false;
+        }
+        if self.secure && !is_secure_scheme(url) {
+            return false;
+        }
+
+        // A request without a site for cookies is a top-level navigation started by the user,
+        // which may carry every cookie. Otherwise Strict and Lax cookies stay on their own site.
+        match site_for_cookies {
+            Some(site) if !is_same_site(url, site) => self.same_site == SameSite::None,
+            _ => true,
+        }
+    }
+
+    fn is_same_entry(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self {
+            cookies: Vec::new(),
+        }
+    }
+
+    pub fn cookies(&self) -> Vec<Cookie> {
+        self.cookies.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    pub fn insert(&mut self, cookie: Cookie, now: u64) {
+        // A replaced cookie keeps its position so that the creation order used to sort the
+        // Cookie header is preserved.
+        match self.cookies.iter().position(|c| c.is_same_entry(&cookie)) {
+            Some(index) if cookie.is_expired(now) => {
+                self.cookies.remove(index);
+            }
+            Some(index) => self.cookies[index] = cookie,
+            None if cookie.is_expired(now) => {}
+            None => self.cookies.push(cookie),
+        }
+    }
+
+    pub fn set_cookie(&mut self, set_cookie: &str, url: &Url, now: u64) -> Result<(), Error> {
+        let cookie = Cookie::parse(set_cookie, url, now)?;
+        self.insert(cookie, now);
+        Ok(())
+    }
+
+    // Stores every valid Set-Cookie header of `response`. Invalid ones are ignored just like
+    // other browsers do.
+    pub fn store_response(&mut self, url: &Url, response: &HttpResponse, now: u64) {
+        for set_cookie in response.headers().get_all("Set-Cookie") {
+            let _ = self.set_cookie(&set_cookie, url, now);
+        }
+    }
+
+    pub fn remove_expired(&mut self, now: u64) {
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+
+    pub fn cookie_header(
+        &self,
+        url: &Url,
+        site_for_cookies: Option<&Url>,
+        now: u64,
+    ) -> Option<String> {
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| c.matches(url, site_for_cookies, now))
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+
+        // Longer paths come first. The sort is stable, so cookies with the same path length stay
+        // in creation order.
+        cookies.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    // Serializes the jar into one line per cookie with tab-separated fields. Session cookies are
+    // kept as well so that a login session survives a restart.
+    pub fn serialize(&self) -> String {
+        let mut s = String::from("# saba-nogtk cookie jar\n");
+        for c in &self.cookies {
+            let expires = match c.expires {
+                Some(expires) => expires.to_string(),
+                None => String::new(),
+            };
+            s.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                c.domain,
+                bool_str(c.host_only),
+                c.path,
+                bool_str(c.secure),
+                bool_str(c.http_only),
+                c.same_site.as_str(),
+                expires,
+                c.name,
+                c.value
+            ));
+        }
+        s
+    }
+
+    pub fn deserialize(s: &str) -> Result<Self, Error> {
+        let mut jar = Self::new();
+        for line in s.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::UnexpectedInput(format!("invalid cookie jar line: {}", line));
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 9 {
+                return Err(invalid());
+            }
+            let expires = match fields[6] {
+                "" => None,
+                expires => Some(expires.parse::<u64>().map_err(|_| invalid())?),
+            };
+            jar.cookies.push(Cookie {
+                domain: fields[0].to_string(),
+                host_only: parse_bool(fields[1]).ok_or_else(invalid)?,
+                path: fields[2].to_string(),
+                secure: parse_bool(fields[3]).ok_or_else(invalid)?,
+                http_only: parse_bool(fields[4]).ok_or_else(invalid)?,
+                same_site: SameSite::parse(fields[5]).ok_or_else(invalid)?,
+                expires,
+                name: fields[7].to_string(),
+                value: fields[8].to_string(),
+            });
+        }
+        Ok(jar)
+    }
+}
+
+fn bool_str(b: bool) -> &'static str {
+    if b {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "TRUE" => Some(true),
+        "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_secure_scheme(url: &Url) -> bool {
+    matches!(url.scheme().as_str(), "https" | "wss")
+}
+
+fn is_ip_address(url: &Url) -> bool {
+    matches!(url.host_kind(), Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)))
+}
+
+// Approximates the registrable domain with the last two labels since there is no public suffix
+// list available.
+fn site(url: &Url) -> String {
+    let host = url.host().to_ascii_lowercase();
+    if is_ip_address(url) {
+        return host;
+    }
+    let labels: Vec<&str> = host.rsplitn(3, '.').collect();
+    if labels.len() < 2 {
+        return host;
+    }
+    format!("{}.{}", labels[1], labels[0])
+}
+
+fn is_same_site(a: &Url, b: &Url) -> bool {
+    is_secure_scheme(a) == is_secure_scheme(b) && site(a) == site(b)
+}
+
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    host.ends_with(domain)
+        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+        && !host.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && !host.starts_with('[')
+}
+
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    let request_path = if request_path.is_empty() {
+        "/"
+    } else {
+        request_path
+    };
+    if request_path == cookie_path {
+        return true;
+    }
+    request_path.starts_with(cookie_path)
+        && (cookie_path.ends_with('/') || request_path.as_bytes()[cookie_path.len()] == b'/')
+}
+
+// Parses a cookie-date as described in RFC 6265 section 5.1.1 and returns it as seconds since
+// the Unix epoch. Dates before the epoch are clamped to 0.
+pub(crate) fn parse_cookie_date(s: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let is_delimiter = |c: char| {
+        c == '\t'
+            || (' '..='/').contains(&c)
+            || (';'..='@').contains(&c)
+            || ('['..='`').contains(&c)
+            || ('{'..='~').contains(&c)
+    };
+
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    for token in s.split(is_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            if let Some(t) = parse_time(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(d) = parse_digits(token, 1, 2) {
+                day = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_ascii_lowercase();
+            if let Some(m) = MONTHS.iter().position(|m| *m == prefix) {
+                month = Some(m as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(y) = parse_digits(token, 2, 4) {
+                year = Some(y);
+                continue;
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+    if (70..=99).contains(&year) {
+        year += 1900;
+    } else if year <= 69 {
+        year += 2000;
+    }
+    if year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    if day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month as i64, day as i64);
+    let seconds = days * 86400 + (hour * 3600 + minute * 60 + second) as i64;
+    Some(if seconds < 0 { 0 } else { seconds as u64 })
+}
+
+// Parses `min..=max` leading digits that are not followed by another digit.
+fn parse_digits(token: &str, min: usize, max: usize) -> Option<u32> {
+    let len = token.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if len < min || len > max {
+        return None;
+    }
+    token[..len].parse().ok()
+}
+
+fn parse_time(token: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = token.splitn(3, ':');
+    let hour = parts.next()?;
+    let minute = parts.next()?;
+    let second = parts.next()?;
+    if hour.len() > 2
+        || minute.len() > 2
+        || !hour
+            .bytes()
+            .chain(minute.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    Some((
+        parse_digits(hour, 1, 2)?,
+        parse_digits(minute, 1, 2)?,
+        parse_digits(second, 1, 2)?,
+    ))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Returns the number of days since 1970-01-01 of a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_url(url: &str) -> Url {
+        Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url")
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let url = parse_url("https://www.example.com/account/login");
+        let cookie = Cookie::parse(
+            "sid=abc123; Domain=.Example.com; Path=/account; Max-Age=60; Secure; HttpOnly; SameSite=Strict",
+            &url,
+            1000,
+        )
+        .expect("failed to parse cookie");
+        assert_eq!(cookie.name(), "sid");
+        assert_eq!(cookie.value(), "abc123");
+        assert_eq!(cookie.domain(), "example.com");
+        assert!(!cookie.host_only());
+        assert_eq!(cookie.path(), "/account");
+        assert_eq!(cookie.expires(), Some(1060));
+        assert!(cookie.secure());
+        assert!(cookie.http_only());
+        assert_eq!(cookie.same_site(), SameSite::Strict);
+
+        let cookie = Cookie::parse("lang=ja", &url, 1000).expect("failed to parse cookie");
+        assert_eq!(cookie.domain(), "www.example.com");
+        assert!(cookie.host_only());
+        assert_eq!(cookie.path(), "/account");
+        assert_eq!(cookie.expires(), None);
+        assert_eq!(cookie.same_site(), SameSite::Lax);
+    }
+
+    #[test]
+    fn test_reject_invalid_cookies() {
+        let url = parse_url("http://www.example.com/");
+        assert!(Cookie::parse("no-equals-sign", &url, 0).is_err());
+        assert!(Cookie::parse("=value", &url, 0).is_err());
+        assert!(Cookie::parse("a=b; Domain=other.com", &url, 0).is_err());
+        assert!(Cookie::parse("a=b; Domain=com", &url, 0).is_err());
+        assert!(Cookie::parse("a=b; Secure", &url, 0).is_err());
+        assert!(Cookie::parse("a=b; SameSite=None", &url, 0).is_err());
+
+        let url = parse_url("https://www.example.com/");
+        assert!(Cookie::parse("__Host-a=b; Secure; Domain=example.com", &url, 0).is_err());
+        assert!(Cookie::parse("__Host-a=b; Secure; Path=/", &url, 0).is_ok());
+    }
+
+    #[test]
+    fn test_parse_cookie_date() {
+        assert_eq!(
+            parse_cookie_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(1445412480)
+        );
+        assert_eq!(
+            parse_cookie_date("Thursday, 01-Jan-70 00:00:01 GMT"),
+            Some(1)
+        );
+        assert_eq!(
+            parse_cookie_date("Sun Nov  6 08:49:37 1994"),
+            Some(784111777)
+        );
+        assert_eq!(parse_cookie_date("31 Feb 2020 00:00:00"), None);
+        assert_eq!(parse_cookie_date("not a date"), None);
+    }
+
+    #[test]
+    fn test_cookie_header() {
+        let mut jar = CookieJar::new();
+        let login = parse_url("https://app.example.com/login");
+        jar.set_cookie("sid=1; Path=/; Secure", &login, 0).unwrap();
+        jar.set_cookie("theme=dark; Domain=example.com; Path=/", &login, 0)
+            .unwrap();
+        jar.set_cookie("pref=a; Path=/docs", &login, 0).unwrap();
+        jar.set_cookie("strict=1; SameSite=Strict", &login, 0)
+            .unwrap();
+
+        let docs = parse_url("https://app.example.com/docs/index.html");
+        assert_eq!(
+            jar.cookie_header(&docs, None, 0),
+            Some("pref=a; sid=1; theme=dark; strict=1".to_string())
+        );
+
+        let insecure = parse_url("http://www.example.com/");
+        assert_eq!(
+            jar.cookie_header(&insecure, None, 0),
+            Some("theme=dark".to_string())
+        );
+        assert_eq!(
+            jar.cookie_header(&parse_url("https://other.com/"), None, 0),
+            None
+        );
+
+        let third_party = parse_url("https://tracker.test/");
+        assert_eq!(jar.cookie_header(&docs, Some(&third_party), 0), None);
+        assert_eq!(
+            jar.cookie_header(&docs, Some(&parse_url("https://www.example.com/")), 0),
+            Some("pref=a; sid=1; theme=dark; strict=1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_replace_and_expire() {
+        let mut jar = CookieJar::new();
+        let url = parse_url("http://example.com/");
+        jar.set_cookie("a=1", &url, 0).unwrap();
+        jar.set_cookie("b=2; Max-Age=10", &url, 0).unwrap();
+        jar.set_cookie("a=3", &url, 0).unwrap();
+        assert_eq!(
+            jar.cookie_header(&url, None, 5),
+            Some("a=3; b=2".to_string())
+        );
+        assert_eq!(jar.cookie_header(&url, None, 10), Some("a=3".to_string()));
+
+        jar.set_cookie("a=gone; Expires=Thu, 01 Jan 1970 00:00:00 GMT", &url, 5)
+            .unwrap();
+        jar.remove_expired(10);
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn test_store_response_and_serialize() {
+        let mut jar = CookieJar::new();
+        let url = parse_url("https://example.com/");
+        let response = HttpResponse::new(
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: sid=abc; Secure; HttpOnly\r\nset-cookie: theme=dark; Max-Age=3600\r\nSet-Cookie: broken\r\n\r\n",
+        )
+        .expect("failed to parse http response");
+        jar.store_response(&url, &response, 100);
+        assert_eq!(jar.len(), 2);
+
+        let serialized = jar.serialize();
+        let restored = CookieJar::deserialize(&serialized).expect("failed to deserialize");
+        assert_eq!(restored, jar);
+        assert_eq!(
+            restored.cookie_header(&url, None, 200),
+            Some("sid=abc; theme=dark".to_string())
+        );
+        assert!(CookieJar::deserialize("example.com\tTRUE\t/").is_err());
+    }
+}
@@ -12,6 +12,12 @@ pub enum DisplayItem {
         layout_point: LayoutPoint,
         layout_size: LayoutSize,
     },
+    Image {
+        src: String,
+        style: ComputedStyle,
+        layout_point: LayoutPoint,
+        layout_size: LayoutSize,
+    },
     Text {
         text: String,
         style: ComputedStyle,
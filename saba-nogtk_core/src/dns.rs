@@ -0,0 +1,847 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::clock::Clock;
+use crate::error::Error;
+use crate::random::RandomSource;
+
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NAME_ERROR: u16 = 3;
+
+const DEFAULT_MAX_ENTRIES: usize = 256;
+const DEFAULT_ATTEMPTS: usize = 3;
+// A reply bigger than this is truncated by the server and would need TCP.
+const MAX_UDP_MESSAGE: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answer {
+    addresses: Vec<IpAddr>,
+    // Seconds the addresses may be reused for.
+    ttl: u32,
+}
+
+impl Answer {
+    pub fn new(addresses: Vec<IpAddr>, ttl: u32) -> Self {
+        Self { addresses, ttl }
+    }
+
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        self.addresses.clone()
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+}
+
+pub trait Resolver {
+    fn resolve(&mut self, name: &str) -> Result<Answer, Error>;
+}
+
+// Parses a host that is already an address, as in "127.0.0.1" or "[::1]".
+pub fn parse_ip_literal(host: &str) -> Option<IpAddr> {
+    match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        Some(v6) => v6.parse::<Ipv6Addr>().ok().map(IpAddr::V6),
+        None => host.parse::<Ipv4Addr>().ok().map(IpAddr::V4),
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+// Fixed name-to-address mappings in the format of /etc/hosts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostsTable {
+    entries: Vec<(String, IpAddr)>,
+}
+
+impl HostsTable {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    // Reads "address name [aliases...]" lines, skipping comments and malformed lines.
+    pub fn parse(text: &str) -> Self {
+        let mut table = Self::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let Some(address) = fields.next().and_then(|a| a.parse::<IpAddr>().ok()) else {
+                continue;
+            };
+            for name in fields {
+                table.add(name, address);
+            }
+        }
+        table
+    }
+
+    pub fn add(&mut self, name: &str, address: IpAddr) {
+        let name = normalize_name(name);
+        if !self.entries.contains(&(name.clone(), address)) {
+            self.entries.push((name, address));
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Vec<IpAddr> {
+        let name = normalize_name(name);
+        self.entries
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .map(|(_, address)| *address)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// Answers from a hosts table first and asks the inner resolver about every other name.
+pub struct HostsResolver<R: Resolver> {
+    hosts: HostsTable,
+    inner: R,
+}
+
+impl<R: Resolver> HostsResolver<R> {
+    pub fn new(hosts: HostsTable, inner: R) -> Self {
+        Self { hosts, inner }
+    }
+
+    pub fn hosts(&self) -> HostsTable {
+        self.hosts.clone()
+    }
+
+    pub fn set_hosts(&mut self, hosts: HostsTable) {
+        self.hosts = hosts;
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: Resolver> Resolver for HostsResolver<R> {
+    fn resolve(&mut self, name: &str) -> Result<Answer, Error> {
+        let addresses = self.hosts.lookup(name);
+        if !addresses.is_empty() {
+            return Ok(Answer::new(addresses, 0));
+        }
+        self.inner.resolve(name)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    name: String,
+    addresses: Vec<IpAddr>,
+    expires: u64,
+}
+
+// Remembers answers for as long as their TTL allows.
+pub struct CachingResolver<R: Resolver, C: Clock> {
+    inner: R,
+    clock: C,
+    entries: Vec<CacheEntry>,
+    max_entries: usize,
+}
+
+impl<R: Resolver, C: Clock> CachingResolver<R, C> {
+    pub fn new(inner: R, clock: C) -> Self {
+        Self {
+            inner,
+            clock,
+            entries: Vec::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn invalidate(&mut self, name: &str) {
+        let name = normalize_name(name);
+        self.entries.retain(|e| e.name != name);
+    }
+
+    fn evict(&mut self) {
+        if self.entries.len() > self.max_entries {
+            let excess = self.entries.len() - self.max_entries;
+            self.entries.drain(..excess);
+        }
+    }
+}
+
+impl<R: Resolver, C: Clock> Resolver for CachingResolver<R, C> {
+    fn resolve(&mut self, name: &str) -> Result<Answer, Error> {
+        let now = self.clock.now();
+        let key = normalize_name(name);
+        self.entries.retain(|e| e.expires > now);
+        if let Some(entry) = self.entries.iter().find(|e| e.name == key) {
+            let remaining = (entry.expires - now).min(u32::MAX as u64) as u32;
+            return Ok(Answer::new(entry.addresses.clone(), remaining));
+        }
+
+        let answer = self.inner.resolve(name)?;
+        if answer.ttl() > 0 {
+            self.entries.push(CacheEntry {
+                name: key,
+                addresses: answer.addresses(),
+                expires: now + answer.ttl() as u64,
+            });
+            self.evict();
+        }
+        Ok(answer)
+    }
+}
+
+pub trait DatagramSocket {
+    fn send(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    // Waits for the next datagram, returning None when the socket's timeout passes first.
+    fn receive(&mut self, buf: &mut [u8]) -> Result<Option<usize>, Error>;
+}
+
+fn encode_query(id: u16, name: &str, record_type: u16) -> Result<Vec<u8>, Error> {
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    // A standard query with recursion desired and a single question.
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+    let name = name.trim_end_matches('.');
+    if name.is_empty() || name.len() > 253 {
+        return Err(Error::Network(format!("invalid host name: {}", name)));
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 || !label.is_ascii() {
+            return Err(Error::Network(format!("invalid host name: {}", name)));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+fn malformed() -> Error {
+    Error::Network("malformed DNS response".to_string())
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Result<u16, Error> {
+    match message.get(pos..pos + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+        None => Err(malformed()),
+    }
+}
+
+// Reads a possibly compressed name and returns it with the position just after it.
+fn read_name(message: &[u8], mut pos: usize) -> Result<(String, usize), Error> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Each pointer must go backwards, which rules out loops.
+    let mut limit = pos;
+    loop {
+        let len = *message.get(pos).ok_or_else(malformed)? as usize;
+        match len & 0xc0 {
+            0x00 if len == 0 => {
+                let end = end.unwrap_or(pos + 1);
+                return Ok((labels.join("."), end));
+            }
+            0x00 => {
+                let label = message.get(pos + 1..pos + 1 + len).ok_or_else(malformed)?;
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                pos += 1 + len;
+            }
+            0xc0 => {
+                let target = (read_u16(message, pos)? & 0x3fff) as usize;
+                if target >= limit {
+                    return Err(malformed());
+                }
+                end.get_or_insert(pos + 2);
+                limit = target;
+                pos = target;
+            }
+            _ => return Err(malformed()),
+        }
+    }
+}
+
+struct Record {
+    name: String,
+    record_type: u16,
+    ttl: u32,
+    data: Vec<u8>,
+    // The decoded target of a CNAME record.
+    target: Option<String>,
+}
+
+// Extracts the addresses for `name`, following CNAME records within the response. Returns
+// Ok(None) for datagrams that are not the reply to this query.
+fn parse_response(
+    message: &[u8],
+    id: u16,
+    name: &str,
+    record_type: u16,
+) -> Result<Option<Answer>, Error> {
+    if message.len() < 12 || read_u16(message, 0)? != id {
+        return Ok(None);
+    }
+    let flags = read_u16(message, 2)?;
+    if flags & 0x8000 == 0 {
+        return Ok(None);
+    }
+    if flags & 0x0200 != 0 {
+        return Err(Error::Network(format!(
+            "the DNS response for {} was truncated",
+            name
+        )));
+    }
+    match flags & 0x000f {
+        0 => {}
+        RCODE_NAME_ERROR => return Err(Error::Network(format!("{} was not found", name))),
+        rcode => {
+            return Err(Error::Network(format!(
+                "the DNS server failed to resolve {} (rcode {})",
+                name, rcode
+            )))
+        }
+    }
+
+    let question_count = read_u16(message, 4)?;
+    let answer_count = read_u16(message, 6)?;
+    let mut pos = 12;
+    for _ in 0..question_count {
+        let (question, next) = read_name(message, pos)?;
+        if question != normalize_name(name) || read_u16(message, next)? != record_type {
+            return Ok(None);
+        }
+        pos = next + 4;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answer_count {
+        let (owner, next) = read_name(message, pos)?;
+        let rtype = read_u16(message, next)?;
+        let ttl_high = read_u16(message, next + 4)? as u32;
+        let ttl_low = read_u16(message, next + 6)? as u32;
+        let len = read_u16(message, next + 8)? as usize;
+        let start = next + 10;
+        let data = message.get(start..start + len).ok_or_else(malformed)?;
+        let target = match rtype {
+            TYPE_CNAME => Some(read_name(message, start)?.0),
+            _ => None,
+        };
+        records.push(Record {
+            name: owner,
+            record_type: rtype,
+            ttl: (ttl_high << 16) | ttl_low,
+            data: data.to_vec(),
+            target,
+        });
+        pos = start + len;
+    }
+
+    let mut names = Vec::from([normalize_name(name)]);
+    let mut ttl = u32::MAX;
+    // Follow the alias chain; each step can add at most one name.
+    for _ in 0..records.len() {
+        let current = names[names.len() - 1].clone();
+        let Some(alias) = records
+            .iter()
+            .find(|r| r.record_type == TYPE_CNAME && r.name == current)
+        else {
+            break;
+        };
+        let target = alias.target.clone().unwrap_or_default();
+        if names.contains(&target) {
+            break;
+        }
+        ttl = ttl.min(alias.ttl);
+        names.push(target);
+    }
+
+    let mut addresses = Vec::new();
+    for record in records
+        .iter()
+        .filter(|r| r.record_type == record_type && names.contains(&r.name))
+    {
+        let address = match (record.record_type, record.data.len()) {
+            (TYPE_A, 4) => {
+                let octets: [u8; 4] = record.data[..].try_into().map_err(|_| malformed())?;
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = record.data[..].try_into().map_err(|_| malformed())?;
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return Err(malformed()),
+        };
+        ttl = ttl.min(record.ttl);
+        addresses.push(address);
+    }
+    if addresses.is_empty() {
+        ttl = 0;
+    }
+    Ok(Some(Answer::new(addresses, ttl)))
+}
+
+// A stub resolver that sends queries over UDP to a recursive name server.
+pub struct DnsResolver<S: DatagramSocket, R: RandomSource> {
+    socket: S,
+    random: R,
+    attempts: usize,
+    ipv6: bool,
+}
+
+impl<S: DatagramSocket, R: RandomSource> DnsResolver<S, R> {
+    pub fn new(socket: S, random: R) -> Self {
+        Self {
+            socket,
+            random,
+            attempts: DEFAULT_ATTEMPTS,
+            ipv6: true,
+        }
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    // How many times a query is sent before giving up on a silent server.
+    pub fn set_attempts(&mut self, attempts: usize) {
+        self.attempts = core::cmp::max(attempts, 1);
+    }
+
+    pub fn ipv6(&self) -> bool {
+        self.ipv6
+    }
+
+    // Whether AAAA records are asked for in addition to A records.
+    pub fn set_ipv6(&mut self, ipv6: bool) {
+        self.ipv6 = ipv6;
+    }
+
+    fn query(&mut self, name: &str, record_type: u16) -> Result<Answer, Error> {
+        let mut id = [0u8; 2];
+        self.random.fill(&mut id)?;
+        let id = u16::from_be_bytes(id);
+        let query = encode_query(id, name, record_type)?;
+
+        for _ in 0..self.attempts {
+            self.socket.send(&query)?;
+            let mut buf = [0u8; MAX_UDP_MESSAGE];
+            // Skip stray datagrams until the reply arrives or the socket times out.
+            while let Some(n) = self.socket.receive(&mut buf)? {
+                if let Some(answer) = parse_response(&buf[..n], id, name, record_type)? {
+                    return Ok(answer);
+                }
+            }
+        }
+        Err(Error::Network(format!(
+            "the DNS server did not answer for {}",
+            name
+        )))
+    }
+}
+
+impl<S: DatagramSocket, R: RandomSource> Resolver for DnsResolver<S, R> {
+    fn resolve(&mut self, name: &str) -> Result<Answer, Error> {
+        let v4 = self.query(name, TYPE_A);
+        let answer = if self.ipv6 {
+            // Middleboxes and stub resolvers often mishandle one of the query types, so a
+            // failed query only costs its own addresses as long as the other one answered.
+            match (v4, self.query(name, TYPE_AAAA)) {
+                (Ok(mut answer), Ok(v6)) => {
+                    if answer.addresses.is_empty() {
+                        answer.ttl = v6.ttl;
+                    } else if !v6.addresses.is_empty() {
+                        answer.ttl = answer.ttl.min(v6.ttl);
+                    }
+                    answer.addresses.extend(v6.addresses);
+                    answer
+                }
+                (Ok(answer), Err(_)) => answer,
+                (Err(e), Ok(v6)) if v6.addresses.is_empty() => return Err(e),
+                (Err(_), Ok(v6)) => v6,
+                (Err(e), Err(_)) => return Err(e),
+            }
+        } else {
+            v4?
+        };
+        if answer.addresses.is_empty() {
+            return Err(Error::Network(format!("no addresses found for {}", name)));
+        }
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{Connector, ResolvingTransport, Stream, Transport};
+    use alloc::boxed::Box;
+    use alloc::collections::VecDeque;
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::{Cell, RefCell};
+
+    struct CountingResolver {
+        answer: Answer,
+        calls: Rc<Cell<usize>>,
+    }
+
+    impl Resolver for CountingResolver {
+        fn resolve(&mut self, _name: &str) -> Result<Answer, Error> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(self.answer.clone())
+        }
+    }
+
+    struct SteppingClock {
+        now: Rc<Cell<u64>>,
+    }
+
+    impl Clock for SteppingClock {
+        fn now(&self) -> u64 {
+            self.now.get()
+        }
+    }
+
+    // Replies to each query with a scripted handler.
+    struct ScriptedSocket {
+        replies: VecDeque<Option<Vec<u8>>>,
+        respond: fn(&[u8]) -> Vec<u8>,
+        last_query: Vec<u8>,
+    }
+
+    impl DatagramSocket for ScriptedSocket {
+        fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+            self.last_query = data.to_vec();
+            self.replies.push_back(Some((self.respond)(data)));
+            Ok(())
+        }
+
+        fn receive(&mut self, buf: &mut [u8]) -> Result<Option<usize>, Error> {
+            match self.replies.pop_front().flatten() {
+                Some(reply) => {
+                    buf[..reply.len()].copy_from_slice(&reply);
+                    Ok(Some(reply.len()))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+
+    struct CountingRandom {}
+
+    impl RandomSource for CountingRandom {
+        fn fill(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+            buf.fill(0x42);
+            Ok(())
+        }
+    }
+
+    fn record(name_pointer: u16, record_type: u16, ttl: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = name_pointer.to_be_bytes().to_vec();
+        out.extend_from_slice(&record_type.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&ttl.to_be_bytes());
+        out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    // Builds a reply that echoes the question and appends answer records.
+    fn reply(query: &[u8], rcode: u8, answers: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = query.to_vec();
+        out[2] = 0x81;
+        out[3] = 0x80 | rcode;
+        out[7] = answers.len() as u8;
+        for answer in answers {
+            out.extend_from_slice(answer);
+        }
+        out
+    }
+
+    fn query_type(query: &[u8]) -> u16 {
+        u16::from_be_bytes([query[query.len() - 4], query[query.len() - 3]])
+    }
+
+    fn dns_resolver(respond: fn(&[u8]) -> Vec<u8>) -> DnsResolver<ScriptedSocket, CountingRandom> {
+        DnsResolver::new(
+            ScriptedSocket {
+                replies: VecDeque::new(),
+                respond,
+                last_query: Vec::new(),
+            },
+            CountingRandom {},
+        )
+    }
+
+    #[test]
+    fn test_hosts_table() {
+        let hosts = HostsTable::parse(
+            "# comment\n127.0.0.1 localhost loopback\n::1 localhost # v6\n\nbogus line\n10.0.0.5 Intranet.Example.\n",
+        );
+        assert_eq!(hosts.len(), 4);
+        assert_eq!(
+            hosts.lookup("LOCALHOST"),
+            vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ]
+        );
+        assert_eq!(
+            hosts.lookup("intranet.example"),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))]
+        );
+        assert!(hosts.lookup("example.com").is_empty());
+
+        let calls = Rc::new(Cell::new(0));
+        let mut resolver = HostsResolver::new(
+            hosts,
+            CountingResolver {
+                answer: Answer::new(vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))], 60),
+                calls: calls.clone(),
+            },
+        );
+        assert_eq!(
+            resolver.resolve("loopback").unwrap().addresses(),
+            vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]
+        );
+        assert_eq!(calls.get(), 0);
+        assert_eq!(
+            resolver.resolve("example.com").unwrap().addresses(),
+            vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))]
+        );
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_cache_honors_ttl() {
+        let calls = Rc::new(Cell::new(0));
+        let now = Rc::new(Cell::new(1000));
+        let mut resolver = CachingResolver::new(
+            CountingResolver {
+                answer: Answer::new(vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))], 30),
+                calls: calls.clone(),
+            },
+            SteppingClock { now: now.clone() },
+        );
+
+        assert_eq!(resolver.resolve("example.com").unwrap().ttl(), 30);
+        now.set(1010);
+        assert_eq!(resolver.resolve("Example.com.").unwrap().ttl(), 20);
+        assert_eq!(calls.get(), 1);
+        now.set(1030);
+        resolver.resolve("example.com").unwrap();
+        assert_eq!(calls.get(), 2);
+
+        resolver.invalidate("example.com");
+        assert!(resolver.is_empty());
+        resolver.resolve("a.example").unwrap();
+        resolver.resolve("b.example").unwrap();
+        resolver.set_max_entries(1);
+        assert_eq!(resolver.len(), 1);
+    }
+
+    #[test]
+    fn test_dns_resolver_follows_cname() {
+        let mut resolver = dns_resolver(|query| match query_type(query) {
+            TYPE_A => {
+                // www.example.com -> example.com (pointer to the "example" label) -> 93.184.216.34
+                reply(
+                    query,
+                    0,
+                    &[
+                        record(0xc00c, TYPE_CNAME, 300, &[0xc0, 0x10]),
+                        record(0xc010, TYPE_A, 120, &[93, 184, 216, 34]),
+                    ],
+                )
+            }
+            _ => reply(
+                query,
+                0,
+                &[record(
+                    0xc00c,
+                    TYPE_AAAA,
+                    60,
+                    &[0x26, 0x06, 0x28, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                )],
+            ),
+        });
+        let answer = resolver.resolve("www.example.com").unwrap();
+        assert_eq!(
+            answer.addresses(),
+            vec![
+                IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34)),
+                "2606:2800::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+        assert_eq!(answer.ttl(), 60);
+        assert_eq!(
+            resolver.socket.last_query[12..],
+            b"\x03www\x07example\x03com\x00\x00\x1c\x00\x01"[..]
+        );
+    }
+
+    #[test]
+    fn test_dns_resolver_errors() {
+        let mut missing = dns_resolver(|query| reply(query, RCODE_NAME_ERROR as u8, &[]));
+        assert_eq!(
+            missing.resolve("nope.example"),
+            Err(Error::Network("nope.example was not found".to_string()))
+        );
+
+        // A reply with the wrong id is ignored, so the server looks silent.
+        let mut wrong_id = dns_resolver(|query| {
+            let mut out = reply(query, 0, &[record(0xc00c, TYPE_A, 60, &[1, 2, 3, 4])]);
+            out[0] ^= 0xff;
+            out
+        });
+        wrong_id.set_attempts(2);
+        assert!(wrong_id.resolve("example.com").is_err());
+
+        // A failing AAAA query leaves the IPv4 addresses, and a failing A query the IPv6 ones.
+        let mut no_v6 = dns_resolver(|query| match query_type(query) {
+            TYPE_A => reply(query, 0, &[record(0xc00c, TYPE_A, 60, &[1, 2, 3, 4])]),
+            _ => reply(query, 2, &[]),
+        });
+        assert_eq!(
+            no_v6.resolve("example.com").unwrap().addresses(),
+            vec![IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))]
+        );
+        let mut no_v4 = dns_resolver(|query| match query_type(query) {
+            TYPE_A => reply(query, 2, &[]),
+            _ => reply(
+                query,
+                0,
+                &[record(
+                    0xc00c,
+                    TYPE_AAAA,
+                    60,
+                    &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                )],
+            ),
+        });
+        assert_eq!(
+            no_v4.resolve("example.com").unwrap().addresses(),
+            vec!["2001:db8::1".parse::<IpAddr>().unwrap()]
+        );
+        let mut servfail = dns_resolver(|query| reply(query, 2, &[]));
+        assert_eq!(
+            servfail.resolve("example.com"),
+            Err(Error::Network(
+                "the DNS server failed to resolve example.com (rcode 2)".to_string()
+            ))
+        );
+
+        let mut looping =
+            dns_resolver(|query| reply(query, 0, &[record(0xc00c, TYPE_A, 60, &[1])]));
+        assert!(looping.resolve("example.com").is_err());
+        assert!(encode_query(1, "bad..name", TYPE_A).is_err());
+        assert!(read_name(&[0xc0, 0x00], 0).is_err());
+    }
+
+    struct FlakyConnector {
+        attempts: Rc<RefCell<Vec<IpAddr>>>,
+    }
+
+    struct NullStream {}
+
+    impl Stream for NullStream {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Error> {
+            Ok(0)
+        }
+
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            Ok(buf.len())
+        }
+    }
+
+    impl Connector for FlakyConnector {
+        fn connect_addr(&mut self, address: IpAddr, _port: u16) -> Result<Box<dyn Stream>, Error> {
+            self.attempts.borrow_mut().push(address);
+            if address == IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)) {
+                Ok(Box::new(NullStream {}))
+            } else {
+                Err(Error::Network("connection refused".to_string()))
+            }
+        }
+    }
+
+    #[test]
+    fn test_transport_falls_back_across_addresses() {
+        let attempts = Rc::new(RefCell::new(Vec::new()));
+        let mut hosts = HostsTable::new();
+        hosts.add("multi.example", IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        hosts.add("multi.example", IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+        hosts.add("down.example", IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        hosts.add("down.example", IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4)));
+        let mut transport = ResolvingTransport::new(
+            FlakyConnector {
+                attempts: attempts.clone(),
+            },
+            HostsResolver::new(
+                hosts,
+                CountingResolver {
+                    answer: Answer::new(Vec::new(), 0),
+                    calls: Rc::new(Cell::new(0)),
+                },
+            ),
+        );
+
+        assert!(transport.connect("multi.example", 80).is_ok());
+        assert_eq!(attempts.borrow().len(), 2);
+        assert!(matches!(
+            transport.connect("down.example", 80),
+            Err(Error::Network(message)) if message.contains("any of the 2 addresses")
+        ));
+        assert!(transport.connect("[::1]", 80).is_err());
+        assert_eq!(
+            attempts.borrow().last(),
+            Some(&IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+        assert!(matches!(
+            transport.connect("nowhere.example", 80),
+            Err(Error::Network(message)) if message.contains("No IP addresses")
+        ));
+    }
+}
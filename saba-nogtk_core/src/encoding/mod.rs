@@ -0,0 +1,769 @@
+mod jis0208;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use self::jis0208::JIS0208;
+
+const REPLACEMENT: char = '\u{fffd}';
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    ShiftJis,
+    EucJp,
+    // ISO-8859-1 and US-ASCII labels are decoded as windows-1252, as browsers do.
+    Windows1252,
+}
+
+impl Encoding {
+    // Looks up an encoding label as defined by the WHATWG Encoding Standard.
+    pub fn for_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_ascii_lowercase();
+        match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Encoding::Utf8),
+            "unicodefffe" | "utf-16be" => Some(Encoding::Utf16Be),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Encoding::Utf16Le),
+            "csshiftjis" | "ms932" | "ms_kanji" | "shift-jis" | "shift_jis" | "sjis"
+            | "windows-31j" | "x-sjis" => Some(Encoding::ShiftJis),
+            "cseucpkdfmtjapanese" | "euc-jp" | "x-euc-jp" => Some(Encoding::EucJp),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::EucJp => "EUC-JP",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    pub fn from_bom(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            Some(Encoding::Utf8)
+        } else if bytes.starts_with(&[0xfe, 0xff]) {
+            Some(Encoding::Utf16Be)
+        } else if bytes.starts_with(&[0xff, 0xfe]) {
+            Some(Encoding::Utf16Le)
+        } else {
+            None
+        }
+    }
+
+    // Decodes `bytes` into a string. A leading BOM of this encoding is removed, and invalid
+    // sequences are replaced with U+FFFD.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        self.decode_with_errors(bytes).0
+    }
+
+    fn decode_with_errors(&self, bytes: &[u8]) -> (String, bool) {
+        let bytes = match (Encoding::from_bom(bytes), self) {
+            (Some(Encoding::Utf8), Encoding::Utf8) => &bytes[3..],
+            (Some(Encoding::Utf16Le), Encoding::Utf16Le)
+            | (Some(Encoding::Utf16Be), Encoding::Utf16Be) => &bytes[2..],
+            _ => bytes,
+        };
+        self.decode_without_bom(bytes)
+    }
+
+    fn decode_without_bom(&self, bytes: &[u8]) -> (String, bool) {
+        match self {
+            Encoding::Utf8 => match core::str::from_utf8(bytes) {
+                Ok(s) => (s.to_string(), false),
+                Err(_) => (String::from_utf8_lossy(bytes).to_string(), true),
+            },
+            Encoding::Utf16Le => decode_utf16(bytes, false),
+            Encoding::Utf16Be => decode_utf16(bytes, true),
+            Encoding::ShiftJis => decode_shift_jis(bytes),
+            Encoding::EucJp => decode_euc_jp(bytes),
+            Encoding::Windows1252 => (bytes.iter().map(|b| windows_1252(*b)).collect(), false),
+        }
+    }
+}
+
+// Picks the encoding of an HTML document from the BOM, then the Content-Type charset, then a
+// `<meta>` prescan, and finally a guess from the bytes themselves.
+pub fn detect_encoding(bytes: &[u8], content_type_charset: Option<&str>) -> Encoding {
+    if let Some(encoding) = Encoding::from_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = content_type_charset.and_then(Encoding::for_label) {
+        return encoding;
+    }
+    if let Some(encoding) = prescan_meta_charset(bytes) {
+        return encoding;
+    }
+    guess_encoding(bytes, false)
+}
+
+// Like `detect_encoding`, for a document whose bytes are still arriving. Returns `None` until
+// there are enough of them to decide: the first three for a BOM and the first 1024 for the
+// `<meta>` prescan. Without a `<meta>` the guess is made from those 1024 bytes, rather than
+// waiting for the whole document.
+pub fn detect_encoding_early(bytes: &[u8], content_type_charset: Option<&str>) -> Option<Encoding> {
+    if bytes.len() < 3 {
+        return None;
+    }
+    if let Some(encoding) = Encoding::from_bom(bytes) {
+        return Some(encoding);
+    }
+    if let Some(encoding) = content_type_charset.and_then(Encoding::for_label) {
+        return Some(encoding);
+    }
+    if bytes.len() < 1024 {
+        return None;
+    }
+    let window = &bytes[..1024];
+    Some(prescan_meta_charset(window).unwrap_or_else(|| guess_encoding(window, true)))
+}
+
+// Like `detect_encoding`, but for plain text, which has no `<meta>` to look for.
+pub fn detect_text_encoding(bytes: &[u8], content_type_charset: Option<&str>) -> Encoding {
+    Encoding::from_bom(bytes)
+        .or_else(|| content_type_charset.and_then(Encoding::for_label))
+        .unwrap_or_else(|| guess_encoding(bytes, false))
+}
+
+// With `partial`, `bytes` are only the start of the document and may end partway through a
+// character.
+fn guess_encoding(bytes: &[u8], partial: bool) -> Encoding {
+    // EUC-JP is checked before Shift_JIS since EUC-JP text is often valid Shift_JIS as well.
+    for encoding in [Encoding::Utf8, Encoding::EucJp, Encoding::ShiftJis] {
+        let bytes = if partial {
+            &bytes[..complete_len(encoding, bytes)]
+        } else {
+            bytes
+        };
+        if !encoding.decode_with_errors(bytes).1 {
+            return encoding;
+        }
+    }
+    Encoding::Windows1252
+}
+
+// The length of the longest prefix of `bytes` that does not end partway through a character.
+// Sequences that turn out to be invalid are counted at the length their first byte announces.
+fn complete_len(encoding: Encoding, bytes: &[u8]) -> usize {
+    let mut i = 0;
+    while i < bytes.len() {
+        let len = match encoding {
+            Encoding::Utf8 => match bytes[i] {
+                0xc2..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf4 => 4,
+                _ => 1,
+            },
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let high = match encoding {
+                    Encoding::Utf16Le => bytes.get(i + 1),
+                    _ => bytes.get(i),
+                };
+                match high {
+                    Some(0xd8..=0xdb) => 4,
+                    _ => 2,
+                }
+            }
+            Encoding::ShiftJis => match bytes[i] {
+                0x81..=0x9f | 0xe0..=0xfc => 2,
+                _ => 1,
+            },
+            Encoding::EucJp => match (bytes[i], bytes.get(i + 1)) {
+                (0x8f, None) | (0x8f, Some(0xa1..=0xfe)) => 3,
+                (0x8e | 0x8f | 0xa1..=0xfe, _) => 2,
+                _ => 1,
+            },
+            Encoding::Windows1252 => 1,
+        };
+        if i + len > bytes.len() {
+            return i;
+        }
+        i += len;
+    }
+    i
+}
+
+// Decodes bytes that arrive in pieces, such as a response body read from the network. The bytes
+// of a character split between pieces are held back until the rest of them arrive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamDecoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl StreamDecoder {
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            pending: Vec::new(),
+            started: false,
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let rest = self
+            .pending
+            .split_off(complete_len(self.encoding, &self.pending));
+        let complete = core::mem::replace(&mut self.pending, rest);
+        self.decode_complete(&complete)
+    }
+
+    // Decodes what is left at the end of the stream. A character cut off there becomes U+FFFD.
+    pub fn finish(&mut self) -> String {
+        let rest = core::mem::take(&mut self.pending);
+        self.decode_complete(&rest)
+    }
+
+    fn decode_complete(&mut self, bytes: &[u8]) -> String {
+        if bytes.is_empty() {
+            return String::new();
+        }
+        // Only the start of the stream can have a BOM; a U+FEFF later on is an ordinary
+        // character.
+        if core::mem::replace(&mut self.started, true) {
+            self.encoding.decode_without_bom(bytes).0
+        } else {
+            self.encoding.decode(bytes)
+        }
+    }
+}
+
+pub fn decode_html(bytes: &[u8], content_type_charset: Option<&str>) -> (String, Encoding) {
+    let encoding = detect_encoding(bytes, content_type_charset);
+    (encoding.decode(bytes), encoding)
+}
+
+// Implements "prescan a byte stream to determine its encoding" of the HTML standard over the
+// first 1024 bytes.
+pub fn prescan_meta_charset(bytes: &[u8]) -> Option<Encoding> {
+    let bytes = &bytes[..core::cmp::min(bytes.len(), 1024)];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            pos += 2;
+            while pos < bytes.len() && !bytes[..pos + 1].ends_with(b"-->") {
+                pos += 1;
+            }
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .map_or(false, |b| is_html_whitespace(*b) || *b == b'/')
+        {
+            pos += 6;
+            if let Some(encoding) = process_meta(bytes, &mut pos) {
+                return Some(match encoding {
+                    Encoding::Utf16Le | Encoding::Utf16Be => Encoding::Utf8,
+                    encoding => encoding,
+                });
+            }
+            continue;
+        } else if (rest.len() > 1 && rest[0] == b'<' && rest[1].is_ascii_alphabetic())
+            || (rest.len() > 2 && rest.starts_with(b"</") && rest[2].is_ascii_alphabetic())
+        {
+            while pos < bytes.len() && !is_html_whitespace(bytes[pos]) && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            while pos < bytes.len() && bytes[pos] != b'>' {
+                pos += 1;
+            }
+        }
+        pos += 1;
+    }
+    None
+}
+
+fn process_meta(bytes: &[u8], pos: &mut usize) -> Option<Encoding> {
+    let mut names: Vec<String> = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        if names.contains(&name) {
+            continue;
+        }
+        match name.as_str() {
+            "http-equiv" => {
+                if value == "content-type" {
+                    got_pragma = true;
+                }
+            }
+            "content" => {
+                if charset.is_none() {
+                    if let Some(label) = extract_charset_from_content(&value) {
+                        charset = Encoding::for_label(&label);
+                        need_pragma = Some(true);
+                    }
+                }
+            }
+            "charset" => {
+                charset = Encoding::for_label(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        names.push(name);
+    }
+
+    match need_pragma {
+        Some(true) if !got_pragma => None,
+        Some(_) => charset,
+        None => None,
+    }
+}
+
+// Reads one attribute of a tag for the prescan, lowercasing both the name and the value.
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(String, String)> {
+    while *pos < bytes.len() && (is_html_whitespace(bytes[*pos]) || bytes[*pos] == b'/') {
+        *pos += 1;
+    }
+    if *pos >= bytes.len() || bytes[*pos] == b'>' {
+        return None;
+    }
+
+    let mut name = Vec::new();
+    loop {
+        let b = *bytes.get(*pos)?;
+        if b == b'=' && !name.is_empty() {
+            *pos += 1;
+            break;
+        }
+        if is_html_whitespace(b) {
+            while *pos < bytes.len() && is_html_whitespace(bytes[*pos]) {
+                *pos += 1;
+            }
+            if bytes.get(*pos) != Some(&b'=') {
+                return Some((lossy(&name), String::new()));
+            }
+            *pos += 1;
+            break;
+        }
+        if b == b'/' || b == b'>' {
+            return Some((lossy(&name), String::new()));
+        }
+        name.push(b.to_ascii_lowercase());
+        *pos += 1;
+    }
+
+    while *pos < bytes.len() && is_html_whitespace(bytes[*pos]) {
+        *pos += 1;
+    }
+    let mut value = Vec::new();
+    match *bytes.get(*pos)? {
+        quote @ (b'"' | b'\'') => {
+            *pos += 1;
+            loop {
+                let b = *bytes.get(*pos)?;
+                *pos += 1;
+                if b == quote {
+                    return Some((lossy(&name), lossy(&value)));
+                }
+                value.push(b.to_ascii_lowercase());
+            }
+        }
+        b'>' => Some((lossy(&name), String::new())),
+        _ => {
+            while let Some(&b) = bytes.get(*pos) {
+                if is_html_whitespace(b) || b == b'>' {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+                *pos += 1;
+            }
+            Some((lossy(&name), lossy(&value)))
+        }
+    }
+}
+
+// Implements "extracting a character encoding from a meta element" for values such as
+// `text/html; charset=shift_jis`.
+fn extract_charset_from_content(content: &str) -> Option<String> {
+    let lower = content.to_ascii_lowercase();
+    let mut rest = lower.as_str();
+    loop {
+        let index = rest.find("charset")?;
+        rest = rest[index + 7..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+        if let Some(after) = rest.strip_prefix('=') {
+            rest = after.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            break;
+        }
+    }
+
+    match rest.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let value = &rest[1..];
+            value.find(quote).map(|end| value[..end].to_string())
+        }
+        _ => {
+            let end = rest
+                .find(|c: char| c.is_ascii_whitespace() || c == ';')
+                .unwrap_or(rest.len());
+            if end == 0 {
+                None
+            } else {
+                Some(rest[..end].to_string())
+            }
+        }
+    }
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn is_html_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | 0x0c | b'\r' | b' ')
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> (String, bool) {
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if big_endian {
+            u16::from_be_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_le_bytes([pair[0], pair[1]])
+        }
+    });
+
+    let mut s = String::new();
+    let mut had_errors = false;
+    for c in char::decode_utf16(units) {
+        s.push(c.unwrap_or_else(|_| {
+            had_errors = true;
+            REPLACEMENT
+        }));
+    }
+    if bytes.len() % 2 != 0 {
+        s.push(REPLACEMENT);
+        had_errors = true;
+    }
+    (s, had_errors)
+}
+
+fn jis0208(pointer: usize) -> Option<char> {
+    match JIS0208.get(pointer) {
+        Some(&code_point) if code_point != 0 => char::from_u32(code_point as u32),
+        _ => None,
+    }
+}
+
+fn decode_shift_jis(bytes: &[u8]) -> (String, bool) {
+    let mut s = String::new();
+    let mut had_errors = false;
+    let mut lead = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+
+        if let Some(l) = lead.take() {
+            let offset = if b < 0x7f { 0x40 } else { 0x41 };
+            let lead_offset = if l < 0xa0 { 0x81 } else { 0xc1 };
+            let pointer = match b {
+                0x40..=0x7e | 0x80..=0xfc => {
+                    Some((l as usize - lead_offset) * 188 + b as usize - offset)
+                }
+                _ => None,
+            };
+            // Pointers 8836 to 10715 are the end-user-defined area mapped to the Private Use
+            // Area.
+            let c = match pointer {
+                Some(p @ 8836..=10715) => char::from_u32(0xe000 + p as u32 - 8836),
+                Some(p) => jis0208(p),
+                None => None,
+            };
+            match c {
+                Some(c) => s.push(c),
+                None => {
+                    s.push(REPLACEMENT);
+                    had_errors = true;
+                    if b.is_ascii() {
+                        i -= 1;
+                    }
+                }
+            }
+            continue;
+        }
+
+        match b {
+            0x00..=0x80 => s.push(b as char),
+            0xa1..=0xdf => s.push(char::from_u32(0xff61 + b as u32 - 0xa1).unwrap_or(REPLACEMENT)),
+            0x81..=0x9f | 0xe0..=0xfc => lead = Some(b),
+            _ => {
+                s.push(REPLACEMENT);
+                had_errors = true;
+            }
+        }
+    }
+    if lead.is_some() {
+        s.push(REPLACEMENT);
+        had_errors = true;
+    }
+    (s, had_errors)
+}
+
+fn decode_euc_jp(bytes: &[u8]) -> (String, bool) {
+    let mut s = String::new();
+    let mut had_errors = false;
+    let mut lead = None;
+    let mut jis0212 = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+
+        match lead.take() {
+            Some(0x8e) if (0xa1..=0xdf).contains(&b) => {
+                s.push(char::from_u32(0xff61 + b as u32 - 0xa1).unwrap_or(REPLACEMENT));
+            }
+            Some(0x8f) if (0xa1..=0xfe).contains(&b) => {
+                jis0212 = true;
+                lead = Some(b);
+            }
+            Some(l) => {
+                // JIS X 0212 is rarely used on the web and its table is not included, so those
+                // characters are decoded as U+FFFD.
+                let c = if (0xa1..=0xfe).contains(&l) && (0xa1..=0xfe).contains(&b) && !jis0212 {
+                    jis0208((l as usize - 0xa1) * 94 + b as usize - 0xa1)
+                } else {
+                    None
+                };
+                jis0212 = false;
+                match c {
+                    Some(c) => s.push(c),
+                    None => {
+                        s.push(REPLACEMENT);
+                        had_errors = true;
+                        if b.is_ascii() {
+                            i -= 1;
+                        }
+                    }
+                }
+            }
+            None => match b {
+                0x00..=0x7f => s.push(b as char),
+                0x8e | 0x8f | 0xa1..=0xfe => lead = Some(b),
+                _ => {
+                    s.push(REPLACEMENT);
+                    had_errors = true;
+                }
+            },
+        }
+    }
+    if lead.is_some() {
+        s.push(REPLACEMENT);
+        had_errors = true;
+    }
+    (s, had_errors)
+}
+
+pub(crate) fn windows_1252(b: u8) -> char {
+    const HIGH: [u16; 32] = [
+        0x20ac, 0x0081, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160,
+        0x2039, 0x0152, 0x008d, 0x017d, 0x008f, 0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022,
+        0x2013, 0x2014, 0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x009d, 0x017e, 0x0178,
+    ];
+    match b {
+        0x80..=0x9f => char::from_u32(HIGH[(b - 0x80) as usize] as u32).unwrap_or(REPLACEMENT),
+        _ => b as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "日本語のテキスト" in each encoding.
+    const SHIFT_JIS: &[u8] = &[
+        0x93, 0xfa, 0x96, 0x7b, 0x8c, 0xea, 0x82, 0xcc, 0x83, 0x65, 0x83, 0x4c, 0x83, 0x58, 0x83,
+        0x67,
+    ];
+    const EUC_JP: &[u8] = &[
+        0xc6, 0xfc, 0xcb, 0xdc, 0xb8, 0xec, 0xa4, 0xce, 0xa5, 0xc6, 0xa5, 0xad, 0xa5, 0xb9, 0xa5,
+        0xc8,
+    ];
+
+    #[test]
+    fn test_decode_japanese() {
+        assert_eq!(Encoding::ShiftJis.decode(SHIFT_JIS), "日本語のテキスト");
+        assert_eq!(Encoding::EucJp.decode(EUC_JP), "日本語のテキスト");
+        assert_eq!(
+            Encoding::ShiftJis.decode(&[b'a', 0xb1, 0x5c, 0x87, 0x40]),
+            "aｱ\\①"
+        );
+        assert_eq!(Encoding::EucJp.decode(&[0x8e, 0xb1, 0xa1, 0xa2]), "ｱ、");
+    }
+
+    #[test]
+    fn test_invalid_sequences_are_replaced() {
+        assert_eq!(
+            Encoding::ShiftJis.decode(&[0x93, b' ', 0xa0, 0x93]),
+            "\u{fffd} \u{fffd}\u{fffd}"
+        );
+        assert_eq!(
+            Encoding::EucJp.decode(&[0xc6, b'<', 0xff]),
+            "\u{fffd}<\u{fffd}"
+        );
+        assert_eq!(Encoding::EucJp.decode(&[0x8f, 0xb0, 0xa1]), "\u{fffd}");
+        assert_eq!(Encoding::Utf8.decode(&[b'a', 0xff, b'b']), "a\u{fffd}b");
+        assert_eq!(
+            Encoding::Utf16Le.decode(&[0x3d, 0xd8, b'a', 0x00, 0x01]),
+            "\u{fffd}a\u{fffd}"
+        );
+    }
+
+    #[test]
+    fn test_decode_utf16_and_windows_1252() {
+        assert_eq!(
+            Encoding::Utf16Be.decode(&[0xfe, 0xff, 0x00, b'h', 0x30, 0x42, 0xd8, 0x3d, 0xde, 0x00]),
+            "hあ😀"
+        );
+        assert_eq!(Encoding::Utf16Le.decode(&[0xff, 0xfe, b'h', 0x00]), "h");
+        assert_eq!(Encoding::Windows1252.decode(&[b'c', 0x80, 0xe9]), "c€é");
+        assert_eq!(
+            Encoding::for_label(" ISO-8859-1 "),
+            Some(Encoding::Windows1252)
+        );
+        assert_eq!(Encoding::for_label("x-sjis"), Some(Encoding::ShiftJis));
+        assert_eq!(Encoding::for_label("unknown"), None);
+    }
+
+    #[test]
+    fn test_stream_decoder() {
+        let utf8 = "\u{feff}aé日\u{feff}😀".as_bytes();
+        let utf16: Vec<u8> = [0xfeff, 0x61, 0xd83d, 0xde00, 0x65e5]
+            .iter()
+            .flat_map(|u: &u16| u.to_le_bytes())
+            .collect();
+        let cases: [(Encoding, &[u8]); 4] = [
+            (Encoding::Utf8, utf8),
+            (Encoding::Utf16Le, &utf16),
+            (Encoding::ShiftJis, SHIFT_JIS),
+            (Encoding::EucJp, &[0x8e, 0xb1, 0x8f, 0xb0, 0xa1, 0xc6, 0xfc]),
+        ];
+        for (encoding, bytes) in cases {
+            let expected = encoding.decode(bytes);
+            for at in 0..=bytes.len() {
+                let mut decoder = StreamDecoder::new(encoding);
+                let mut decoded = decoder.decode(&bytes[..at]);
+                decoded.push_str(&decoder.decode(&bytes[at..]));
+                decoded.push_str(&decoder.finish());
+                assert_eq!(decoded, expected, "{:?} split at {}", encoding, at);
+            }
+        }
+
+        let mut decoder = StreamDecoder::new(Encoding::Utf8);
+        assert_eq!(decoder.decode(&[b'a', 0xe6, 0x97]), "a");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_detect_encoding_early() {
+        assert_eq!(detect_encoding_early(b"<p", Some("utf-8")), None);
+        assert_eq!(
+            detect_encoding_early(b"<p>", Some("shift_jis")),
+            Some(Encoding::ShiftJis)
+        );
+        assert_eq!(
+            detect_encoding_early(&[0xef, 0xbb, 0xbf], Some("shift_jis")),
+            Some(Encoding::Utf8)
+        );
+        let mut html = b"<meta charset=euc-jp>".to_vec();
+        assert_eq!(detect_encoding_early(&html, None), None);
+        html.resize(1024, b' ');
+        assert_eq!(detect_encoding_early(&html, None), Some(Encoding::EucJp));
+        // Without a label the guess is made from the prescan window, even when it ends partway
+        // through a character.
+        assert_eq!(
+            detect_encoding_early(&[b' '; 2048], None),
+            Some(Encoding::Utf8)
+        );
+        let mut sjis = [0x93, 0xfa, 0x96, 0x7b].repeat(300);
+        assert_eq!(detect_encoding_early(&sjis[..1023], None), None);
+        assert_eq!(detect_encoding_early(&sjis, None), Some(Encoding::ShiftJis));
+        sjis.insert(0, b' ');
+        assert_eq!(detect_encoding_early(&sjis, None), Some(Encoding::ShiftJis));
+    }
+
+    #[test]
+    fn test_prescan_meta_charset() {
+        assert_eq!(
+            prescan_meta_charset(b"<!DOCTYPE html><html><head><META CHARSET=\"Shift_JIS\">"),
+            Some(Encoding::ShiftJis)
+        );
+        assert_eq!(
+            prescan_meta_charset(
+                b"<meta http-equiv='Content-Type' content='text/html; charset=euc-jp'>"
+            ),
+            Some(Encoding::EucJp)
+        );
+        // A content attribute without http-equiv is ignored.
+        assert_eq!(
+            prescan_meta_charset(b"<meta content='text/html; charset=euc-jp'>"),
+            None
+        );
+        assert_eq!(
+            prescan_meta_charset(b"<!-- <meta charset=euc-jp> --><meta charset=utf-16>"),
+            Some(Encoding::Utf8)
+        );
+        assert_eq!(
+            prescan_meta_charset(b"<p title='<meta charset=euc-jp>'>text</p>"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_encoding_order() {
+        let mut html = b"<meta charset=euc-jp>".to_vec();
+        html.extend_from_slice(EUC_JP);
+        assert_eq!(detect_encoding(&html, None), Encoding::EucJp);
+        assert_eq!(
+            detect_encoding(&html, Some("shift_jis")),
+            Encoding::ShiftJis
+        );
+        assert_eq!(
+            detect_encoding(&[0xef, 0xbb, 0xbf, b'a'], Some("shift_jis")),
+            Encoding::Utf8
+        );
+        assert_eq!(detect_encoding(SHIFT_JIS, None), Encoding::ShiftJis);
+        assert_eq!(detect_encoding(EUC_JP, None), Encoding::EucJp);
+        assert_eq!(detect_encoding("日本語".as_bytes(), None), Encoding::Utf8);
+        assert_eq!(detect_encoding(&[0xa0, 0xff], None), Encoding::Windows1252);
+
+        let (text, encoding) = decode_html(&html, Some("bogus"));
+        assert_eq!(encoding, Encoding::EucJp);
+        assert_eq!(text, "<meta charset=euc-jp>日本語のテキスト");
+
+        // Plain text is not prescanned for a <meta>.
+        let mut text = b"<meta charset=shift_jis>".to_vec();
+        text.extend_from_slice(EUC_JP);
+        assert_eq!(detect_text_encoding(&text, None), Encoding::EucJp);
+        assert_eq!(detect_text_encoding(&text, Some("utf-8")), Encoding::Utf8);
+    }
+}
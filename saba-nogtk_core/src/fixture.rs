@@ -0,0 +1,810 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::base64;
+use crate::display_item::DisplayItem;
+use crate::error::Error;
+use crate::fetch::{url_without_fragment, Fetcher, ResponseSink};
+use crate::http::{check_header, HttpRequest, HttpResponse, Method};
+use crate::renderer::page::Page;
+use crate::url::Url;
+
+#[derive(Debug, Clone)]
+enum Outcome {
+    Response(Box<HttpResponse>),
+    Failure(String),
+}
+
+// A canned answer to a request. `delay` is the simulated time in milliseconds the answer takes.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    outcome: Outcome,
+    delay: u64,
+}
+
+impl Fixture {
+    pub fn new(response: HttpResponse) -> Self {
+        Self {
+            outcome: Outcome::Response(Box::new(response)),
+            delay: 0,
+        }
+    }
+
+    // Parses a recorded response in the HTTP/1.1 wire format.
+    pub fn from_raw(raw: &[u8]) -> Result<Self, Error> {
+        Ok(Self::new(HttpResponse::new(raw)?))
+    }
+
+    pub fn with_status(
+        status_code: u32,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Self, Error> {
+        let mut raw = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status_code,
+            reason_phrase(status_code)
+        );
+        for (name, value) in headers {
+            check_header(name, value)?;
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        raw.push_str("\r\n");
+
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(body);
+        Self::from_raw(&raw)
+    }
+
+    pub fn failure(message: &str) -> Self {
+        Self {
+            outcome: Outcome::Failure(message.to_string()),
+            delay: 0,
+        }
+    }
+
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    pub fn set_delay(&mut self, delay: u64) {
+        self.delay = delay;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    method: Option<Method>,
+    key: String,
+    fixtures: Vec<Fixture>,
+}
+
+// A fetcher that answers from canned fixtures instead of the network. Several fixtures for the
+// same URL are returned in order, and the last one is repeated once the others are used up.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureFetcher {
+    routes: Vec<Route>,
+    requests: Vec<HttpRequest>,
+    elapsed: u64,
+    timeout: Option<u64>,
+    chunk_size: Option<usize>,
+}
+
+impl FixtureFetcher {
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            requests: Vec::new(),
+            elapsed: 0,
+            timeout: None,
+            chunk_size: None,
+        }
+    }
+
+    pub fn add(&mut self, url: &str, fixture: Fixture) -> Result<(), Error> {
+        self.add_route(None, url, fixture)
+    }
+
+    pub fn add_for_method(
+        &mut self,
+        method: Method,
+        url: &str,
+        fixture: Fixture,
+    ) -> Result<(), Error> {
+        self.add_route(Some(method), url, fixture)
+    }
+
+    pub fn add_raw(&mut self, url: &str, raw: &[u8]) -> Result<(), Error> {
+        self.add(url, Fixture::from_raw(raw)?)
+    }
+
+    pub fn add_html(&mut self, url: &str, html: &str) -> Result<(), Error> {
+        self.add(
+            url,
+            Fixture::with_status(
+                200,
+                &[("Content-Type", "text/html; charset=utf-8")],
+                html.as_bytes(),
+            )?,
+        )
+    }
+
+    pub fn add_redirect(
+        &mut self,
+        url: &str,
+        status_code: u32,
+        location: &str,
+    ) -> Result<(), Error> {
+        self.add(
+            url,
+            Fixture::with_status(status_code, &[("Location", location)], b"")?,
+        )
+    }
+
+    pub fn add_failure(&mut self, url: &str, message: &str) -> Result<(), Error> {
+        self.add(url, Fixture::failure(message))
+    }
+
+    // Loads the entries of a HAR-like JSON archive:
+    //
+    //   {"log": {"entries": [{"request": {"method": "GET", "url": "..."},
+    //     "response": {"status": 200, "headers": [{"name": "...", "value": "..."}],
+    //       "content": {"text": "...", "encoding": "base64"}},
+    //     "time": 120, "_failure": "connection reset"}]}}
+    //
+    // `time` becomes the simulated delay and the optional `_failure` makes the entry fail.
+    pub fn load_har(&mut self, json: &str) -> Result<(), Error> {
+        let invalid =
+            |reason: &str| Error::UnexpectedInput(format!("invalid HAR archive: {}", reason));
+
+        let root = JsonParser::new(json).parse()?;
+        let entries = match root.get("log").and_then(|log| log.get("entries")) {
+            Some(Json::Array(entries)) => entries,
+            _ => return Err(invalid("missing log.entries")),
+        };
+
+        for entry in entries {
+            let request = entry
+                .get("request")
+                .ok_or_else(|| invalid("missing request"))?;
+            let url = request
+                .get("url")
+                .and_then(|u| u.as_str())
+                .ok_or_else(|| invalid("missing request.url"))?;
+            let method = match request.get("method").and_then(|m| m.as_str()) {
+                Some(method) => {
+                    Some(Method::parse(method).ok_or_else(|| invalid("unsupported method"))?)
+                }
+                None => None,
+            };
+
+            let mut fixture = match entry.get("_failure").and_then(|f| f.as_str()) {
+                Some(message) => Fixture::failure(message),
+                None => Self::har_response(entry)?,
+            };
+            if let Some(time) = entry.get("time").and_then(|t| t.as_number()) {
+                if time > 0.0 {
+                    fixture.set_delay(time as u64);
+                }
+            }
+            self.add_route(method, url, fixture)?;
+        }
+        Ok(())
+    }
+
+    fn har_response(entry: &Json) -> Result<Fixture, Error> {
+        let invalid =
+            |reason: &str| Error::UnexpectedInput(format!("invalid HAR archive: {}", reason));
+
+        let response = entry
+            .get("response")
+            .ok_or_else(|| invalid("missing response"))?;
+        let status_code = response
+            .get("status")
+            .and_then(|s| s.as_number())
+            .ok_or_else(|| invalid("missing response.status"))? as u32;
+
+        let mut headers = Vec::new();
+        if let Some(Json::Array(list)) = response.get("headers") {
+            for header in list {
+                match (
+                    header.get("name").and_then(|n| n.as_str()),
+                    header.get("value").and_then(|v| v.as_str()),
+                ) {
+                    (Some(name), Some(value)) => headers.push((name, value)),
+                    _ => return Err(invalid("invalid header")),
+                }
+            }
+        }
+
+        let content = response.get("content");
+        let text = content.and_then(|c| c.get("text")).and_then(|t| t.as_str());
+        let body = match (
+            text,
+            content
+                .and_then(|c| c.get("encoding"))
+                .and_then(|e| e.as_str()),
+        ) {
+            (Some(text), Some("base64")) => {
+                base64::decode(text).ok_or_else(|| invalid("invalid base64 content"))?
+            }
+            (Some(text), _) => text.as_bytes().to_vec(),
+            (None, _) => Vec::new(),
+        };
+
+        let mut raw = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status_code,
+            reason_phrase(status_code)
+        );
+        for (name, value) in &headers {
+            // Recorded bodies are already decoded, so their transfer and content codings no
+            // longer apply.
+            if name.eq_ignore_ascii_case("Content-Encoding")
+                || name.eq_ignore_ascii_case("Transfer-Encoding")
+                || name.eq_ignore_ascii_case("Content-Length")
+            {
+                continue;
+            }
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        raw.push_str("\r\n");
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(&body);
+        Fixture::from_raw(&raw)
+    }
+
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.clone()
+    }
+
+    // Returns the total simulated time in milliseconds spent on fetches so far.
+    pub fn elapsed(&self) -> u64 {
+        self.elapsed
+    }
+
+    pub fn timeout(&self) -> Option<u64> {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Option<u64>) {
+        self.timeout = timeout;
+    }
+
+    pub fn chunk_size(&self) -> Option<usize> {
+        self.chunk_size
+    }
+
+    // With a chunk size, `fetch_streaming` hands bodies to the sink that many bytes at a time,
+    // as if they were being read from the network.
+    pub fn set_chunk_size(&mut self, chunk_size: Option<usize>) {
+        self.chunk_size = chunk_size;
+    }
+
+    fn add_route(
+        &mut self,
+        method: Option<Method>,
+        url: &str,
+        fixture: Fixture,
+    ) -> Result<(), Error> {
+        let url = match Url::new(url.to_string()).parse() {
+            Ok(url) => url,
+            Err(e) => {
+                return Err(Error::UnexpectedInput(format!(
+                    "invalid fixture url {}: {:?}",
+                    url, e
+                )))
+            }
+        };
+        let key = url_without_fragment(&url);
+
+        match self
+            .routes
+            .iter_mut()
+            .find(|r| r.method == method && r.key == key)
+        {
+            Some(route) => route.fixtures.push(fixture),
+            None => self.routes.push(Route {
+                method,
+                key,
+                fixtures: vec![fixture],
+            }),
+        }
+        Ok(())
+    }
+}
+
+impl Fetcher for FixtureFetcher {
+    fn fetch(&mut self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        self.requests.push(request.clone());
+
+        let url = request.url();
+        let key = url_without_fragment(&url);
+        let route = self
+            .routes
+            .iter_mut()
+            .filter(|r| r.key == key)
+            .find(|r| r.method.map_or(true, |m| m == request.method()));
+        let route = match route {
+            Some(route) => route,
+            None => return Err(Error::Network(format!("no fixture for {}", url.href()))),
+        };
+        let fixture = if route.fixtures.len() > 1 {
+            route.fixtures.remove(0)
+        } else {
+            route.fixtures[0].clone()
+        };
+
+        if let Some(timeout) = self.timeout {
+            if fixture.delay > timeout {
+                self.elapsed += timeout;
+                return Err(Error::Network(format!(
+                    "timed out after {} ms fetching {}",
+                    timeout,
+                    url.href()
+                )));
+            }
+        }
+        self.elapsed += fixture.delay;
+
+        match fixture.outcome {
+            Outcome::Response(mut response) => {
+                if request.method() == Method::Head {
+                    response.set_body(Vec::new());
+                }
+                response.set_url(url);
+                Ok(*response)
+            }
+            Outcome::Failure(message) => Err(Error::Network(message)),
+        }
+    }
+
+    fn fetch_streaming(
+        &mut self,
+        request: &HttpRequest,
+        sink: &mut dyn ResponseSink,
+    ) -> Result<HttpResponse, Error> {
+        let response = self.fetch(request)?;
+        if let Some(chunk_size) = self.chunk_size {
+            let mut head = response.clone();
+            head.set_body(Vec::new());
+            sink.receive_head(&head);
+            for chunk in response.body().chunks(chunk_size.max(1)) {
+                sink.receive_body(chunk);
+            }
+        }
+        Ok(response)
+    }
+}
+
+fn reason_phrase(status_code: u32) -> &'static str {
+    match status_code {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+// A small JSON reader that is just enough for HAR archives.
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Json, Error> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.pos != self.input.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, reason: &str) -> Error {
+        Error::UnexpectedInput(format!("invalid JSON at byte {}: {}", self.pos, reason))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), Error> {
+        if self.input[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error("unexpected token"))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, Error> {
+        self.skip_whitespace();
+        match self.input.get(self.pos) {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, Error> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.input.get(self.pos) != Some(&b'"') {
+                return Err(self.error("expected a member name"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            let value = self.parse_value()?;
+            members.push((key, value));
+
+            self.skip_whitespace();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, Error> {
+        self.pos += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, Error> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let b = match self.input.get(self.pos) {
+                Some(b) => *b,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escape = match self.input.get(self.pos) {
+                        Some(b) => *b,
+                        None => return Err(self.error("unterminated escape")),
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, Error> {
+        let high = self.parse_hex4()?;
+        if (0xd800..0xdc00).contains(&high) && self.input[self.pos..].starts_with(b"\\u") {
+            self.pos += 2;
+            let low = self.parse_hex4()?;
+            if (0xdc00..0xe000).contains(&low) {
+                let c = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                return Ok(char::from_u32(c).unwrap_or('\u{fffd}'));
+            }
+            return Ok('\u{fffd}');
+        }
+        Ok(char::from_u32(high).unwrap_or('\u{fffd}'))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, Error> {
+        let digits = match self.input.get(self.pos..self.pos + 4) {
+            Some(digits) => digits,
+            None => return Err(self.error("truncated unicode escape")),
+        };
+        let mut value = 0;
+        for &d in digits {
+            value = value * 16
+                + (d as char)
+                    .to_digit(16)
+                    .ok_or_else(|| self.error("invalid unicode escape"))?;
+        }
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Json, Error> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+        core::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+}
+
+// The text runs painted on `page`, in paint order. End-to-end tests compare these against
+// what the document should show.
+pub fn painted_texts(page: &Page) -> Vec<String> {
+    page.display_items()
+        .into_iter()
+        .filter_map(|item| match item {
+            DisplayItem::Text { text, .. } => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::Browser;
+    use crate::fetch::{RedirectFetcher, RedirectPolicy};
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::new(
+            Method::Get,
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    #[test]
+    fn test_canned_responses_in_order() {
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .add_html("http://example.com", "<p>first</p>")
+            .unwrap();
+        fetcher
+            .add_raw(
+                "http://example.com/",
+                b"HTTP/1.1 503 Service Unavailable\r\n\r\nbusy",
+            )
+            .unwrap();
+
+        let first = fetcher.fetch(&get("http://example.com/#top")).unwrap();
+        assert_eq!(first.status_code(), 200);
+        assert_eq!(first.body_text(), "<p>first</p>");
+        for _ in 0..2 {
+            let next = fetcher.fetch(&get("http://example.com/")).unwrap();
+            assert_eq!(next.status_code(), 503);
+        }
+        assert_eq!(fetcher.requests().len(), 3);
+        assert!(fetcher.fetch(&get("http://example.com/missing")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_fixture_headers() {
+        assert!(Fixture::with_status(200, &[("Bad Name", "x")], b"").is_err());
+        assert!(Fixture::with_status(200, &[("X-Note", "a\r\nSet-Cookie: b=c")], b"").is_err());
+
+        let mut fetcher = FixtureFetcher::new();
+        assert!(fetcher
+            .add_redirect("http://example.com/", 302, "/next\r\n")
+            .is_err());
+    }
+
+    #[test]
+    fn test_redirects_failures_and_delays() {
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .add_redirect("http://example.com/old", 301, "/new")
+            .unwrap();
+        let mut slow = Fixture::with_status(200, &[], b"moved here").unwrap();
+        slow.set_delay(150);
+        fetcher.add("http://example.com/new", slow).unwrap();
+        fetcher
+            .add_failure("http://example.com/down", "connection refused")
+            .unwrap();
+
+        let mut redirecting = RedirectFetcher::new(fetcher, RedirectPolicy::default());
+        let response = redirecting
+            .fetch(&get("http://example.com/old"))
+            .expect("failed to follow the redirect");
+        assert_eq!(response.body_text(), "moved here");
+        assert!(response.redirected());
+        assert_eq!(redirecting.inner_mut().elapsed(), 150);
+
+        assert_eq!(
+            redirecting
+                .fetch(&get("http://example.com/down"))
+                .map(|r| r.status_code()),
+            Err(Error::Network("connection refused".to_string()))
+        );
+
+        redirecting.inner_mut().set_timeout(Some(100));
+        assert!(redirecting.fetch(&get("http://example.com/new")).is_err());
+        assert_eq!(redirecting.inner_mut().elapsed(), 250);
+    }
+
+    #[test]
+    fn test_load_har() {
+        let har = r#"{
+            "log": {
+                "version": "1.2",
+                "entries": [
+                    {
+                        "request": {"method": "GET", "url": "http://example.com/"},
+                        "response": {
+                            "status": 200,
+                            "headers": [
+                                {"name": "Content-Type", "value": "text/html"},
+                                {"name": "Content-Encoding", "value": "gzip"}
+                            ],
+                            "content": {"text": "<p>café 😀</p>"}
+                        },
+                        "time": 42.5
+                    },
+                    {
+                        "request": {"method": "GET", "url": "http://example.com/logo.png"},
+                        "response": {
+                            "status": 200,
+                            "headers": [],
+                            "content": {"text": "iVBORw0K", "encoding": "base64"}
+                        }
+                    },
+                    {
+                        "request": {"method": "POST", "url": "http://example.com/"},
+                        "_failure": "reset"
+                    }
+                ]
+            }
+        }"#;
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .load_har(har)
+            .expect("failed to load the HAR archive");
+
+        let page = fetcher.fetch(&get("http://example.com/")).unwrap();
+        assert_eq!(page.body_text(), "<p>café 😀</p>");
+        assert_eq!(page.header_value("Content-Encoding"), None);
+        assert_eq!(fetcher.elapsed(), 42);
+
+        let logo = fetcher.fetch(&get("http://example.com/logo.png")).unwrap();
+        assert_eq!(logo.body(), vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a]);
+
+        let post = HttpRequest::new(Method::Post, get("http://example.com/").url());
+        assert!(fetcher.fetch(&post).is_err());
+
+        assert!(fetcher.load_har("{\"log\": {}}").is_err());
+        assert!(fetcher.load_har("{\"log\": ").is_err());
+    }
+
+    #[test]
+    fn test_full_page_load() {
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .add_redirect("http://example.com/", 302, "/ja/index.html")
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/ja/index.html",
+                Fixture::with_status(
+                    200,
+                    &[("Content-Type", "text/html; charset=Shift_JIS")],
+                    &[
+                        b'<', b'p', b'>', 0x93, 0xfa, 0x96, 0x7b, b'<', b'/', b'p', b'>', b'\n',
+                    ],
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let mut fetcher = RedirectFetcher::new(fetcher, RedirectPolicy::default());
+
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+        page.borrow_mut()
+            .load(&mut fetcher, &get("http://example.com/"))
+            .expect("failed to load the page");
+
+        let page = page.borrow();
+        assert_eq!(
+            page.url().map(|u| u.href()),
+            Some("http://example.com/ja/index.html".to_string())
+        );
+        assert_eq!(painted_texts(&page), vec!["日本".to_string()]);
+    }
+}
@@ -0,0 +1,118 @@
+// Reads the intrinsic width and height of an encoded image without decoding its pixels.
+pub fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return png_dimensions(data);
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        let width = u16::from_le_bytes([*data.get(6)?, *data.get(7)?]);
+        let height = u16::from_le_bytes([*data.get(8)?, *data.get(9)?]);
+        return Some((width as u32, height as u32));
+    }
+    if data.starts_with(b"BM") {
+        return bmp_dimensions(data);
+    }
+    if data.starts_with(b"\xff\xd8") {
+        return jpeg_dimensions(data);
+    }
+    None
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    // The IHDR chunk must come first, right after the signature.
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be_u32(data, 16)?, be_u32(data, 20)?))
+}
+
+fn bmp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let header_size = u32::from_le_bytes(data.get(14..18)?.try_into().ok()?);
+    if header_size == 12 {
+        // The old OS/2 header stores 16-bit sizes.
+        let width = u16::from_le_bytes(data.get(18..20)?.try_into().ok()?);
+        let height = u16::from_le_bytes(data.get(20..22)?.try_into().ok()?);
+        return Some((width as u32, height as u32));
+    }
+    let width = i32::from_le_bytes(data.get(18..22)?.try_into().ok()?);
+    // A negative height marks a top-down bitmap.
+    let height = i32::from_le_bytes(data.get(22..26)?.try_into().ok()?);
+    Some((width.unsigned_abs(), height.unsigned_abs()))
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    loop {
+        // Markers may be preceded by any number of fill bytes.
+        while *data.get(i)? == 0xff && *data.get(i + 1)? == 0xff {
+            i += 1;
+        }
+        if *data.get(i)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        i += 2;
+        if marker == 0xd8 || marker == 0x01 || (0xd0..=0xd7).contains(&marker) {
+            continue;
+        }
+        if marker == 0xd9 || marker == 0xda {
+            return None;
+        }
+        let length = u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]) as usize;
+        // Every start-of-frame marker except DHT (c4), JPG (c8) and DAC (cc) carries the size.
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = u16::from_be_bytes([*data.get(i + 3)?, *data.get(i + 4)?]);
+            let width = u16::from_be_bytes([*data.get(i + 5)?, *data.get(i + 6)?]);
+            return Some((width as u32, height as u32));
+        }
+        if length < 2 {
+            return None;
+        }
+        i += length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_png_and_gif() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(image_dimensions(&png), Some((640, 480)));
+        assert_eq!(image_dimensions(&png[..20]), None);
+
+        assert_eq!(
+            image_dimensions(b"GIF89a\x10\x00\x20\x00\x00"),
+            Some((16, 32))
+        );
+        assert_eq!(image_dimensions(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_bmp() {
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&[0; 12]);
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&100i32.to_le_bytes());
+        bmp.extend_from_slice(&(-50i32).to_le_bytes());
+        assert_eq!(image_dimensions(&bmp), Some((100, 50)));
+    }
+
+    #[test]
+    fn test_jpeg() {
+        let mut jpeg: Vec<u8> = b"\xff\xd8".to_vec();
+        // An APP0 segment to skip, then a baseline frame header.
+        jpeg.extend_from_slice(b"\xff\xe0\x00\x06JFIF");
+        jpeg.extend_from_slice(b"\xff\xff\xc0\x00\x11\x08\x01\x2c\x02\x58\x03");
+        assert_eq!(image_dimensions(&jpeg), Some((600, 300)));
+        assert_eq!(image_dimensions(b"\xff\xd8\xff\xda\x00\x02"), None);
+    }
+}
@@ -0,0 +1,639 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::Error;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompressionFormat {
+    // Raw DEFLATE data as described in RFC 1951.
+    Deflate,
+    // DEFLATE data with the zlib header and Adler-32 trailer of RFC 1950.
+    Zlib,
+    // One or more gzip members as described in RFC 1952.
+    Gzip,
+}
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum InflateError {
+    // The input ended in the middle of a unit. Decoding resumes from the start of that unit
+    // when more input is fed.
+    NeedMore,
+    Corrupt(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    // Builds a canonical Huffman code from code lengths. Over-subscribed codes are rejected,
+    // and incomplete codes are accepted only when they contain a single code.
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        let mut left: i32 = 1;
+        for &count in counts.iter().skip(1) {
+            left <<= 1;
+            left -= count as i32;
+            if left < 0 {
+                return Err(InflateError::Corrupt("over-subscribed huffman code"));
+            }
+        }
+        let codes = lengths.len() - counts[0] as usize;
+        if left > 0 && codes > 1 {
+            return Err(InflateError::Corrupt("incomplete huffman code"));
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; codes];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn fixed() -> (Self, Self) {
+        let mut lengths = [0u8; 288];
+        for (symbol, len) in lengths.iter_mut().enumerate() {
+            *len = match symbol {
+                0..=143 => 8,
+                144..=255 => 9,
+                256..=279 => 7,
+                _ => 8,
+            };
+        }
+        let lit = Self::new(&lengths).expect("fixed literal/length code is valid");
+        // Distance symbols 30 and 31 complete the code but never appear in valid data.
+        let dist = Self::new(&[5u8; 32]).expect("fixed distance code is valid");
+        (lit, dist)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    Header,
+    BlockHeader,
+    Stored(usize),
+    Codes(Huffman, Huffman),
+    Trailer,
+    Done,
+}
+
+enum Next {
+    Stay,
+    Goto(State),
+}
+
+// A streaming decoder. Input can be fed in arbitrary pieces; `output` holds what has been decoded
+// so far, and `finish` returns all of it once the end of the stream has been reached.
+#[derive(Debug, Clone)]
+pub struct Inflater {
+    format: CompressionFormat,
+    state: State,
+    input: Vec<u8>,
+    bit_pos: usize,
+    output: Vec<u8>,
+    member_start: usize,
+    final_block: bool,
+}
+
+impl Inflater {
+    pub fn new(format: CompressionFormat) -> Self {
+        Self {
+            format,
+            state: State::Header,
+            input: Vec::new(),
+            bit_pos: 0,
+            output: Vec::new(),
+            member_start: 0,
+            final_block: false,
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.input.extend_from_slice(data);
+
+        loop {
+            let state = core::mem::replace(&mut self.state, State::Done);
+            let checkpoint = (self.bit_pos, self.output.len());
+            match self.step(&state) {
+                Ok(Next::Stay) => self.state = state,
+                Ok(Next::Goto(next)) => self.state = next,
+                Err(e) => {
+                    self.state = state;
+                    self.bit_pos = checkpoint.0;
+                    self.output.truncate(checkpoint.1);
+                    match e {
+                        InflateError::NeedMore => break,
+                        InflateError::Corrupt(reason) => {
+                            return Err(Error::UnexpectedInput(format!(
+                                "invalid compressed data: {}",
+                                reason
+                            )))
+                        }
+                    }
+                }
+            }
+            if matches!(self.state, State::Done) && self.bit_pos / 8 >= self.input.len() {
+                break;
+            }
+        }
+
+        let consumed = self.bit_pos / 8;
+        self.input.drain(..consumed);
+        self.bit_pos -= consumed * 8;
+        Ok(())
+    }
+
+    pub fn format(&self) -> CompressionFormat {
+        self.format
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        if !self.is_done() {
+            return Err(Error::UnexpectedInput(
+                "compressed data ended before the end of the stream".into(),
+            ));
+        }
+        Ok(self.output)
+    }
+
+    fn step(&mut self, state: &State) -> Result<Next, InflateError> {
+        match state {
+            State::Header => {
+                self.read_header()?;
+                self.member_start = self.output.len();
+                self.final_block = false;
+                Ok(Next::Goto(State::BlockHeader))
+            }
+            State::BlockHeader => self.read_block_header().map(Next::Goto),
+            State::Stored(remaining) => {
+                if *remaining == 0 {
+                    return Ok(Next::Goto(self.end_of_block()));
+                }
+                let start = self.bit_pos / 8;
+                let n = core::cmp::min(*remaining, self.input.len() - start);
+                if n == 0 {
+                    return Err(InflateError::NeedMore);
+                }
+                self.output.extend_from_slice(&self.input[start..start + n]);
+                self.bit_pos += n * 8;
+                Ok(Next::Goto(State::Stored(remaining - n)))
+            }
+            State::Codes(lit, dist) => {
+                if self.decode_symbol(lit, dist)? {
+                    Ok(Next::Goto(self.end_of_block()))
+                } else {
+                    Ok(Next::Stay)
+                }
+            }
+            State::Trailer => {
+                self.align_to_byte();
+                self.read_trailer()?;
+                Ok(Next::Goto(State::Done))
+            }
+            State::Done => {
+                // Another gzip member may follow. Any other trailing data is ignored.
+                let start = self.bit_pos / 8;
+                if self.format == CompressionFormat::Gzip && self.input.get(start) == Some(&0x1f) {
+                    match self.input.get(start + 1) {
+                        Some(0x8b) => return Ok(Next::Goto(State::Header)),
+                        Some(_) => {}
+                        None => return Err(InflateError::NeedMore),
+                    }
+                }
+                self.bit_pos = self.input.len() * 8;
+                Ok(Next::Stay)
+            }
+        }
+    }
+
+    fn end_of_block(&self) -> State {
+        if self.final_block {
+            State::Trailer
+        } else {
+            State::BlockHeader
+        }
+    }
+
+    fn read_header(&mut self) -> Result<(), InflateError> {
+        match self.format {
+            CompressionFormat::Deflate => Ok(()),
+            CompressionFormat::Zlib => {
+                let cmf = self.read_byte()?;
+                let flg = self.read_byte()?;
+                if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+                    return Err(InflateError::Corrupt("unsupported zlib compression method"));
+                }
+                if ((cmf as u16) << 8 | flg as u16) % 31 != 0 {
+                    return Err(InflateError::Corrupt("zlib header check failed"));
+                }
+                if flg & 0x20 != 0 {
+                    return Err(InflateError::Corrupt(
+                        "zlib preset dictionary is not supported",
+                    ));
+                }
+                Ok(())
+            }
+            CompressionFormat::Gzip => {
+                let start = self.bit_pos / 8;
+                if self.read_byte()? != 0x1f || self.read_byte()? != 0x8b {
+                    return Err(InflateError::Corrupt("invalid gzip magic number"));
+                }
+                if self.read_byte()? != 8 {
+                    return Err(InflateError::Corrupt("unsupported gzip compression method"));
+                }
+                let flags = self.read_byte()?;
+                if flags & 0xe0 != 0 {
+                    return Err(InflateError::Corrupt("reserved gzip flags are set"));
+                }
+                // MTIME, XFL and OS.
+                for _ in 0..6 {
+                    self.read_byte()?;
+                }
+                if flags & 0x04 != 0 {
+                    let xlen = self.read_u16_le()?;
+                    for _ in 0..xlen {
+                        self.read_byte()?;
+                    }
+                }
+                // FNAME and FCOMMENT are zero-terminated.
+                for flag in [0x08, 0x10] {
+                    if flags & flag != 0 {
+                        while self.read_byte()? != 0 {}
+                    }
+                }
+                if flags & 0x02 != 0 {
+                    let crc = crc32(&self.input[start..self.bit_pos / 8]);
+                    if self.read_u16_le()? != crc as u16 {
+                        return Err(InflateError::Corrupt("gzip header checksum mismatch"));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn read_trailer(&mut self) -> Result<(), InflateError> {
+        let output = &self.output[self.member_start..];
+        match self.format {
+            CompressionFormat::Deflate => Ok(()),
+            CompressionFormat::Zlib => {
+                let expected = adler32(output);
+                let mut checksum = 0u32;
+                for _ in 0..4 {
+                    checksum = checksum << 8 | self.read_byte()? as u32;
+                }
+                if checksum != expected {
+                    return Err(InflateError::Corrupt("zlib checksum mismatch"));
+                }
+                Ok(())
+            }
+            CompressionFormat::Gzip => {
+                let expected_crc = crc32(output);
+                let expected_size = output.len() as u32;
+                if self.read_u32_le()? != expected_crc {
+                    return Err(InflateError::Corrupt("gzip checksum mismatch"));
+                }
+                if self.read_u32_le()? != expected_size {
+                    return Err(InflateError::Corrupt("gzip size mismatch"));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn read_block_header(&mut self) -> Result<State, InflateError> {
+        self.final_block = self.read_bits(1)? == 1;
+        match self.read_bits(2)? {
+            0 => {
+                self.align_to_byte();
+                let len = self.read_u16_le()?;
+                let nlen = self.read_u16_le()?;
+                if len != !nlen {
+                    return Err(InflateError::Corrupt("stored block length mismatch"));
+                }
+                Ok(State::Stored(len as usize))
+            }
+            1 => {
+                let (lit, dist) = Huffman::fixed();
+                Ok(State::Codes(lit, dist))
+            }
+            2 => self.read_dynamic_codes(),
+            _ => Err(InflateError::Corrupt("invalid block type")),
+        }
+    }
+
+    fn read_dynamic_codes(&mut self) -> Result<State, InflateError> {
+        let nlen = self.read_bits(5)? as usize + 257;
+        let ndist = self.read_bits(5)? as usize + 1;
+        let ncode = self.read_bits(4)? as usize + 4;
+        if nlen > 286 || ndist > 30 {
+            return Err(InflateError::Corrupt("too many length or distance codes"));
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &index in CODE_LENGTH_ORDER.iter().take(ncode) {
+            code_lengths[index] = self.read_bits(3)? as u8;
+        }
+        let code = Huffman::new(&code_lengths)?;
+
+        let mut lengths = vec![0u8; nlen + ndist];
+        let mut i = 0;
+        while i < nlen + ndist {
+            let symbol = self.decode(&code)?;
+            let (len, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    if i == 0 {
+                        return Err(InflateError::Corrupt("repeat with no previous length"));
+                    }
+                    (lengths[i - 1], 3 + self.read_bits(2)? as usize)
+                }
+                17 => (0, 3 + self.read_bits(3)? as usize),
+                _ => (0, 11 + self.read_bits(7)? as usize),
+            };
+            if i + repeat > nlen + ndist {
+                return Err(InflateError::Corrupt("too many code lengths"));
+            }
+            lengths[i..i + repeat].fill(len);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(InflateError::Corrupt("missing end-of-block code"));
+        }
+
+        let lit = Huffman::new(&lengths[..nlen])?;
+        let dist = Huffman::new(&lengths[nlen..])?;
+        Ok(State::Codes(lit, dist))
+    }
+
+    // Decodes one literal or one length/distance pair. Returns true at the end of the block.
+    fn decode_symbol(&mut self, lit: &Huffman, dist: &Huffman) -> Result<bool, InflateError> {
+        let symbol = self.decode(lit)? as usize;
+        if symbol < 256 {
+            self.output.push(symbol as u8);
+            return Ok(false);
+        }
+        if symbol == 256 {
+            return Ok(true);
+        }
+
+        let index = symbol - 257;
+        if index >= LENGTH_BASE.len() {
+            return Err(InflateError::Corrupt("invalid length symbol"));
+        }
+        let len =
+            LENGTH_BASE[index] as usize + self.read_bits(LENGTH_EXTRA[index] as usize)? as usize;
+
+        let index = self.decode(dist)? as usize;
+        if index >= DIST_BASE.len() {
+            return Err(InflateError::Corrupt("invalid distance symbol"));
+        }
+        let distance =
+            DIST_BASE[index] as usize + self.read_bits(DIST_EXTRA[index] as usize)? as usize;
+        if distance > self.output.len() - self.member_start {
+            return Err(InflateError::Corrupt("distance is too far back"));
+        }
+
+        let start = self.output.len() - distance;
+        for i in 0..len {
+            let b = self.output[start + i];
+            self.output.push(b);
+        }
+        Ok(false)
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, InflateError> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..=MAX_BITS {
+            code |= self.read_bits(1)? as i32;
+            let count = huffman.counts[len] as i32;
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(InflateError::Corrupt("invalid huffman code"))
+    }
+
+    fn read_bits(&mut self, n: usize) -> Result<u32, InflateError> {
+        let mut value = 0u32;
+        for i in 0..n {
+            let byte = match self.input.get(self.bit_pos / 8) {
+                Some(byte) => *byte,
+                None => return Err(InflateError::NeedMore),
+            };
+            let bit = (byte >> (self.bit_pos % 8)) & 1;
+            value |= (bit as u32) << i;
+            self.bit_pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, InflateError> {
+        self.read_bits(8).map(|b| b as u8)
+    }
+
+    fn read_u16_le(&mut self) -> Result<u16, InflateError> {
+        self.read_bits(16).map(|b| b as u16)
+    }
+
+    fn read_u32_le(&mut self) -> Result<u32, InflateError> {
+        self.read_bits(32)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_pos = (self.bit_pos + 7) / 8 * 8;
+    }
+}
+
+pub fn inflate(data: &[u8], format: CompressionFormat) -> Result<Vec<u8>, Error> {
+    let mut inflater = Inflater::new(format);
+    inflater.feed(data)?;
+    inflater.finish()
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO: &[u8] = b"hello, hello, hello, world!";
+
+    const HELLO_ZLIB: [u8; 23] = [
+        0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xc8, 0x40, 0xa1, 0xca, 0xf3, 0x8b,
+        0x72, 0x52, 0x14, 0x01, 0x85, 0xfa, 0x09, 0x6a,
+    ];
+
+    const HELLO_GZIP: [u8; 46] = [
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x69, 0x6e, 0x64, 0x65, 0x78,
+        0x2e, 0x68, 0x74, 0x6d, 0x6c, 0x00, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xc8, 0x40,
+        0xa1, 0xca, 0xf3, 0x8b, 0x72, 0x52, 0x14, 0x01, 0x83, 0x89, 0x1f, 0x6e, 0x1b, 0x00, 0x00,
+        0x00,
+    ];
+
+    #[test]
+    fn test_stored_and_fixed_blocks() {
+        let stored = [
+            0x01, 0x06, 0x00, 0xf9, 0xff, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64,
+        ];
+        assert_eq!(
+            inflate(&stored, CompressionFormat::Deflate),
+            Ok(b"stored".to_vec())
+        );
+        assert_eq!(
+            inflate(&HELLO_ZLIB[2..19], CompressionFormat::Deflate),
+            Ok(HELLO.to_vec())
+        );
+        assert_eq!(
+            inflate(&HELLO_ZLIB, CompressionFormat::Zlib),
+            Ok(HELLO.to_vec())
+        );
+    }
+
+    #[test]
+    fn test_dynamic_block() {
+        let compressed = [
+            0x78, 0xda, 0x0d, 0xcb, 0xc7, 0x15, 0x80, 0x20, 0x10, 0x45, 0xd1, 0x56, 0xbe, 0x0d,
+            0x78, 0xcc, 0xa1, 0x0b, 0x17, 0x36, 0x60, 0x40, 0xc0, 0xc0, 0x08, 0x8a, 0xa9, 0x7a,
+            0x67, 0xfd, 0xee, 0x6b, 0x95, 0x80, 0xf5, 0x7a, 0x58, 0xd0, 0x3b, 0xba, 0x0d, 0x26,
+            0x7a, 0x30, 0xfb, 0x6d, 0x3f, 0x40, 0x97, 0x70, 0x38, 0x39, 0xaf, 0xdd, 0xf7, 0x62,
+            0x24, 0x19, 0xa2, 0xe9, 0xd8, 0x6d, 0x2f, 0x7a, 0x46, 0xb7, 0x3e, 0x15, 0x26, 0x7d,
+            0x09, 0x4e, 0x9f, 0x30, 0x58, 0xb5, 0xf5, 0xe4, 0xf8, 0x95, 0x47, 0x80, 0x28, 0x4e,
+            0xd2, 0x2c, 0x2f, 0xca, 0xaa, 0xfe, 0x01, 0x87, 0x4d, 0x20, 0xdf,
+        ];
+        assert_eq!(
+            inflate(&compressed, CompressionFormat::Zlib),
+            Ok(b"The quick brown fox jumps over the lazy dog. Pack my box with five dozen liquor jugs! 0123456789".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_gzip_members() {
+        assert_eq!(
+            inflate(&HELLO_GZIP, CompressionFormat::Gzip),
+            Ok(HELLO.to_vec())
+        );
+
+        let mut twice = HELLO_GZIP.to_vec();
+        twice.extend_from_slice(&HELLO_GZIP);
+        let mut expected = HELLO.to_vec();
+        expected.extend_from_slice(HELLO);
+        assert_eq!(inflate(&twice, CompressionFormat::Gzip), Ok(expected));
+    }
+
+    #[test]
+    fn test_streaming_input() {
+        let mut inflater = Inflater::new(CompressionFormat::Gzip);
+        for b in HELLO_GZIP.iter() {
+            assert!(!inflater.is_done());
+            inflater
+                .feed(core::slice::from_ref(b))
+                .expect("failed to feed compressed data");
+        }
+        assert!(inflater.is_done());
+        assert_eq!(inflater.finish(), Ok(HELLO.to_vec()));
+
+        let mut inflater = Inflater::new(CompressionFormat::Zlib);
+        inflater
+            .feed(&HELLO_ZLIB[..10])
+            .expect("failed to feed compressed data");
+        assert!(inflater.finish().is_err());
+    }
+
+    #[test]
+    fn test_corrupt_data() {
+        let mut bad_checksum = HELLO_ZLIB;
+        bad_checksum[22] ^= 1;
+        assert!(inflate(&bad_checksum, CompressionFormat::Zlib).is_err());
+
+        let mut bad_size = HELLO_GZIP;
+        bad_size[43] ^= 1;
+        assert!(inflate(&bad_size, CompressionFormat::Gzip).is_err());
+
+        assert!(inflate(&[0x78, 0x9d], CompressionFormat::Zlib).is_err());
+        assert!(inflate(&[0x1f, 0x8c], CompressionFormat::Gzip).is_err());
+        // A block with the reserved block type 3.
+        assert!(inflate(&[0x07], CompressionFormat::Deflate).is_err());
+        // A stored block whose NLEN is not the complement of LEN.
+        assert!(inflate(
+            &[0x01, 0x01, 0x00, 0x00, 0x00, 0x61],
+            CompressionFormat::Deflate
+        )
+        .is_err());
+        // A fixed block that copies from before the start of the output.
+        assert!(inflate(&[0x03, 0x02, 0x00], CompressionFormat::Deflate).is_err());
+    }
+}
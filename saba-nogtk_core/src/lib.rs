@@ -2,11 +2,29 @@
 
 extern crate alloc;
 
+pub mod auth;
+pub mod base64;
 pub mod browser;
+pub mod cache;
+pub mod clock;
 pub mod constants;
+pub mod cookie;
 pub mod display_item;
+pub mod dns;
+pub mod encoding;
 pub mod error;
+pub mod fetch;
+pub mod fixture;
+pub mod hash;
 pub mod http;
+pub mod image;
+pub mod inflate;
+pub mod mime;
+pub mod proxy;
+pub mod random;
 pub mod renderer;
+pub mod tls;
+pub mod transport;
 pub mod url;
 pub mod utils;
+pub mod websocket;
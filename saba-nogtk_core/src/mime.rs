@@ -0,0 +1,488 @@
+use alloc::string::{String, ToString};
+
+use crate::http::ContentType;
+
+// Only this much of a resource is examined, as in the MIME Sniffing Standard.
+const RESOURCE_HEADER_LEN: usize = 1445;
+
+// A byte pattern with a mask; bytes in `ignored` are skipped before matching.
+struct Pattern {
+    pattern: &'static [u8],
+    mask: &'static [u8],
+    ignored: &'static [u8],
+    mime_type: &'static str,
+}
+
+const WHITESPACE: &[u8] = b"\t\n\x0c\r ";
+
+// The HTML signatures are matched case-insensitively and, except for the comment opener, must
+// end with a tag-terminating byte.
+const HTML_SIGNATURES: [&[u8]; 17] = [
+    b"<!DOCTYPE HTML",
+    b"<HTML",
+    b"<HEAD",
+    b"<SCRIPT",
+    b"<IFRAME",
+    b"<H1",
+    b"<DIV",
+    b"<FONT",
+    b"<TABLE",
+    b"<A",
+    b"<STYLE",
+    b"<TITLE",
+    b"<B",
+    b"<BODY",
+    b"<BR",
+    b"<P",
+    b"<!--",
+];
+
+const SCRIPTABLE_PATTERNS: [Pattern; 2] = [
+    Pattern {
+        pattern: b"<?xml",
+        mask: b"\xff\xff\xff\xff\xff",
+        ignored: WHITESPACE,
+        mime_type: "text/xml",
+    },
+    Pattern {
+        pattern: b"%PDF-",
+        mask: b"\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "application/pdf",
+    },
+];
+
+const TEXT_PATTERNS: [Pattern; 4] = [
+    Pattern {
+        pattern: b"%!PS-Adobe-",
+        mask: b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "application/postscript",
+    },
+    Pattern {
+        pattern: b"\xfe\xff\x00\x00",
+        mask: b"\xff\xff\x00\x00",
+        ignored: b"",
+        mime_type: "text/plain",
+    },
+    Pattern {
+        pattern: b"\xff\xfe\x00\x00",
+        mask: b"\xff\xff\x00\x00",
+        ignored: b"",
+        mime_type: "text/plain",
+    },
+    Pattern {
+        pattern: b"\xef\xbb\xbf\x00",
+        mask: b"\xff\xff\xff\x00",
+        ignored: b"",
+        mime_type: "text/plain",
+    },
+];
+
+const IMAGE_PATTERNS: [Pattern; 8] = [
+    Pattern {
+        pattern: b"\x00\x00\x01\x00",
+        mask: b"\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/x-icon",
+    },
+    Pattern {
+        pattern: b"\x00\x00\x02\x00",
+        mask: b"\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/x-icon",
+    },
+    Pattern {
+        pattern: b"BM",
+        mask: b"\xff\xff",
+        ignored: b"",
+        mime_type: "image/bmp",
+    },
+    Pattern {
+        pattern: b"GIF87a",
+        mask: b"\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/gif",
+    },
+    Pattern {
+        pattern: b"GIF89a",
+        mask: b"\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/gif",
+    },
+    Pattern {
+        pattern: b"RIFF\x00\x00\x00\x00WEBPVP",
+        mask: b"\xff\xff\xff\xff\x00\x00\x00\x00\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/webp",
+    },
+    Pattern {
+        pattern: b"\x89PNG\r\n\x1a\n",
+        mask: b"\xff\xff\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/png",
+    },
+    Pattern {
+        pattern: b"\xff\xd8\xff",
+        mask: b"\xff\xff\xff",
+        ignored: b"",
+        mime_type: "image/jpeg",
+    },
+];
+
+const AUDIO_VIDEO_PATTERNS: [Pattern; 6] = [
+    Pattern {
+        pattern: b"FORM\x00\x00\x00\x00AIFF",
+        mask: b"\xff\xff\xff\xff\x00\x00\x00\x00\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "audio/aiff",
+    },
+    Pattern {
+        pattern: b"ID3",
+        mask: b"\xff\xff\xff",
+        ignored: b"",
+        mime_type: "audio/mpeg",
+    },
+    Pattern {
+        pattern: b"OggS\x00",
+        mask: b"\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "application/ogg",
+    },
+    Pattern {
+        pattern: b"MThd\x00\x00\x00\x06",
+        mask: b"\xff\xff\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "audio/midi",
+    },
+    Pattern {
+        pattern: b"RIFF\x00\x00\x00\x00AVI ",
+        mask: b"\xff\xff\xff\xff\x00\x00\x00\x00\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "video/avi",
+    },
+    Pattern {
+        pattern: b"RIFF\x00\x00\x00\x00WAVE",
+        mask: b"\xff\xff\xff\xff\x00\x00\x00\x00\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "audio/wave",
+    },
+];
+
+const ARCHIVE_PATTERNS: [Pattern; 3] = [
+    Pattern {
+        pattern: b"\x1f\x8b\x08",
+        mask: b"\xff\xff\xff",
+        ignored: b"",
+        mime_type: "application/x-gzip",
+    },
+    Pattern {
+        pattern: b"PK\x03\x04",
+        mask: b"\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "application/zip",
+    },
+    Pattern {
+        pattern: b"Rar \x1a\x07\x00",
+        mask: b"\xff\xff\xff\xff\xff\xff\xff",
+        ignored: b"",
+        mime_type: "application/x-rar-compressed",
+    },
+];
+
+impl Pattern {
+    fn matches(&self, input: &[u8]) -> bool {
+        let start = input
+            .iter()
+            .position(|b| !self.ignored.contains(b))
+            .unwrap_or(input.len());
+        let input = &input[start..];
+        input.len() >= self.pattern.len()
+            && self
+                .pattern
+                .iter()
+                .zip(self.mask)
+                .zip(input)
+                .all(|((p, m), b)| b & m == *p)
+    }
+}
+
+fn match_patterns(patterns: &[Pattern], input: &[u8]) -> Option<&'static str> {
+    patterns
+        .iter()
+        .find(|p| p.matches(input))
+        .map(|p| p.mime_type)
+}
+
+fn matches_html(input: &[u8]) -> bool {
+    let start = input
+        .iter()
+        .position(|b| !WHITESPACE.contains(b))
+        .unwrap_or(input.len());
+    let input = &input[start..];
+    HTML_SIGNATURES.iter().any(|signature| {
+        if input.len() < signature.len()
+            || !input[..signature.len()].eq_ignore_ascii_case(signature)
+        {
+            return false;
+        }
+        *signature == b"<!--" || matches!(input.get(signature.len()), Some(b' ' | b'>'))
+    })
+}
+
+fn is_binary_data_byte(b: u8) -> bool {
+    matches!(b, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f)
+}
+
+// The "mp4" signature: an ftyp box whose major or compatible brands include "mp4".
+fn matches_mp4(input: &[u8]) -> bool {
+    if input.len() < 12 {
+        return false;
+    }
+    let box_size = u32::from_be_bytes([input[0], input[1], input[2], input[3]]) as usize;
+    if input.len() < box_size || box_size % 4 != 0 || &input[4..8] != b"ftyp" {
+        return false;
+    }
+    if &input[8..11] == b"mp4" {
+        return true;
+    }
+    (16..box_size)
+        .step_by(4)
+        .any(|offset| input.get(offset..offset + 3) == Some(b"mp4"))
+}
+
+// The "webm" signature: an EBML header whose DocType element is "webm".
+fn matches_webm(input: &[u8]) -> bool {
+    if input.len() < 4 || input[..4] != [0x1a, 0x45, 0xdf, 0xa3] {
+        return false;
+    }
+    let mut iter = 4;
+    while iter < input.len() && iter < 38 {
+        if input[iter..].starts_with(&[0x42, 0x82]) {
+            iter += 2;
+            if iter >= input.len() {
+                break;
+            }
+            // The element size is a variable-length integer; its first byte gives its length.
+            let size_len = input[iter].leading_zeros() as usize + 1;
+            iter += size_len;
+            if iter + 4 > input.len() {
+                break;
+            }
+            if &input[iter..iter + 4] == b"webm" {
+                return true;
+            }
+        }
+        iter += 1;
+    }
+    false
+}
+
+fn match_image(input: &[u8]) -> Option<&'static str> {
+    match_patterns(&IMAGE_PATTERNS, input)
+}
+
+fn match_audio_video(input: &[u8]) -> Option<&'static str> {
+    if let Some(mime_type) = match_patterns(&AUDIO_VIDEO_PATTERNS, input) {
+        return Some(mime_type);
+    }
+    if matches_mp4(input) {
+        return Some("video/mp4");
+    }
+    if matches_webm(input) {
+        return Some("video/webm");
+    }
+    None
+}
+
+// "Rules for identifying an unknown MIME type".
+fn identify_unknown(input: &[u8], sniff_scriptable: bool) -> &'static str {
+    if sniff_scriptable {
+        if matches_html(input) {
+            return "text/html";
+        }
+        if let Some(mime_type) = match_patterns(&SCRIPTABLE_PATTERNS, input) {
+            return mime_type;
+        }
+    }
+    match_patterns(&TEXT_PATTERNS, input)
+        .or_else(|| match_image(input))
+        .or_else(|| match_audio_video(input))
+        .or_else(|| match_patterns(&ARCHIVE_PATTERNS, input))
+        .unwrap_or_else(|| {
+            if input.iter().any(|b| is_binary_data_byte(*b)) {
+                "application/octet-stream"
+            } else {
+                "text/plain"
+            }
+        })
+}
+
+// "Rules for distinguishing if a resource is text or binary".
+fn distinguish_text_or_binary(input: &[u8]) -> &'static str {
+    if input.starts_with(b"\xfe\xff")
+        || input.starts_with(b"\xff\xfe")
+        || input.starts_with(b"\xef\xbb\xbf")
+        || !input.iter().any(|b| is_binary_data_byte(*b))
+    {
+        return "text/plain";
+    }
+    identify_unknown(input, false)
+}
+
+pub fn is_xml_type(mime_type: &str) -> bool {
+    mime_type.ends_with("+xml") || mime_type == "text/xml" || mime_type == "application/xml"
+}
+
+pub fn is_image_type(mime_type: &str) -> bool {
+    mime_type.starts_with("image/")
+}
+
+fn is_audio_video_type(mime_type: &str) -> bool {
+    mime_type.starts_with("audio/")
+        || mime_type.starts_with("video/")
+        || mime_type == "application/ogg"
+}
+
+// Determines the computed MIME type of a resource following the MIME Sniffing Standard.
+// `supplied` is the raw Content-Type header value, and `no_sniff` reflects
+// `X-Content-Type-Options: nosniff`. Returns the essence, as in "text/html".
+pub fn sniff_mime_type(supplied: Option<&str>, no_sniff: bool, resource: &[u8]) -> String {
+    let input = &resource[..core::cmp::min(resource.len(), RESOURCE_HEADER_LEN)];
+    let content_type = supplied.and_then(ContentType::parse);
+    let mime_type = match &content_type {
+        Some(content_type) => content_type.mime_type(),
+        None => return identify_unknown(input, !no_sniff).to_string(),
+    };
+    if matches!(
+        mime_type.as_str(),
+        "unknown/unknown" | "application/unknown" | "*/*"
+    ) {
+        return identify_unknown(input, !no_sniff).to_string();
+    }
+    if no_sniff {
+        return mime_type;
+    }
+
+    // Servers used to label everything text/plain, so such labels are double-checked.
+    if matches!(
+        supplied.map(|s| s.trim()),
+        Some("text/plain")
+            | Some("text/plain; charset=ISO-8859-1")
+            | Some("text/plain; charset=iso-8859-1")
+            | Some("text/plain; charset=UTF-8")
+    ) {
+        return distinguish_text_or_binary(input).to_string();
+    }
+    if is_xml_type(&mime_type) || mime_type == "text/html" {
+        return mime_type;
+    }
+    if is_image_type(&mime_type) {
+        if let Some(sniffed) = match_image(input) {
+            return sniffed.to_string();
+        }
+    }
+    if is_audio_video_type(&mime_type) {
+        if let Some(sniffed) = match_audio_video(input) {
+            return sniffed.to_string();
+        }
+    }
+    mime_type
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_types() {
+        assert_eq!(
+            sniff_mime_type(None, false, b"  \n<!doctype html><p>hi"),
+            "text/html"
+        );
+        assert_eq!(sniff_mime_type(None, false, b"<p>x"), "text/html");
+        assert_eq!(sniff_mime_type(None, false, b"<pre>x"), "text/plain");
+        assert_eq!(sniff_mime_type(None, false, b"<!--x-->"), "text/html");
+        assert_eq!(sniff_mime_type(None, false, b"<?xml version"), "text/xml");
+        assert_eq!(
+            sniff_mime_type(Some("*/*"), false, b"%PDF-1.7"),
+            "application/pdf"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("application/unknown"), false, b"\x89PNG\r\n\x1a\n...."),
+            "image/png"
+        );
+        assert_eq!(sniff_mime_type(None, false, b"plain words"), "text/plain");
+        assert_eq!(
+            sniff_mime_type(None, false, b"\x00\x01binary"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            sniff_mime_type(None, false, b"PK\x03\x04rest"),
+            "application/zip"
+        );
+        // Scriptable types are never sniffed when the server forbids it.
+        assert_eq!(sniff_mime_type(None, true, b"<html>"), "text/plain");
+    }
+
+    #[test]
+    fn test_supplied_types() {
+        assert_eq!(
+            sniff_mime_type(Some("text/html; charset=utf-8"), false, b"plain"),
+            "text/html"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("Application/JSON"), false, b"{}"),
+            "application/json"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("image/png"), false, b"GIF89a......"),
+            "image/gif"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("image/png"), false, b"not an image"),
+            "image/png"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("image/png"), true, b"GIF89a......"),
+            "image/png"
+        );
+        assert_eq!(
+            sniff_mime_type(
+                Some("video/x-unknown"),
+                false,
+                b"\x1a\x45\xdf\xa3\x42\x82\x84webm"
+            ),
+            "video/webm"
+        );
+        assert_eq!(
+            sniff_mime_type(
+                Some("video/unknown"),
+                false,
+                b"\x00\x00\x00\x18ftypisom\x00\x00\x00\x00mp41iso5"
+            ),
+            "video/mp4"
+        );
+    }
+
+    #[test]
+    fn test_apache_bug_check() {
+        assert_eq!(
+            sniff_mime_type(Some("text/plain"), false, b"\x89PNG\r\n\x1a\n...."),
+            "image/png"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("text/plain; charset=UTF-8"), false, b"\x00\x00\x00"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            sniff_mime_type(Some("text/plain"), false, b"<html>"),
+            "text/plain"
+        );
+        // Any other text/plain label is trusted.
+        assert_eq!(
+            sniff_mime_type(Some("text/plain; charset=shift_jis"), false, b"\x00\x00"),
+            "text/plain"
+        );
+    }
+}
@@ -0,0 +1,6 @@
+use crate::error::Error;
+
+// A source of unpredictable bytes, such as the operating system's random number generator.
+pub trait RandomSource {
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), Error>;
+}
@@ -6,8 +6,36 @@ use alloc::{
     vec::Vec,
 };
 
+use crate::{renderer::html::attribute::Attribute, url::Url};
+
 use super::node::{Element, ElementKind, Node, NodeKind};
 
+// Appends `child` as the last child of `parent`.
+pub fn append_child(parent: &Rc<RefCell<Node>>, child: Rc<RefCell<Node>>) {
+    child.borrow_mut().set_parent(Rc::downgrade(parent));
+    let last_child = parent.borrow().last_child().upgrade();
+    match last_child {
+        Some(last) => {
+            child
+                .borrow_mut()
+                .set_previous_sibling(Rc::downgrade(&last));
+            last.borrow_mut().set_next_sibling(Some(child.clone()));
+        }
+        None => parent.borrow_mut().set_first_child(Some(child.clone())),
+    }
+    parent.borrow_mut().set_last_child(Rc::downgrade(&child));
+}
+
+pub fn create_element(name: &str, attributes: Vec<Attribute>) -> Rc<RefCell<Node>> {
+    Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+        name, attributes,
+    )))))
+}
+
+pub fn create_text(text: &str) -> Rc<RefCell<Node>> {
+    Rc::new(RefCell::new(Node::new(NodeKind::Text(text.to_string()))))
+}
+
 pub fn get_target_element_node(
     node: Option<Rc<RefCell<Node>>>,
     element_kind: ElementKind,
@@ -34,6 +62,29 @@ pub fn get_target_element_node(
     }
 }
 
+// Collects the elements of the given kinds in document order.
+pub fn get_elements(root: Rc<RefCell<Node>>, kinds: &[ElementKind]) -> Vec<Rc<RefCell<Node>>> {
+    let mut elements = Vec::new();
+    let mut stack = Vec::from([root]);
+    while let Some(node) = stack.pop() {
+        if node
+            .borrow()
+            .element_kind()
+            .is_some_and(|k| kinds.contains(&k))
+        {
+            elements.push(node.clone());
+        }
+        let mut children = Vec::new();
+        let mut child = node.borrow().first_child();
+        while let Some(c) = child {
+            child = c.borrow().next_sibling();
+            children.push(c);
+        }
+        stack.extend(children.into_iter().rev());
+    }
+    elements
+}
+
 pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
     let style_node = match get_target_element_node(Some(root), ElementKind::Style) {
         Some(node) => node,
@@ -49,3 +100,21 @@ pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
     };
     content
 }
+
+// The first <base> element with an href sets the base URL; others are ignored.
+pub fn get_document_base_url(root: Rc<RefCell<Node>>, document_url: &Url) -> Url {
+    let href = get_elements(root, &[ElementKind::Base])
+        .iter()
+        .find_map(|node| {
+            node.borrow()
+                .get_element()
+                .and_then(|element| element.get_attribute("href"))
+        });
+
+    match href {
+        Some(href) => document_url
+            .resolve(&href)
+            .unwrap_or_else(|_| document_url.clone()),
+        None => document_url.clone(),
+    }
+}
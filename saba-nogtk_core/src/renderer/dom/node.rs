@@ -1,4 +1,5 @@
 use crate::renderer::html::attribute::Attribute;
+use crate::renderer::html::diagnostics::SourcePosition;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
@@ -14,6 +15,7 @@ pub enum NodeKind {
     Document,
     Element(Element),
     Text(String),
+    Comment(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,7 +39,13 @@ impl Element {
 
     pub fn is_block_element(&self) -> bool {
         match self.kind {
-            ElementKind::Body | ElementKind::P | ElementKind::H1 | ElementKind::H2 => true,
+            ElementKind::Body
+            | ElementKind::P
+            | ElementKind::H1
+            | ElementKind::H2
+            | ElementKind::Pre
+            | ElementKind::Xmp
+            | ElementKind::Plaintext => true,
             _ => false,
         }
     }
@@ -45,19 +53,38 @@ impl Element {
     pub fn attributes(&self) -> Vec<Attribute> {
         self.attributes.clone()
     }
+
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.name() == name)
+            .map(|attr| attr.value())
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ElementKind {
     Html,
     Head,
+    Base,
+    Link,
+    Title,
     Style,
     Script,
+    Noscript,
+    Noframes,
     Body,
     P,
     H1,
     H2,
+    Pre,
+    Xmp,
+    Plaintext,
+    Textarea,
     A,
+    Img,
+    Iframe,
+    Noembed,
 }
 
 impl FromStr for ElementKind {
@@ -67,13 +94,25 @@ impl FromStr for ElementKind {
         match s {
             "html" => Ok(Self::Html),
             "head" => Ok(Self::Head),
+            "base" => Ok(Self::Base),
+            "link" => Ok(Self::Link),
+            "title" => Ok(Self::Title),
             "style" => Ok(Self::Style),
             "script" => Ok(Self::Script),
+            "noscript" => Ok(Self::Noscript),
+            "noframes" => Ok(Self::Noframes),
             "body" => Ok(Self::Body),
             "p" => Ok(Self::P),
             "h1" => Ok(Self::H1),
             "h2" => Ok(Self::H2),
+            "pre" => Ok(Self::Pre),
+            "xmp" => Ok(Self::Xmp),
+            "plaintext" => Ok(Self::Plaintext),
+            "textarea" => Ok(Self::Textarea),
             "a" => Ok(Self::A),
+            "img" => Ok(Self::Img),
+            "iframe" => Ok(Self::Iframe),
+            "noembed" => Ok(Self::Noembed),
             _ => Err(format!("failed to convert string to ElementKind: {}", s)),
         }
     }
@@ -84,27 +123,49 @@ impl Display for ElementKind {
         let s = match self {
             ElementKind::Html => "html",
             ElementKind::Head => "head",
+            ElementKind::Base => "base",
+            ElementKind::Link => "link",
+            ElementKind::Title => "title",
             ElementKind::Style => "style",
             ElementKind::Script => "script",
+            ElementKind::Noscript => "noscript",
+            ElementKind::Noframes => "noframes",
             ElementKind::Body => "body",
             ElementKind::P => "p",
             ElementKind::H1 => "h1",
             ElementKind::H2 => "h2",
+            ElementKind::Pre => "pre",
+            ElementKind::Xmp => "xmp",
+            ElementKind::Plaintext => "plaintext",
+            ElementKind::Textarea => "textarea",
             ElementKind::A => "a",
+            ElementKind::Img => "img",
+            ElementKind::Iframe => "iframe",
+            ElementKind::Noembed => "noembed",
         };
         write!(f, "{}", s)
     }
 }
 
+// How closely the document follows the standards, as decided from its DOCTYPE.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuirksMode {
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
+}
+
 #[derive(Debug, Clone)]
 pub struct Window {
     document: Rc<RefCell<Node>>,
+    quirks_mode: QuirksMode,
 }
 
 impl Window {
     pub fn new() -> Self {
         let window = Self {
             document: Rc::new(RefCell::new(Node::new(NodeKind::Document))),
+            quirks_mode: QuirksMode::NoQuirks,
         };
         window
             .document
@@ -117,6 +178,14 @@ impl Window {
     pub fn document(&self) -> Rc<RefCell<Node>> {
         self.document.clone()
     }
+
+    pub fn set_quirks_mode(&mut self, quirks_mode: QuirksMode) {
+        self.quirks_mode = quirks_mode;
+    }
+
+    pub fn quirks_mode(&self) -> QuirksMode {
+        self.quirks_mode
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -128,6 +197,8 @@ pub struct Node {
     last_child: Weak<RefCell<Node>>,
     previous_sibling: Weak<RefCell<Node>>,
     next_sibling: Option<Rc<RefCell<Node>>>,
+    // Where the node starts in the HTML source. Nodes built by other means have none.
+    position: Option<SourcePosition>,
 }
 
 impl Node {
@@ -140,6 +211,7 @@ impl Node {
             last_child: Weak::new(),
             previous_sibling: Weak::new(),
             next_sibling: None,
+            position: None,
         }
     }
 
@@ -191,16 +263,24 @@ impl Node {
         self.kind.clone()
     }
 
+    pub fn set_position(&mut self, position: SourcePosition) {
+        self.position = Some(position);
+    }
+
+    pub fn position(&self) -> Option<SourcePosition> {
+        self.position
+    }
+
     pub fn get_element(&self) -> Option<Element> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
+            NodeKind::Document | NodeKind::Text(_) | NodeKind::Comment(_) => None,
             NodeKind::Element(ref e) => Some(e.clone()),
         }
     }
 
     pub fn element_kind(&self) -> Option<ElementKind> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
+            NodeKind::Document | NodeKind::Text(_) | NodeKind::Comment(_) => None,
             NodeKind::Element(ref e) => Some(e.kind()),
         }
     }
@@ -221,6 +301,7 @@ impl PartialEq for NodeKind {
                 _ => false,
             },
             NodeKind::Text(_) => matches!(other, NodeKind::Text(_)),
+            NodeKind::Comment(_) => matches!(other, NodeKind::Comment(_)),
         }
     }
 }
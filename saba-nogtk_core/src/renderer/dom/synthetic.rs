@@ -0,0 +1,159 @@
+use core::cell::RefCell;
+
+use alloc::{format, rc::Rc, string::ToString, vec, vec::Vec};
+
+use crate::renderer::html::attribute::Attribute;
+
+use super::{
+    api::{append_child, create_element, create_text},
+    node::{Node, Window},
+};
+
+// Builds an empty html/head/body document and returns it together with its body.
+fn skeleton() -> (Rc<RefCell<Window>>, Rc<RefCell<Node>>) {
+    let window = Rc::new(RefCell::new(Window::new()));
+    let document = window.borrow().document();
+    let html = create_element("html", Vec::new());
+    append_child(&document, html.clone());
+    append_child(&html, create_element("head", Vec::new()));
+    let body = create_element("body", Vec::new());
+    append_child(&html, body.clone());
+    (window, body)
+}
+
+// The document shown for a plain text resource: the whole text inside a single <pre>.
+pub fn text_document(text: &str) -> Rc<RefCell<Window>> {
+    let (window, body) = skeleton();
+    let pre = create_element("pre", Vec::new());
+    append_child(&body, pre.clone());
+    if !text.is_empty() {
+        append_child(&pre, create_text(text));
+    }
+    window
+}
+
+// The document shown for an image resource: a single <img> referring to the resource itself.
+pub fn image_document(src: &str, dimensions: Option<(u32, u32)>) -> Rc<RefCell<Window>> {
+    let (window, body) = skeleton();
+    let mut attributes = vec![Attribute::with_value("src", src)];
+    if let Some((width, height)) = dimensions {
+        attributes.push(Attribute::with_value("width", &width.to_string()));
+        attributes.push(Attribute::with_value("height", &height.to_string()));
+    }
+    append_child(&body, create_element("img", attributes));
+    window
+}
+
+// The document shown for a resource that cannot be displayed, offering it as a download.
+pub fn download_document(
+    href: &str,
+    filename: &str,
+    mime_type: &str,
+    size: usize,
+) -> Rc<RefCell<Window>> {
+    let (window, body) = skeleton();
+    let heading = create_element("h2", Vec::new());
+    append_child(&body, heading.clone());
+    append_child(&heading, create_text(filename));
+
+    let description = create_element("p", Vec::new());
+    append_child(&body, description.clone());
+    append_child(
+        &description,
+        create_text(&format!(
+            "This {} file ({} bytes) cannot be displayed.",
+            mime_type, size
+        )),
+    );
+
+    let paragraph = create_element("p", Vec::new());
+    append_child(&body, paragraph.clone());
+    let link = create_element(
+        "a",
+        vec![
+            Attribute::with_value("href", href),
+            Attribute::with_value("download", filename),
+        ],
+    );
+    append_child(&paragraph, link.clone());
+    append_child(&link, create_text("Download"));
+    window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::{
+        api::get_elements,
+        node::{ElementKind, NodeKind},
+    };
+    use alloc::string::String;
+
+    fn text_of(node: &Rc<RefCell<Node>>) -> Option<String> {
+        match node.borrow().first_child()?.borrow().kind() {
+            NodeKind::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_text_document() {
+        let window = text_document("a\n  b");
+        let document = window.borrow().document();
+        let kinds: Vec<ElementKind> = get_elements(
+            document.clone(),
+            &[
+                ElementKind::Html,
+                ElementKind::Head,
+                ElementKind::Body,
+                ElementKind::Pre,
+            ],
+        )
+        .iter()
+        .filter_map(|n| n.borrow().element_kind())
+        .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ElementKind::Html,
+                ElementKind::Head,
+                ElementKind::Body,
+                ElementKind::Pre
+            ]
+        );
+        let pre = get_elements(document, &[ElementKind::Pre]).remove(0);
+        assert_eq!(text_of(&pre), Some("a\n  b".to_string()));
+        let body = pre.borrow().parent().upgrade().unwrap();
+        assert_eq!(body.borrow().element_kind(), Some(ElementKind::Body));
+    }
+
+    #[test]
+    fn test_image_and_download_documents() {
+        let window = image_document("http://example.com/a.png", Some((3, 4)));
+        let img = get_elements(window.borrow().document(), &[ElementKind::Img]).remove(0);
+        let element = img.borrow().get_element().unwrap();
+        assert_eq!(
+            element.get_attribute("src"),
+            Some("http://example.com/a.png".to_string())
+        );
+        assert_eq!(element.get_attribute("width"), Some("3".to_string()));
+        assert_eq!(element.get_attribute("height"), Some("4".to_string()));
+
+        let window = download_document("http://example.com/a.zip", "a.zip", "application/zip", 10);
+        let document = window.borrow().document();
+        let link = get_elements(document.clone(), &[ElementKind::A]).remove(0);
+        assert_eq!(
+            link.borrow()
+                .get_element()
+                .unwrap()
+                .get_attribute("download"),
+            Some("a.zip".to_string())
+        );
+        assert_eq!(text_of(&link), Some("Download".to_string()));
+        let paragraphs = get_elements(document, &[ElementKind::P]);
+        assert_eq!(paragraphs.len(), 2);
+        // Siblings are linked in both directions.
+        let previous = paragraphs[1].borrow().previous_sibling().upgrade().unwrap();
+        assert!(Rc::ptr_eq(&previous, &paragraphs[0]));
+    }
+}
@@ -14,6 +14,13 @@ impl Attribute {
         }
     }
 
+    pub fn with_value(name: &str, value: &str) -> Self {
+        Self {
+            name: String::from(name),
+            value: String::from(value),
+        }
+    }
+
     pub fn add_char(&mut self, c: char, is_name: bool) {
         if is_name {
             self.name.push(c);
@@ -0,0 +1,275 @@
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::{Display, Formatter};
+
+// A place in the HTML source. Lines and columns count from 1; columns count characters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourcePosition {
+    line: usize,
+    column: usize,
+}
+
+impl SourcePosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// Turns character offsets into the source into line/column positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(input: &[char]) -> Self {
+        let mut index = Self {
+            line_starts: vec![0],
+        };
+        index.extend(input, 0);
+        index
+    }
+
+    // Indexes the characters of `input` from `start` on, which have been appended since the
+    // index was last brought up to date.
+    pub fn extend(&mut self, input: &[char], start: usize) {
+        for (i, c) in input.iter().enumerate().skip(start) {
+            if *c == '\n' {
+                self.line_starts.push(i + 1);
+            }
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> SourcePosition {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        SourcePosition::new(line + 1, offset - self.line_starts[line] + 1)
+    }
+}
+
+// The tokenizer errors carry the names the HTML standard gives them. The standard leaves tree
+// construction errors unnamed; those are named after the token that could not be handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    AbruptClosingOfEmptyComment,
+    AbruptDoctypePublicIdentifier,
+    AbruptDoctypeSystemIdentifier,
+    AbsenceOfDigitsInNumericCharacterReference,
+    CdataInHtmlContent,
+    CharacterReferenceOutsideUnicodeRange,
+    ControlCharacterReference,
+    DuplicateAttribute,
+    EndTagWithAttributes,
+    EndTagWithTrailingSolidus,
+    EofBeforeTagName,
+    EofInCdata,
+    EofInComment,
+    EofInDoctype,
+    EofInTag,
+    IncorrectlyClosedComment,
+    IncorrectlyOpenedComment,
+    InvalidCharacterSequenceAfterDoctypeName,
+    InvalidFirstCharacterOfTagName,
+    MissingAttributeValue,
+    MissingDoctypeName,
+    MissingDoctypePublicIdentifier,
+    MissingDoctypeSystemIdentifier,
+    MissingEndTagName,
+    MissingQuoteBeforeDoctypePublicIdentifier,
+    MissingQuoteBeforeDoctypeSystemIdentifier,
+    MissingSemicolonAfterCharacterReference,
+    MissingWhitespaceAfterDoctypePublicKeyword,
+    MissingWhitespaceAfterDoctypeSystemKeyword,
+    MissingWhitespaceBeforeDoctypeName,
+    MissingWhitespaceBetweenAttributes,
+    MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+    NestedComment,
+    NoncharacterCharacterReference,
+    NullCharacterReference,
+    SurrogateCharacterReference,
+    UnexpectedCharacterAfterDoctypeSystemIdentifier,
+    UnexpectedCharacterInAttributeName,
+    UnexpectedCharacterInUnquotedAttributeValue,
+    UnexpectedEqualsSignBeforeAttributeName,
+    UnexpectedNullCharacter,
+    UnexpectedQuestionMarkInsteadOfTagName,
+    UnexpectedSolidusInTag,
+    UnknownNamedCharacterReference,
+    // Tree construction.
+    MissingDoctype,
+    NonConformingDoctype,
+    UnexpectedDoctype,
+    UnexpectedStartTag(String),
+    UnexpectedEndTag(String),
+    UnexpectedContentAfterBody,
+    EofInElement(String),
+}
+
+impl ParseErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AbruptClosingOfEmptyComment => "abrupt-closing-of-empty-comment",
+            Self::AbruptDoctypePublicIdentifier => "abrupt-doctype-public-identifier",
+            Self::AbruptDoctypeSystemIdentifier => "abrupt-doctype-system-identifier",
+            Self::AbsenceOfDigitsInNumericCharacterReference => {
+                "absence-of-digits-in-numeric-character-reference"
+            }
+            Self::CdataInHtmlContent => "cdata-in-html-content",
+            Self::CharacterReferenceOutsideUnicodeRange => {
+                "character-reference-outside-unicode-range"
+            }
+            Self::ControlCharacterReference => "control-character-reference",
+            Self::DuplicateAttribute => "duplicate-attribute",
+            Self::EndTagWithAttributes => "end-tag-with-attributes",
+            Self::EndTagWithTrailingSolidus => "end-tag-with-trailing-solidus",
+            Self::EofBeforeTagName => "eof-before-tag-name",
+            Self::EofInCdata => "eof-in-cdata",
+            Self::EofInComment => "eof-in-comment",
+            Self::EofInDoctype => "eof-in-doctype",
+            Self::EofInTag => "eof-in-tag",
+            Self::IncorrectlyClosedComment => "incorrectly-closed-comment",
+            Self::IncorrectlyOpenedComment => "incorrectly-opened-comment",
+            Self::InvalidCharacterSequenceAfterDoctypeName => {
+                "invalid-character-sequence-after-doctype-name"
+            }
+            Self::InvalidFirstCharacterOfTagName => "invalid-first-character-of-tag-name",
+            Self::MissingAttributeValue => "missing-attribute-value",
+            Self::MissingDoctypeName => "missing-doctype-name",
+            Self::MissingDoctypePublicIdentifier => "missing-doctype-public-identifier",
+            Self::MissingDoctypeSystemIdentifier => "missing-doctype-system-identifier",
+            Self::MissingEndTagName => "missing-end-tag-name",
+            Self::MissingQuoteBeforeDoctypePublicIdentifier => {
+                "missing-quote-before-doctype-public-identifier"
+            }
+            Self::MissingQuoteBeforeDoctypeSystemIdentifier => {
+                "missing-quote-before-doctype-system-identifier"
+            }
+            Self::MissingSemicolonAfterCharacterReference => {
+                "missing-semicolon-after-character-reference"
+            }
+            Self::MissingWhitespaceAfterDoctypePublicKeyword => {
+                "missing-whitespace-after-doctype-public-keyword"
+            }
+            Self::MissingWhitespaceAfterDoctypeSystemKeyword => {
+                "missing-whitespace-after-doctype-system-keyword"
+            }
+            Self::MissingWhitespaceBeforeDoctypeName => "missing-whitespace-before-doctype-name",
+            Self::MissingWhitespaceBetweenAttributes => "missing-whitespace-between-attributes",
+            Self::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers => {
+                "missing-whitespace-between-doctype-public-and-system-identifiers"
+            }
+            Self::NestedComment => "nested-comment",
+            Self::NoncharacterCharacterReference => "noncharacter-character-reference",
+            Self::NullCharacterReference => "null-character-reference",
+            Self::SurrogateCharacterReference => "surrogate-character-reference",
+            Self::UnexpectedCharacterAfterDoctypeSystemIdentifier => {
+                "unexpected-character-after-doctype-system-identifier"
+            }
+            Self::UnexpectedCharacterInAttributeName => "unexpected-character-in-attribute-name",
+            Self::UnexpectedCharacterInUnquotedAttributeValue => {
+                "unexpected-character-in-unquoted-attribute-value"
+            }
+            Self::UnexpectedEqualsSignBeforeAttributeName => {
+                "unexpected-equals-sign-before-attribute-name"
+            }
+            Self::UnexpectedNullCharacter => "unexpected-null-character",
+            Self::UnexpectedQuestionMarkInsteadOfTagName => {
+                "unexpected-question-mark-instead-of-tag-name"
+            }
+            Self::UnexpectedSolidusInTag => "unexpected-solidus-in-tag",
+            Self::UnknownNamedCharacterReference => "unknown-named-character-reference",
+            Self::MissingDoctype => "missing-doctype",
+            Self::NonConformingDoctype => "non-conforming-doctype",
+            Self::UnexpectedDoctype => "unexpected-doctype",
+            Self::UnexpectedStartTag(_) => "unexpected-start-tag",
+            Self::UnexpectedEndTag(_) => "unexpected-end-tag",
+            Self::UnexpectedContentAfterBody => "unexpected-content-after-body",
+            Self::EofInElement(_) => "eof-in-element",
+        }
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::UnexpectedStartTag(tag) | Self::EofInElement(tag) => {
+                write!(f, "{} <{}>", self.name(), tag)
+            }
+            Self::UnexpectedEndTag(tag) => write!(f, "{} </{}>", self.name(), tag),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    position: SourcePosition,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, position: SourcePosition) -> Self {
+        Self { kind, position }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind.clone()
+    }
+
+    pub fn position(&self) -> SourcePosition {
+        self.position
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}: {}", self.position, self.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_line_index() {
+        let input: Vec<char> = "ab\ncd\n\nあ".chars().collect();
+        let index = LineIndex::new(&input);
+        assert_eq!(index.position(0), SourcePosition::new(1, 1));
+        assert_eq!(index.position(2), SourcePosition::new(1, 3));
+        assert_eq!(index.position(3), SourcePosition::new(2, 1));
+        assert_eq!(index.position(6), SourcePosition::new(3, 1));
+        assert_eq!(index.position(7), SourcePosition::new(4, 1));
+        // The end of the input is a position too.
+        assert_eq!(index.position(8), SourcePosition::new(4, 2));
+    }
+
+    #[test]
+    fn test_display() {
+        let error = ParseError::new(
+            ParseErrorKind::UnexpectedEndTag("p".to_string()),
+            SourcePosition::new(3, 14),
+        );
+        assert_eq!(error.to_string(), "3:14: unexpected-end-tag </p>");
+        assert_eq!(
+            ParseErrorKind::UnexpectedNullCharacter.to_string(),
+            "unexpected-null-character"
+        );
+    }
+}
@@ -1,3 +1,5 @@
 pub mod attribute;
+pub mod diagnostics;
+pub mod entities;
 pub mod parser;
 pub mod token;
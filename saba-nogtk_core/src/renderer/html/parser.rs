@@ -1,12 +1,20 @@
 use core::{cell::RefCell, str::FromStr};
 
-use alloc::{rc::Rc, string::String, vec::Vec};
+use alloc::{
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
 
-use crate::renderer::dom::node::{Element, ElementKind, Node, NodeKind, Window};
+use crate::renderer::dom::{
+    api::append_child,
+    node::{Element, ElementKind, Node, NodeKind, QuirksMode, Window},
+};
 
 use super::{
     attribute::Attribute,
-    token::{HtmlToken, HtmlTokenizer},
+    diagnostics::{ParseError, ParseErrorKind, SourcePosition},
+    token::{is_whitespace, HtmlToken, HtmlTokenizer, State},
 };
 
 #[derive(Debug, Clone)]
@@ -16,6 +24,12 @@ pub struct HtmlParser {
     original_insertion_mode: InsertionMode,
     stack_of_open_elements: Vec<Rc<RefCell<Node>>>,
     t: HtmlTokenizer,
+    errors: Vec<ParseError>,
+    // Set after a <pre> or <textarea> start tag, whose first newline is dropped. Kept here since
+    // the newline may arrive with a later piece of the input.
+    skip_newline: bool,
+    // Where the token being processed starts in the source.
+    position: SourcePosition,
 }
 
 impl HtmlParser {
@@ -26,20 +40,102 @@ impl HtmlParser {
             original_insertion_mode: InsertionMode::Initial,
             stack_of_open_elements: Vec::new(),
             t,
+            errors: Vec::new(),
+            skip_newline: false,
+            position: SourcePosition::new(1, 1),
         }
     }
 
+    // The document as far as it has been parsed.
+    pub fn window(&self) -> Rc<RefCell<Window>> {
+        self.window.clone()
+    }
+
+    // Hands the next piece of a streamed document to the tokenizer and builds as much of the
+    // tree as it allows.
+    pub fn feed(&mut self, chunk: &str) {
+        self.t.feed(chunk);
+        self.run();
+    }
+
+    // The errors found by the tokenizer and the tree builder, in source order.
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors = self.t.errors();
+        errors.extend(self.errors.iter().cloned());
+        errors.sort_by_key(|e| e.position());
+        errors
+    }
+
+    fn error(&mut self, kind: ParseErrorKind) {
+        self.errors.push(ParseError::new(kind, self.position));
+    }
+
+    // Parses the rest of the input and ends the document.
     pub fn construct_tree(&mut self) -> Rc<RefCell<Window>> {
-        let mut token = self.t.next();
+        self.t.finish();
+        self.run();
+        self.stop_parsing()
+    }
+
+    fn next_token(&mut self) -> Option<HtmlToken> {
+        let mut token = self.t.next_positioned()?;
+        if core::mem::take(&mut self.skip_newline) && token.token() == HtmlToken::Char('\n') {
+            token = self.t.next_positioned()?;
+        }
+        self.position = token.position();
+        Some(token.token())
+    }
+
+    // Builds the tree from the tokens available, until the end of the document or until the
+    // tokenizer needs more input.
+    fn run(&mut self) {
+        let mut token = self.next_token();
 
         while token.is_some() {
             match self.mode {
                 InsertionMode::Initial => {
-                    if let Some(HtmlToken::Char(_)) = token {
-                        token = self.t.next();
-                        continue;
+                    match token {
+                        Some(HtmlToken::Char(c)) if is_whitespace(c) => {
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            append_child(&document, self.create_comment(data));
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype {
+                            ref name,
+                            ref public_id,
+                            ref system_id,
+                            force_quirks,
+                        }) => {
+                            let conforming = name.as_deref() == Some("html")
+                                && public_id.is_none()
+                                && system_id
+                                    .as_deref()
+                                    .map_or(true, |id| id == "about:legacy-compat");
+                            if !conforming {
+                                self.error(ParseErrorKind::NonConformingDoctype);
+                            }
+                            let quirks_mode = quirks_mode_for_doctype(
+                                name.as_deref(),
+                                public_id.as_deref(),
+                                system_id.as_deref(),
+                                force_quirks,
+                            );
+                            self.window.borrow_mut().set_quirks_mode(quirks_mode);
+                            self.mode = InsertionMode::BeforeHtml;
+                            token = self.next_token();
+                            continue;
+                        }
+                        _ => {}
                     }
 
+                    // A document without a DOCTYPE is rendered in quirks mode.
+                    self.error(ParseErrorKind::MissingDoctype);
+                    self.window.borrow_mut().set_quirks_mode(QuirksMode::Quirks);
                     self.mode = InsertionMode::BeforeHtml;
                     continue;
                 }
@@ -47,7 +143,7 @@ impl HtmlParser {
                     match token {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
@@ -59,13 +155,22 @@ impl HtmlParser {
                             if tag == "html" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::BeforeHead;
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            append_child(&document, self.create_comment(data));
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
                         }
+                        Some(HtmlToken::Eof) | None => return,
                         _ => {}
                     }
                     self.insert_element("html", Vec::new());
@@ -76,7 +181,7 @@ impl HtmlParser {
                     match token {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
@@ -88,11 +193,21 @@ impl HtmlParser {
                             if tag == "head" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::InHead;
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => return self.window.clone(),
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Eof) | None => return,
                         _ => {}
                     }
                     self.insert_element("head", Vec::new());
@@ -104,20 +219,49 @@ impl HtmlParser {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
                                 self.insert_char(c);
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
+                            // Text ends the head and goes into the body.
+                            self.pop_until(ElementKind::Head);
+                            self.mode = InsertionMode::AfterHead;
+                            continue;
                         }
                         Some(HtmlToken::StartTag {
                             ref tag,
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "style" || tag == "script" {
+                            if tag == "title" {
+                                self.parse_text_element(tag, attributes.to_vec(), State::Rcdata);
+                                token = self.next_token();
+                                continue;
+                            }
+                            // Scripting is disabled, so <noscript> contents are parsed as markup.
+                            if tag == "style" || tag == "noframes" {
+                                self.parse_text_element(tag, attributes.to_vec(), State::Rawtext);
+                                token = self.next_token();
+                                continue;
+                            }
+                            if tag == "script" {
+                                self.parse_text_element(
+                                    tag,
+                                    attributes.to_vec(),
+                                    State::ScriptData,
+                                );
+                                token = self.next_token();
+                                continue;
+                            }
+                            if tag == "base" {
                                 self.insert_element(tag, attributes.to_vec());
-                                self.original_insertion_mode = self.mode;
-                                self.mode = InsertionMode::Text;
-                                token = self.t.next();
+                                self.pop_current_node(ElementKind::Base);
+                                token = self.next_token();
+                                continue;
+                            }
+                            if tag == "link" {
+                                self.insert_element(tag, attributes.to_vec());
+                                self.pop_current_node(ElementKind::Link);
+                                token = self.next_token();
                                 continue;
                             }
                             if tag == "body" {
@@ -134,14 +278,30 @@ impl HtmlParser {
                         Some(HtmlToken::EndTag { ref tag }) => {
                             if tag == "head" {
                                 self.mode = InsertionMode::AfterHead;
-                                token = self.t.next();
+                                token = self.next_token();
+                                self.pop_until(ElementKind::Head);
+                                continue;
+                            }
+                            if tag == "body" || tag == "html" || tag == "br" {
                                 self.pop_until(ElementKind::Head);
+                                self.mode = InsertionMode::AfterHead;
                                 continue;
                             }
+                            self.error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
                         }
-                        Some(HtmlToken::Eof) | None => return self.window.clone(),
+                        Some(HtmlToken::Eof) | None => return,
                     }
-                    token = self.t.next();
+                    token = self.next_token();
                     continue;
                 }
                 InsertionMode::AfterHead => {
@@ -149,7 +309,7 @@ impl HtmlParser {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
                                 self.insert_char(c);
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
@@ -161,11 +321,21 @@ impl HtmlParser {
                             if tag == "body" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::InBody;
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => return self.window.clone(),
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Eof) | None => return,
                         _ => {}
                     }
                     self.insert_element("body", Vec::new());
@@ -180,28 +350,79 @@ impl HtmlParser {
                     }) => match tag.as_str() {
                         "p" => {
                             self.insert_element(tag, attributes.to_vec());
-                            token = self.t.next();
+                            token = self.next_token();
                             continue;
                         }
                         "h1" | "h2" => {
                             self.insert_element(tag, attributes.to_vec());
-                            token = self.t.next();
+                            token = self.next_token();
+                            continue;
+                        }
+                        "pre" => {
+                            self.insert_element(tag, attributes.to_vec());
+                            // A newline right after the start tag is not part of the content.
+                            self.skip_newline = true;
+                            token = self.next_token();
+                            continue;
+                        }
+                        "textarea" => {
+                            self.parse_text_element(tag, attributes.to_vec(), State::Rcdata);
+                            self.skip_newline = true;
+                            token = self.next_token();
+                            continue;
+                        }
+                        "title" => {
+                            self.parse_text_element(tag, attributes.to_vec(), State::Rcdata);
+                            token = self.next_token();
+                            continue;
+                        }
+                        "style" | "xmp" | "iframe" | "noembed" | "noframes" => {
+                            self.parse_text_element(tag, attributes.to_vec(), State::Rawtext);
+                            token = self.next_token();
+                            continue;
+                        }
+                        "plaintext" => {
+                            // There is no way out of the PLAINTEXT state: the rest of the
+                            // document is the element's text.
+                            self.insert_element(tag, attributes.to_vec());
+                            self.t.switch_to(State::Plaintext);
+                            token = self.next_token();
                             continue;
                         }
                         "a" => {
                             self.insert_element(tag, attributes.to_vec());
-                            token = self.t.next();
+                            token = self.next_token();
                             continue;
                         }
-                        _ => token = self.t.next(),
+                        "img" | "link" => {
+                            // Void elements have no children and no end tag.
+                            let element_kind = ElementKind::from_str(tag)
+                                .expect("failed to convert string to ElementKind");
+                            self.insert_element(tag, attributes.to_vec());
+                            self.pop_current_node(element_kind);
+                            token = self.next_token();
+                            continue;
+                        }
+                        "script" => {
+                            self.parse_text_element(tag, attributes.to_vec(), State::ScriptData);
+                            token = self.next_token();
+                            continue;
+                        }
+                        "html" | "head" | "body" => {
+                            self.error(ParseErrorKind::UnexpectedStartTag(tag.clone()));
+                            token = self.next_token();
+                        }
+                        _ => token = self.next_token(),
                     },
                     Some(HtmlToken::EndTag { ref tag }) => match tag.as_str() {
                         "body" => {
                             self.mode = InsertionMode::AfterBody;
-                            token = self.t.next();
                             if !self.contain_in_stack(ElementKind::Body) {
+                                self.error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
                                 continue;
                             }
+                            token = self.next_token();
                             self.pop_until(ElementKind::Body);
                             continue;
                         }
@@ -210,62 +431,62 @@ impl HtmlParser {
                                 self.mode = InsertionMode::AfterBody;
                                 assert!(self.pop_current_node(ElementKind::Html));
                             } else {
-                                token = self.t.next();
+                                self.error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
                             }
                             continue;
                         }
-                        "p" => {
-                            let element_kind = ElementKind::from_str(tag)
-                                .expect("failed to convert string to ElementKind");
-                            token = self.t.next();
-                            self.pop_until(element_kind);
-                            continue;
-                        }
-                        "h1" | "h2" => {
-                            let element_kind = ElementKind::from_str(tag)
-                                .expect("failed to convert string to ElementKind");
-                            token = self.t.next();
-                            self.pop_until(element_kind);
-                            continue;
-                        }
-                        "a" => {
+                        "p" | "h1" | "h2" | "pre" | "a" => {
                             let element_kind = ElementKind::from_str(tag)
                                 .expect("failed to convert string to ElementKind");
-                            token = self.t.next();
-                            self.pop_until(element_kind);
+                            self.close_element(tag, element_kind);
+                            token = self.next_token();
                             continue;
                         }
                         _ => {
-                            token = self.t.next();
+                            // Elements this parser does not build are dropped along with their
+                            // end tags; an end tag for one it does build is out of place here.
+                            if ElementKind::from_str(tag).is_ok() {
+                                self.error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                            }
+                            token = self.next_token();
                         }
                     },
-                    Some(HtmlToken::Eof) | None => return self.window.clone(),
+                    Some(HtmlToken::Comment(ref data)) => {
+                        self.insert_comment(data);
+                        token = self.next_token();
+                        continue;
+                    }
+                    Some(HtmlToken::Doctype { .. }) => {
+                        self.error(ParseErrorKind::UnexpectedDoctype);
+                        token = self.next_token();
+                        continue;
+                    }
+                    Some(HtmlToken::Eof) | None => return,
+                    // The tokenizer has already reported it.
+                    Some(HtmlToken::Char('\0')) => {
+                        token = self.next_token();
+                        continue;
+                    }
                     Some(HtmlToken::Char(c)) => {
                         self.insert_char(c);
-                        token = self.t.next();
+                        token = self.next_token();
                         continue;
                     }
                 },
                 InsertionMode::Text => {
                     match token {
-                        Some(HtmlToken::Eof) | None => return self.window.clone(),
-                        Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "style" {
-                                self.pop_until(ElementKind::Style);
-                                self.mode = self.original_insertion_mode;
-                                token = self.t.next();
-                                continue;
-                            }
-                            if tag == "script" {
-                                self.pop_until(ElementKind::Script);
-                                self.mode = self.original_insertion_mode;
-                                token = self.t.next();
-                                continue;
-                            }
+                        Some(HtmlToken::Eof) | None => return,
+                        // The tokenizer only leaves the text states at the element's own end tag.
+                        Some(HtmlToken::EndTag { .. }) => {
+                            self.stack_of_open_elements.pop();
+                            self.mode = self.original_insertion_mode;
+                            token = self.next_token();
+                            continue;
                         }
                         Some(HtmlToken::Char(c)) => {
                             self.insert_char(c);
-                            token = self.t.next();
+                            token = self.next_token();
                             continue;
                         }
                         _ => {}
@@ -274,44 +495,91 @@ impl HtmlParser {
                 }
                 InsertionMode::AfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
-                            token = self.t.next();
+                        Some(HtmlToken::Char(c)) if is_whitespace(c) => {
+                            token = self.next_token();
                             continue;
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
                             if tag == "html" {
                                 self.mode = InsertionMode::AfterAfterBody;
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => return self.window.clone(),
+                        Some(HtmlToken::Comment(ref data)) => {
+                            // Comments after </body> go to the html element.
+                            let parent = match self.stack_of_open_elements.first() {
+                                Some(html) => html.clone(),
+                                None => self.window.borrow().document(),
+                            };
+                            append_child(&parent, self.create_comment(data));
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Eof) | None => return,
                         _ => {}
                     }
 
+                    self.error(ParseErrorKind::UnexpectedContentAfterBody);
                     self.mode = InsertionMode::InBody
                 }
                 InsertionMode::AfterAfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
-                            token = self.t.next();
+                        Some(HtmlToken::Char(c)) if is_whitespace(c) => {
+                            token = self.next_token();
                             continue;
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            append_child(&document, self.create_comment(data));
+                            token = self.next_token();
+                            continue;
                         }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            self.error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Eof) | None => return,
                         _ => {}
                     }
+                    self.error(ParseErrorKind::UnexpectedContentAfterBody);
                     self.mode = InsertionMode::InBody;
                 }
             }
         }
+    }
 
+    // Reports the elements the end of the input cuts off. Paragraphs may be left open.
+    fn stop_parsing(&mut self) -> Rc<RefCell<Window>> {
+        let unclosed = match self.mode {
+            InsertionMode::Text => self.stack_of_open_elements.last(),
+            InsertionMode::InBody => self.stack_of_open_elements.iter().rev().find(|n| {
+                !matches!(
+                    n.borrow().element_kind(),
+                    Some(ElementKind::P) | Some(ElementKind::Body) | Some(ElementKind::Html)
+                )
+            }),
+            _ => None,
+        };
+        if let Some(kind) = unclosed.and_then(|n| n.borrow().element_kind()) {
+            self.position = self.t.end_position();
+            self.error(ParseErrorKind::EofInElement(kind.to_string()));
+        }
         self.window.clone()
     }
 
+    // Nodes are stamped with the position of the token they are built from. Elements the parser
+    // implies take the position of the token that implied them.
     fn create_element(&self, tag: &str, attributes: Vec<Attribute>) -> Node {
-        Node::new(NodeKind::Element(Element::new(tag, attributes)))
+        let mut node = Node::new(NodeKind::Element(Element::new(tag, attributes)));
+        node.set_position(self.position);
+        node
     }
 
     fn insert_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
@@ -322,42 +590,20 @@ impl HtmlParser {
         };
 
         let node = Rc::new(RefCell::new(self.create_element(tag, attributes)));
-
-        if current.borrow().first_child().is_some() {
-            let mut last_sibiling = current.borrow().first_child();
-            loop {
-                last_sibiling = match last_sibiling {
-                    Some(ref node) => {
-                        if node.borrow().next_sibling().is_some() {
-                            node.borrow().next_sibling()
-                        } else {
-                            break;
-                        }
-                    }
-                    None => unimplemented!("last_sibiling should be Some"),
-                };
-            }
-
-            last_sibiling
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-            node.borrow_mut().set_previous_sibling(Rc::downgrade(
-                &current
-                    .borrow()
-                    .first_child()
-                    .expect("failed to get a first child"),
-            ))
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
-        }
-
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
+        append_child(&current, node.clone());
 
         self.stack_of_open_elements.push(node);
     }
 
+    // Inserts an element whose contents are text and tokenizes everything up to its end tag in
+    // `state` (the generic RCDATA and raw text element parsing algorithms).
+    fn parse_text_element(&mut self, tag: &str, attributes: Vec<Attribute>, state: State) {
+        self.insert_element(tag, attributes);
+        self.t.switch_to(state);
+        self.original_insertion_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+
     fn pop_current_node(&mut self, element_kind: ElementKind) -> bool {
         let current = match self.stack_of_open_elements.last() {
             Some(n) => n,
@@ -391,6 +637,27 @@ impl HtmlParser {
         }
     }
 
+    // Pops elements up to and including the one an end tag closes. An end tag without an open
+    // element to close is ignored, as is the mis-nesting when it closes more than paragraphs
+    // along with its own element.
+    fn close_element(&mut self, tag: &str, element_kind: ElementKind) {
+        if !self.contain_in_stack(element_kind) {
+            self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+            return;
+        }
+
+        let misnested = self
+            .stack_of_open_elements
+            .iter()
+            .rev()
+            .take_while(|n| n.borrow().element_kind() != Some(element_kind))
+            .any(|n| n.borrow().element_kind() != Some(ElementKind::P));
+        if misnested {
+            self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+        }
+        self.pop_until(element_kind);
+    }
+
     fn contain_in_stack(&mut self, element_kind: ElementKind) -> bool {
         for i in 0..self.stack_of_open_elements.len() {
             if self.stack_of_open_elements[i].borrow().element_kind() == Some(element_kind) {
@@ -404,7 +671,9 @@ impl HtmlParser {
     fn create_char(&self, c: char) -> Node {
         let mut s = String::new();
         s.push(c);
-        Node::new(NodeKind::Text(s))
+        let mut node = Node::new(NodeKind::Text(s));
+        node.set_position(self.position);
+        node
     }
 
     fn insert_char(&mut self, c: char) {
@@ -413,39 +682,140 @@ impl HtmlParser {
             None => return,
         };
 
-        if let NodeKind::Text(ref mut s) = current.borrow_mut().kind {
-            s.push(c);
-            return;
+        // Characters extend the text node the current node ends with, if there is one.
+        let last_child = current.borrow().last_child().upgrade();
+        if let Some(last_child) = last_child {
+            if let NodeKind::Text(ref mut s) = last_child.borrow_mut().kind {
+                s.push(c);
+                return;
+            }
         }
 
         if c == '\n' || c == ' ' {
             return;
         }
 
-        let node = Rc::new(RefCell::new(self.create_char(c)));
+        append_child(&current, Rc::new(RefCell::new(self.create_char(c))));
+    }
 
-        if current.borrow().first_child().is_some() {
-            current
-                .borrow()
-                .first_child()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-            node.borrow_mut().set_previous_sibling(Rc::downgrade(
-                &current
-                    .borrow()
-                    .first_child()
-                    .expect("failed to get a first child"),
-            ));
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
-        }
+    fn create_comment(&self, data: &str) -> Rc<RefCell<Node>> {
+        let mut node = Node::new(NodeKind::Comment(data.to_string()));
+        node.set_position(self.position);
+        Rc::new(RefCell::new(node))
+    }
 
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
+    fn insert_comment(&mut self, data: &str) {
+        let current = match self.stack_of_open_elements.last() {
+            Some(n) => n.clone(),
+            None => self.window.borrow().document(),
+        };
+        append_child(&current, self.create_comment(data));
+    }
+}
 
-        self.stack_of_open_elements.push(node);
+// Public identifiers starting with any of these put the document in quirks mode.
+const QUIRKS_PUBLIC_ID_PREFIXES: [&str; 55] = [
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html 2.0 level 1//",
+    "-//ietf//dtd html 2.0 level 2//",
+    "-//ietf//dtd html 2.0 strict level 1//",
+    "-//ietf//dtd html 2.0 strict level 2//",
+    "-//ietf//dtd html 2.0 strict//",
+    "-//ietf//dtd html 2.0//",
+    "-//ietf//dtd html 2.1e//",
+    "-//ietf//dtd html 3.0//",
+    "-//ietf//dtd html 3.2 final//",
+    "-//ietf//dtd html 3.2//",
+    "-//ietf//dtd html 3//",
+    "-//ietf//dtd html level 0//",
+    "-//ietf//dtd html level 1//",
+    "-//ietf//dtd html level 2//",
+    "-//ietf//dtd html level 3//",
+    "-//ietf//dtd html strict level 0//",
+    "-//ietf//dtd html strict level 1//",
+    "-//ietf//dtd html strict level 2//",
+    "-//ietf//dtd html strict level 3//",
+    "-//ietf//dtd html strict//",
+    "-//ietf//dtd html//",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer 2.0 html strict//",
+    "-//microsoft//dtd internet explorer 2.0 html//",
+    "-//microsoft//dtd internet explorer 2.0 tables//",
+    "-//microsoft//dtd internet explorer 3.0 html strict//",
+    "-//microsoft//dtd internet explorer 3.0 html//",
+    "-//microsoft//dtd internet explorer 3.0 tables//",
+    "-//netscape comm. corp.//dtd html//",
+    "-//netscape comm. corp.//dtd strict html//",
+    "-//o'reilly and associates//dtd html 2.0//",
+    "-//o'reilly and associates//dtd html extended 1.0//",
+    "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+    "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sun microsystems corp.//dtd hotjava html//",
+    "-//sun microsystems corp.//dtd hotjava strict html//",
+    "-//w3c//dtd html 3 1995-03-24//",
+    "-//w3c//dtd html 3.2 draft//",
+    "-//w3c//dtd html 3.2 final//",
+    "-//w3c//dtd html 3.2//",
+    "-//w3c//dtd html 3.2s draft//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental 19960712//",
+    "-//w3c//dtd html experimental 970421//",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html 2.0//",
+    "-//webtechs//dtd mozilla html//",
+];
+
+// Picks the document mode from a DOCTYPE token as the "initial" insertion mode does. All
+// identifiers are compared case-insensitively.
+fn quirks_mode_for_doctype(
+    name: Option<&str>,
+    public_id: Option<&str>,
+    system_id: Option<&str>,
+    force_quirks: bool,
+) -> QuirksMode {
+    let public_id = public_id.map(|id| id.to_ascii_lowercase());
+    let system_id = system_id.map(|id| id.to_ascii_lowercase());
+    let public_starts_with = |prefix: &str| {
+        public_id
+            .as_deref()
+            .is_some_and(|id| id.starts_with(prefix))
+    };
+    let html4_frameset_or_transitional = public_starts_with("-//w3c//dtd html 4.01 frameset//")
+        || public_starts_with("-//w3c//dtd html 4.01 transitional//");
+
+    if force_quirks
+        || name != Some("html")
+        || matches!(
+            public_id.as_deref(),
+            Some("-//w3o//dtd w3 html strict 3.0//en//")
+                | Some("-/w3c/dtd html 4.0 transitional/en")
+                | Some("html")
+        )
+        || system_id.as_deref()
+            == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || QUIRKS_PUBLIC_ID_PREFIXES
+            .iter()
+            .any(|prefix| public_starts_with(prefix))
+        || (system_id.is_none() && html4_frameset_or_transitional)
+    {
+        return QuirksMode::Quirks;
     }
+
+    if public_starts_with("-//w3c//dtd xhtml 1.0 frameset//")
+        || public_starts_with("-//w3c//dtd xhtml 1.0 transitional//")
+        || (system_id.is_some() && html4_frameset_or_transitional)
+    {
+        return QuirksMode::LimitedQuirks;
+    }
+
+    QuirksMode::NoQuirks
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -463,10 +833,12 @@ pub enum InsertionMode {
 
 #[cfg(test)]
 mod tests {
-    use alloc::vec;
+    use alloc::{format, vec};
 
     use super::*;
     use crate::alloc::string::ToString;
+    use crate::renderer::dom::api::{get_document_base_url, get_elements, get_style_content};
+    use crate::url::Url;
 
     #[test]
     fn test_empty() {
@@ -631,4 +1003,348 @@ mod tests {
             text
         );
     }
+
+    #[test]
+    fn test_base_element() {
+        let html = "<html><head><base href=\"/docs/\"></head><body>text</body></html>".to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let document = window.borrow().document();
+
+        let head = document
+            .borrow()
+            .first_child()
+            .unwrap()
+            .borrow()
+            .first_child()
+            .unwrap();
+        let base = head.borrow().first_child().unwrap();
+        assert_eq!(
+            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+                "base",
+                Vec::new()
+            ))))),
+            base
+        );
+        assert!(base.borrow().first_child().is_none());
+
+        let document_url = Url::new("http://example.com/index.html".to_string())
+            .parse()
+            .expect("failed to parse url");
+        let base_url = get_document_base_url(document, &document_url);
+        assert_eq!(base_url.href(), "http://example.com/docs/");
+        assert_eq!(
+            base_url
+                .resolve("example.com")
+                .expect("failed to resolve url")
+                .href(),
+            "http://example.com/docs/example.com"
+        );
+    }
+
+    #[test]
+    fn test_base_element_without_href() {
+        let html = "<html><head><base target=\"_top\"><base href=\"/docs/\"><base href=\"/other/\"></head></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+
+        let document_url = Url::new("http://example.com/index.html".to_string())
+            .parse()
+            .expect("failed to parse url");
+        let base_url = get_document_base_url(document, &document_url);
+        assert_eq!(base_url.href(), "http://example.com/docs/");
+
+        let html = "<html><head><base target=\"_top\"></head></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+        let base_url = get_document_base_url(document, &document_url);
+        assert_eq!(base_url.href(), "http://example.com/index.html");
+    }
+
+    #[test]
+    fn test_subresource_elements() {
+        let html = "<html><head><link rel=\"stylesheet\" href=\"a.css\"></head><body><img src=\"b.png\"><script src=\"c.js\"></script></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+
+        let html = document.borrow().first_child().unwrap();
+        let head = html.borrow().first_child().unwrap();
+        let link = head.borrow().first_child().unwrap();
+        assert_eq!(link.borrow().element_kind(), Some(ElementKind::Link));
+        assert!(link.borrow().first_child().is_none());
+
+        let body = head.borrow().next_sibling().unwrap();
+        let img = body.borrow().first_child().unwrap();
+        assert_eq!(img.borrow().element_kind(), Some(ElementKind::Img));
+        assert!(img.borrow().first_child().is_none());
+        let script = img.borrow().next_sibling().unwrap();
+        assert_eq!(script.borrow().element_kind(), Some(ElementKind::Script));
+        assert_eq!(
+            script
+                .borrow()
+                .get_element()
+                .and_then(|e| e.get_attribute("src")),
+            Some("c.js".to_string())
+        );
+    }
+
+    fn children(node: &Rc<RefCell<Node>>) -> Vec<NodeKind> {
+        let mut kinds = Vec::new();
+        let mut child = node.borrow().first_child();
+        while let Some(c) = child {
+            kinds.push(c.borrow().kind());
+            child = c.borrow().next_sibling();
+        }
+        kinds
+    }
+
+    #[test]
+    fn test_comments() {
+        let html = "<!--before--><!DOCTYPE html><html><!--in html--><head></head><body><p>a<!--x-->b</p></body></html><!--after-->".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+        assert_eq!(window.borrow().quirks_mode(), QuirksMode::NoQuirks);
+
+        let top = children(&document);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0], NodeKind::Comment("before".to_string()));
+        assert!(matches!(&top[2], NodeKind::Comment(c) if c == "after"));
+
+        let html = document
+            .borrow()
+            .first_child()
+            .unwrap()
+            .borrow()
+            .next_sibling()
+            .unwrap();
+        assert!(matches!(&children(&html)[0], NodeKind::Comment(c) if c == "in html"));
+
+        let p = get_elements(document, &[ElementKind::P]).remove(0);
+        let kinds = children(&p);
+        assert!(matches!(&kinds[0], NodeKind::Text(t) if t == "a"));
+        assert!(matches!(&kinds[1], NodeKind::Comment(c) if c == "x"));
+        assert!(matches!(&kinds[2], NodeKind::Text(t) if t == "b"));
+    }
+
+    #[test]
+    fn test_quirks_mode() {
+        let mode = |html: &str| {
+            let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+            let mode = window.borrow().quirks_mode();
+            mode
+        };
+        assert_eq!(mode("<!DOCTYPE html><p>x"), QuirksMode::NoQuirks);
+        assert_eq!(mode("<p>x"), QuirksMode::Quirks);
+        assert_eq!(mode("<!DOCTYPE html5><p>x"), QuirksMode::Quirks);
+        assert_eq!(
+            mode("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 3.2 Final//EN\">"),
+            QuirksMode::Quirks
+        );
+        assert_eq!(
+            mode("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\">"),
+            QuirksMode::Quirks
+        );
+        assert_eq!(
+            mode("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\">"),
+            QuirksMode::LimitedQuirks
+        );
+        assert_eq!(
+            mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">"),
+            QuirksMode::NoQuirks
+        );
+        // Only the first DOCTYPE counts.
+        assert_eq!(mode("<!DOCTYPE html><!DOCTYPE foo>"), QuirksMode::NoQuirks);
+    }
+
+    #[test]
+    fn test_character_references() {
+        let html = "<body><p title=\"a &amp; b\">x &lt; y&nbsp;&#x3042;</p></body>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let p = get_elements(window.borrow().document(), &[ElementKind::P]).remove(0);
+        assert_eq!(
+            p.borrow().get_element().unwrap().get_attribute("title"),
+            Some("a & b".to_string())
+        );
+        assert!(matches!(&children(&p)[0], NodeKind::Text(t) if t == "x < y\u{a0}あ"));
+    }
+
+    fn text_of(window: &Rc<RefCell<Window>>, element_kind: ElementKind) -> Option<String> {
+        let element = get_elements(window.borrow().document(), &[element_kind]).remove(0);
+        let text = match children(&element).first() {
+            Some(NodeKind::Text(t)) => Some(t.clone()),
+            _ => None,
+        };
+        text
+    }
+
+    #[test]
+    fn test_text_elements() {
+        let html = "<html><head><title>a &amp; <b></title><style>p > a { color: red; } /* <p> */</style></head><body><textarea>\n<p>&lt;</textarea><xmp><b>x</b></xmp><p>after</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+        assert_eq!(
+            text_of(&window, ElementKind::Title),
+            Some("a & <b>".to_string())
+        );
+        assert_eq!(
+            get_style_content(document.clone()),
+            "p > a { color: red; } /* <p> */"
+        );
+        assert_eq!(
+            text_of(&window, ElementKind::Textarea),
+            Some("<p><".to_string())
+        );
+        assert_eq!(
+            text_of(&window, ElementKind::Xmp),
+            Some("<b>x</b>".to_string())
+        );
+        // The end tags put the tree builder back where it was.
+        assert_eq!(text_of(&window, ElementKind::P), Some("after".to_string()));
+        let p = get_elements(document, &[ElementKind::P]).remove(0);
+        let body = p.borrow().parent().upgrade().unwrap();
+        assert_eq!(body.borrow().element_kind(), Some(ElementKind::Body));
+    }
+
+    #[test]
+    fn test_plaintext() {
+        let html = "<body><p>a</p><plaintext></plaintext><p>b".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        assert_eq!(
+            text_of(&window, ElementKind::Plaintext),
+            Some("</plaintext><p>b".to_string())
+        );
+        assert_eq!(
+            get_elements(window.borrow().document(), &[ElementKind::P]).len(),
+            1
+        );
+    }
+
+    fn parse_errors(html: &str) -> Vec<String> {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html.to_string()));
+        parser.construct_tree();
+        parser.errors().iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_errors("<!DOCTYPE html><html><head></head><body><p>a</p></body></html>"),
+            Vec::<String>::new()
+        );
+        // Stray and mis-nested end tags are reported and ignored rather than panicking.
+        assert_eq!(
+            parse_errors("<!DOCTYPE html><body></p><h1><a>x</h1></a>\n<body></head>"),
+            vec![
+                "1:22: unexpected-end-tag </p>".to_string(),
+                "1:34: unexpected-end-tag </h1>".to_string(),
+                "1:39: unexpected-end-tag </a>".to_string(),
+                "2:1: unexpected-start-tag <body>".to_string(),
+                "2:7: unexpected-end-tag </head>".to_string(),
+            ]
+        );
+        // Errors from the tokenizer and the tree builder come out in source order.
+        assert_eq!(
+            parse_errors("<p a=1 a=2>\0</p>\n<h1>x"),
+            vec![
+                "1:1: missing-doctype".to_string(),
+                "1:9: duplicate-attribute".to_string(),
+                "1:12: unexpected-null-character".to_string(),
+                "2:6: eof-in-element <h1>".to_string(),
+            ]
+        );
+        assert_eq!(
+            parse_errors("<!DOCTYPE html><body></body>x<!DOCTYPE html><style>a"),
+            vec![
+                "1:29: unexpected-content-after-body".to_string(),
+                "1:30: unexpected-doctype".to_string(),
+                "1:53: eof-in-element <style>".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_node_positions() {
+        let html = "<!DOCTYPE html>\n<html>\n<body>\n  <p>one\ntwo</p><!--c-->".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+        assert_eq!(document.borrow().position(), None);
+        let p = get_elements(document, &[ElementKind::P]).remove(0);
+        assert_eq!(p.borrow().position(), Some(SourcePosition::new(4, 3)));
+        let text = p.borrow().first_child().unwrap();
+        assert_eq!(text.borrow().position(), Some(SourcePosition::new(4, 6)));
+        let comment = p.borrow().next_sibling().unwrap();
+        assert!(matches!(comment.borrow().kind(), NodeKind::Comment(_)));
+        assert_eq!(comment.borrow().position(), Some(SourcePosition::new(5, 8)));
+    }
+
+    // The tree as text, one node per line, to compare trees built in different ways.
+    fn dump(node: &Rc<RefCell<Node>>, depth: usize, out: &mut String) {
+        let node = node.borrow();
+        out.push_str(&format!(
+            "{:depth$}{:?} {:?}\n",
+            "",
+            node.kind(),
+            node.position()
+        ));
+        let mut child = node.first_child();
+        while let Some(c) = child {
+            dump(&c, depth + 1, out);
+            child = c.borrow().next_sibling();
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        let html = "<!DOCTYPE html><html><head><title>a &amp; b</title><style>p{}</style></head>\
+                    <body><pre>\nx</pre><textarea>\ny</textarea><p>one<!--c-->two</p><h1>z";
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html.to_string()));
+        let mut expected = String::new();
+        dump(
+            &parser.construct_tree().borrow().document(),
+            0,
+            &mut expected,
+        );
+        let expected_errors = parser.errors();
+
+        for at in 0..=html.chars().count() {
+            let mut parser = HtmlParser::new(HtmlTokenizer::streaming());
+            parser.feed(&html.chars().take(at).collect::<String>());
+            parser.feed(&html.chars().skip(at).collect::<String>());
+            let mut tree = String::new();
+            dump(&parser.construct_tree().borrow().document(), 0, &mut tree);
+            assert_eq!(tree, expected, "split at {}", at);
+            assert_eq!(parser.errors(), expected_errors, "split at {}", at);
+        }
+    }
+
+    #[test]
+    fn test_partial_tree() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::streaming());
+        parser.feed("<html><body><p>Hello, wor");
+        let document = parser.window().borrow().document();
+        let p = get_elements(document.clone(), &[ElementKind::P]).remove(0);
+        let text_of = |node: &Rc<RefCell<Node>>| match node.borrow().first_child()?.borrow().kind()
+        {
+            NodeKind::Text(text) => Some(text),
+            _ => None,
+        };
+        assert_eq!(text_of(&p), Some("Hello, wor".to_string()));
+        // Nothing is reported as cut off until the document ends.
+        assert_eq!(
+            parser.errors().iter().map(|e| e.kind()).collect::<Vec<_>>(),
+            vec![ParseErrorKind::MissingDoctype]
+        );
+
+        parser.feed("ld</p><h1>");
+        let window = parser.construct_tree();
+        assert!(Rc::ptr_eq(&window.borrow().document(), &document));
+        assert_eq!(text_of(&p), Some("Hello, world".to_string()));
+        assert_eq!(
+            parser.errors().iter().map(|e| e.kind()).collect::<Vec<_>>(),
+            vec![
+                ParseErrorKind::MissingDoctype,
+                ParseErrorKind::EofInElement("h1".to_string())
+            ]
+        );
+    }
 }
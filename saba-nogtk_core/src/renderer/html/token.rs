@@ -1,4 +1,8 @@
+use crate::encoding::windows_1252;
 use crate::renderer::html::attribute::Attribute;
+use crate::renderer::html::diagnostics::{LineIndex, ParseError, ParseErrorKind, SourcePosition};
+use crate::renderer::html::entities;
+use alloc::collections::VecDeque;
 use alloc::string::String;
 use alloc::vec::Vec;
 
@@ -10,22 +14,140 @@ pub struct HtmlTokenizer {
     latest_token: Option<HtmlToken>,
     input: Vec<char>,
     buf: String,
+    cdata_allowed: bool,
+    return_state: State,
+    character_reference_code: u32,
+    pending_chars: VecDeque<char>,
+    last_start_tag: Option<String>,
+    end_tag_has_attributes: bool,
+    line_index: LineIndex,
+    // Offsets of the character being looked at and of the start of the token being built.
+    current: usize,
+    token_start: usize,
+    errors: Vec<ParseError>,
+    // Whether all of the input has been given. Until then the tokenizer stops, rather than
+    // treating it as the end of the file, where it runs out of input.
+    complete: bool,
+}
+
+// The parts of a DOCTYPE token that are built up character by character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum DoctypeField {
+    Name,
+    PublicId,
+    SystemId,
+}
+
+pub(crate) fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0c' | ' ')
 }
 
 impl HtmlTokenizer {
     pub fn new(html: String) -> Self {
+        let input: Vec<char> = html.chars().collect();
         Self {
             state: State::Data,
             pos: 0,
             reconsume: false,
             latest_token: None,
-            input: html.chars().collect(),
+            line_index: LineIndex::new(&input),
+            input,
             buf: String::new(),
+            cdata_allowed: false,
+            return_state: State::Data,
+            character_reference_code: 0,
+            pending_chars: VecDeque::new(),
+            last_start_tag: None,
+            end_tag_has_attributes: false,
+            current: 0,
+            token_start: 0,
+            errors: Vec::new(),
+            complete: true,
+        }
+    }
+
+    // A tokenizer for a document that arrives in pieces through `feed`. `next` returns `None`
+    // whenever it needs more input, and can be called again after the next piece; `finish`
+    // marks the end of the document.
+    pub fn streaming() -> Self {
+        let mut t = Self::new(String::new());
+        t.complete = false;
+        t
+    }
+
+    pub fn feed(&mut self, chunk: &str) {
+        let start = self.input.len();
+        self.input.extend(chunk.chars());
+        self.line_index.extend(&self.input, start);
+    }
+
+    pub fn finish(&mut self) {
+        self.complete = true;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    // CDATA sections are only recognized in foreign content; elsewhere `<![CDATA[` starts a bogus
+    // comment. The tree builder turns this on while the adjusted current node is foreign.
+    pub fn set_cdata_allowed(&mut self, allowed: bool) {
+        self.cdata_allowed = allowed;
+    }
+
+    // The tree builder switches into the RCDATA, RAWTEXT, script data or PLAINTEXT state right
+    // after inserting an element whose contents are text rather than markup.
+    pub fn switch_to(&mut self, state: State) {
+        self.state = state;
+    }
+
+    // The place just past the input given so far.
+    pub fn end_position(&self) -> SourcePosition {
+        self.line_index.position(self.input.len())
+    }
+
+    // Like `next`, with the place in the source where the token starts.
+    pub fn next_positioned(&mut self) -> Option<PositionedToken> {
+        let token = self.next()?;
+        Some(PositionedToken::new(
+            token,
+            self.line_index.position(self.token_start),
+        ))
+    }
+
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    fn error(&mut self, kind: ParseErrorKind) {
+        let position = self.line_index.position(self.current);
+        self.errors.push(ParseError::new(kind, position));
+    }
+
+    // An end tag is appropriate when it matches the last start tag this tokenizer emitted.
+    fn is_appropriate_end_tag(&self) -> bool {
+        match (&self.latest_token, &self.last_start_tag) {
+            (Some(HtmlToken::EndTag { tag }), Some(last)) => tag == last,
+            _ => false,
         }
     }
 
-    fn is_eof(&self) -> bool {
-        self.pos > self.input.len()
+    // Whether the input so far, counting from the character just consumed, stops partway
+    // through `s`, so that whether it continues with `s` is not known yet.
+    fn is_cut_short(&self, s: &str, ignore_case: bool) -> bool {
+        if self.complete {
+            return false;
+        }
+        let available = &self.input[self.pos - 1..];
+        let expected: Vec<char> = s.chars().collect();
+        available.len() < expected.len()
+            && available.iter().zip(&expected).all(|(a, b)| {
+                if ignore_case {
+                    a.eq_ignore_ascii_case(b)
+                } else {
+                    a == b
+                }
+            })
     }
 
     fn reconsume_input(&mut self) -> char {
@@ -39,7 +161,319 @@ impl HtmlTokenizer {
         c
     }
 
+    // Consumes `s` if the input continues with it, counting from the character just consumed.
+    fn consume_if_next(&mut self, s: &str, ignore_case: bool) -> bool {
+        let start = self.pos - 1;
+        let expected: Vec<char> = s.chars().collect();
+        if start + expected.len() > self.input.len() {
+            return false;
+        }
+        let matched = self.input[start..start + expected.len()]
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| {
+                if ignore_case {
+                    a.eq_ignore_ascii_case(b)
+                } else {
+                    a == b
+                }
+            });
+        if matched {
+            self.pos = start + expected.len();
+        }
+        matched
+    }
+
+    fn consumed_as_part_of_attribute(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted
+                | State::AttributeValueSingleQuoted
+                | State::AttributeValueUnquoted
+        )
+    }
+
+    // Hands the characters in the temporary buffer to the return state: they become part of the
+    // attribute value, or are emitted one by one as character tokens.
+    fn flush_code_points_consumed_as_character_reference(&mut self) {
+        let buf = core::mem::take(&mut self.buf);
+        if self.consumed_as_part_of_attribute() {
+            for c in buf.chars() {
+                self.append_attribute(c, /*is_name*/ false);
+            }
+        } else {
+            self.pending_chars.extend(buf.chars());
+        }
+    }
+
+    // A named character reference can only be matched once the input shows where the name ends,
+    // or is longer than any name, and the character after it is known.
+    fn needs_more_input_for_name(&self) -> bool {
+        if self.complete {
+            return false;
+        }
+        let start = self.pos - 1;
+        let mut end = start;
+        while end < self.input.len() && self.input[end].is_ascii_alphanumeric() {
+            end += 1;
+        }
+        end == self.input.len() && end - start <= entities::LONGEST_NAME
+    }
+
+    // Looks for the longest named character reference starting at the character just consumed
+    // and consumes it. Returns the name as written and the characters it stands for.
+    fn consume_named_character_reference(&mut self) -> Option<(String, &'static str)> {
+        let start = self.pos - 1;
+        // Names are alphanumeric, optionally followed by a semicolon.
+        let mut end = start;
+        while end < self.input.len() && self.input[end].is_ascii_alphanumeric() {
+            end += 1;
+        }
+        if end < self.input.len() && self.input[end] == ';' {
+            end += 1;
+        }
+        let max_len = core::cmp::min(entities::LONGEST_NAME, end - start);
+        for len in (1..=max_len).rev() {
+            let name: String = self.input[start..start + len].iter().collect();
+            if let Some(value) = entities::lookup(&name) {
+                self.pos = start + len;
+                return Some((name, value));
+            }
+        }
+        None
+    }
+
+    // The "numeric character reference end" state.
+    fn finish_numeric_character_reference(&mut self) {
+        let code = self.character_reference_code;
+        let error = match code {
+            0 => Some(ParseErrorKind::NullCharacterReference),
+            0x110000.. => Some(ParseErrorKind::CharacterReferenceOutsideUnicodeRange),
+            0xd800..=0xdfff => Some(ParseErrorKind::SurrogateCharacterReference),
+            0xfdd0..=0xfdef => Some(ParseErrorKind::NoncharacterCharacterReference),
+            _ if code & 0xfffe == 0xfffe => Some(ParseErrorKind::NoncharacterCharacterReference),
+            0x0d | 0x80..=0x9f => Some(ParseErrorKind::ControlCharacterReference),
+            0x01..=0x1f | 0x7f if !matches!(code, 0x09 | 0x0a | 0x0c) => {
+                Some(ParseErrorKind::ControlCharacterReference)
+            }
+            _ => None,
+        };
+        if let Some(error) = error {
+            self.error(error);
+        }
+        let c = match self.character_reference_code {
+            0 => '\u{fffd}',
+            // C1 controls are taken to be windows-1252 bytes, as legacy content expects.
+            code @ 0x80..=0x9f => windows_1252(code as u8),
+            code => char::from_u32(code).unwrap_or('\u{fffd}'),
+        };
+        self.buf = String::from(c);
+        self.flush_code_points_consumed_as_character_reference();
+        self.state = self.return_state.clone();
+    }
+
+    fn create_comment(&mut self) {
+        self.latest_token = Some(HtmlToken::Comment(String::new()));
+    }
+
+    fn append_comment(&mut self, s: &str) {
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Comment(ref mut data)) => data.push_str(s),
+            _ => panic!("`latest_token` should be Comment"),
+        }
+    }
+
+    fn create_doctype(&mut self) {
+        self.latest_token = Some(HtmlToken::Doctype {
+            name: None,
+            public_id: None,
+            system_id: None,
+            force_quirks: false,
+        });
+    }
+
+    fn doctype_field(&mut self, field: DoctypeField) -> &mut Option<String> {
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut name,
+                ref mut public_id,
+                ref mut system_id,
+                force_quirks: _,
+            }) => match field {
+                DoctypeField::Name => name,
+                DoctypeField::PublicId => public_id,
+                DoctypeField::SystemId => system_id,
+            },
+            _ => panic!("`latest_token` should be Doctype"),
+        }
+    }
+
+    fn start_doctype_field(&mut self, field: DoctypeField) {
+        *self.doctype_field(field) = Some(String::new());
+    }
+
+    fn append_doctype_field(&mut self, field: DoctypeField, c: char) {
+        // NULL is replaced rather than dropped, like everywhere else outside of tags.
+        let c = if c == '\0' {
+            self.error(ParseErrorKind::UnexpectedNullCharacter);
+            '\u{fffd}'
+        } else {
+            c
+        };
+        self.doctype_field(field)
+            .get_or_insert_with(String::new)
+            .push(c);
+    }
+
+    fn set_force_quirks(&mut self) {
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut force_quirks,
+                ..
+            }) => *force_quirks = true,
+            _ => panic!("`latest_token` should be Doctype"),
+        }
+    }
+
+    // Called when the input runs out. Comments and DOCTYPEs in progress are emitted as they are,
+    // an unfinished DOCTYPE forcing quirks mode; unfinished tags are dropped.
+    fn emit_eof(&mut self) -> Option<HtmlToken> {
+        self.current = self.input.len();
+        let state = core::mem::replace(&mut self.state, State::Data);
+        match state {
+            State::TagOpen | State::EndTagOpen => {
+                self.error(ParseErrorKind::EofBeforeTagName);
+                self.pending_chars.push_back('<');
+                if state == State::EndTagOpen {
+                    self.pending_chars.push_back('/');
+                }
+                self.emit_pending_or_eof()
+            }
+            State::TagName
+            | State::BeforeAttributeName
+            | State::AttributeName
+            | State::AfterAttributeName
+            | State::BeforeAttributeValue
+            | State::AttributeValueDoubleQuoted
+            | State::AttributeValueSingleQuoted
+            | State::AttributeValueUnquoted
+            | State::AfterAttributeValueQuoted
+            | State::SelfClosingStartTag => {
+                self.error(ParseErrorKind::EofInTag);
+                self.latest_token = None;
+                self.emit_eof()
+            }
+            State::MarkupDeclarationOpen => {
+                self.error(ParseErrorKind::IncorrectlyOpenedComment);
+                self.create_comment();
+                self.take_latest_token()
+            }
+            State::CommentStart
+            | State::CommentStartDash
+            | State::Comment
+            | State::CommentLessThanSign
+            | State::CommentLessThanSignBang
+            | State::CommentLessThanSignBangDash
+            | State::CommentEndDash
+            | State::CommentEnd
+            | State::CommentEndBang => {
+                self.error(ParseErrorKind::EofInComment);
+                self.take_latest_token()
+            }
+            State::BogusComment | State::BogusDoctype => self.take_latest_token(),
+            State::Doctype | State::BeforeDoctypeName => {
+                self.error(ParseErrorKind::EofInDoctype);
+                self.create_doctype();
+                self.set_force_quirks();
+                self.take_latest_token()
+            }
+            State::DoctypeName
+            | State::AfterDoctypeName
+            | State::AfterDoctypePublicKeyword
+            | State::BeforeDoctypePublicIdentifier
+            | State::DoctypePublicIdentifierDoubleQuoted
+            | State::DoctypePublicIdentifierSingleQuoted
+            | State::AfterDoctypePublicIdentifier
+            | State::BetweenDoctypePublicAndSystemIdentifiers
+            | State::AfterDoctypeSystemKeyword
+            | State::BeforeDoctypeSystemIdentifier
+            | State::DoctypeSystemIdentifierDoubleQuoted
+            | State::DoctypeSystemIdentifierSingleQuoted
+            | State::AfterDoctypeSystemIdentifier => {
+                self.error(ParseErrorKind::EofInDoctype);
+                self.set_force_quirks();
+                self.take_latest_token()
+            }
+            State::CdataSection | State::CdataSectionBracket | State::CdataSectionEnd => {
+                self.error(ParseErrorKind::EofInCdata);
+                if state != State::CdataSection {
+                    self.pending_chars.push_back(']');
+                }
+                if state == State::CdataSectionEnd {
+                    self.pending_chars.push_back(']');
+                }
+                self.emit_pending_or_eof()
+            }
+            State::CharacterReference
+            | State::NamedCharacterReference
+            | State::NumericCharacterReference
+            | State::HexadecimalCharacterReferenceStart
+            | State::DecimalCharacterReferenceStart => {
+                if state == State::CharacterReference {
+                    self.buf = String::from("&");
+                }
+                if state == State::HexadecimalCharacterReferenceStart
+                    || state == State::DecimalCharacterReferenceStart
+                {
+                    self.error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+                }
+                self.flush_code_points_consumed_as_character_reference();
+                self.state = self.return_state.clone();
+                self.emit_pending_or_eof()
+            }
+            State::HexadecimalCharacterReference | State::DecimalCharacterReference => {
+                self.error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+                self.finish_numeric_character_reference();
+                self.emit_pending_or_eof()
+            }
+            State::AmbiguousAmpersand => {
+                self.state = self.return_state.clone();
+                self.emit_eof()
+            }
+            State::RcdataLessThanSign
+            | State::RawtextLessThanSign
+            | State::ScriptDataLessThanSign => {
+                self.pending_chars.push_back('<');
+                self.emit_pending_or_eof()
+            }
+            State::RcdataEndTagOpen
+            | State::RawtextEndTagOpen
+            | State::ScriptDataEndTagOpen
+            | State::RcdataEndTagName
+            | State::RawtextEndTagName
+            | State::ScriptDataEndTagName => {
+                self.latest_token = None;
+                self.pending_chars.push_back('<');
+                self.pending_chars.push_back('/');
+                self.pending_chars.extend(self.buf.chars());
+                self.emit_pending_or_eof()
+            }
+            _ => {
+                self.token_start = self.input.len();
+                Some(HtmlToken::Eof)
+            }
+        }
+    }
+
+    fn emit_pending_or_eof(&mut self) -> Option<HtmlToken> {
+        match self.pending_chars.pop_front() {
+            Some(c) => Some(HtmlToken::Char(c)),
+            None => self.emit_eof(),
+        }
+    }
+
     fn create_tag(&mut self, start_tag_token: bool) {
+        self.end_tag_has_attributes = false;
         if start_tag_token {
             self.latest_token = Some(HtmlToken::StartTag {
                 tag: String::new(),
@@ -73,6 +507,14 @@ impl HtmlTokenizer {
         self.latest_token = None;
         assert!(self.latest_token.is_none());
 
+        match &t {
+            Some(HtmlToken::StartTag { tag, .. }) => self.last_start_tag = Some(tag.clone()),
+            Some(HtmlToken::EndTag { .. }) if self.end_tag_has_attributes => {
+                self.error(ParseErrorKind::EndTagWithAttributes)
+            }
+            _ => {}
+        }
+
         t
     }
 
@@ -88,6 +530,8 @@ impl HtmlTokenizer {
                 } => {
                     attributes.push(Attribute::new());
                 }
+                // Attributes on end tags are a parse error and are dropped.
+                HtmlToken::EndTag { .. } => self.end_tag_has_attributes = true,
                 _ => panic!("`latest_token` should be StartTag"),
             }
         }
@@ -108,6 +552,7 @@ impl HtmlTokenizer {
 
                     attributes[len - 1].add_char(c, is_name);
                 }
+                HtmlToken::EndTag { .. } => {}
                 _ => panic!("`latest_token` should be StartTag"),
             }
         }
@@ -123,10 +568,28 @@ impl HtmlTokenizer {
                     ref mut self_closing,
                     attributes: _,
                 } => *self_closing = true,
+                HtmlToken::EndTag { .. } => {
+                    self.error(ParseErrorKind::EndTagWithTrailingSolidus);
+                }
                 _ => panic!("`latest_token` should be StartTag"),
             }
         }
     }
+
+    // Called when an attribute's name is complete. The first of two attributes with the same
+    // name wins, as `Element::get_attribute` returns the first match.
+    fn check_duplicate_attribute(&mut self) {
+        let duplicate = match &self.latest_token {
+            Some(HtmlToken::StartTag { attributes, .. }) => match attributes.split_last() {
+                Some((last, others)) => others.iter().any(|a| a.name() == last.name()),
+                None => false,
+            },
+            _ => false,
+        };
+        if duplicate {
+            self.error(ParseErrorKind::DuplicateAttribute);
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -140,9 +603,37 @@ pub enum HtmlToken {
         tag: String,
     },
     Char(char),
+    Comment(String),
+    Doctype {
+        name: Option<String>,
+        public_id: Option<String>,
+        system_id: Option<String>,
+        force_quirks: bool,
+    },
     Eof,
 }
 
+// A token and where it starts in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionedToken {
+    token: HtmlToken,
+    position: SourcePosition,
+}
+
+impl PositionedToken {
+    pub fn new(token: HtmlToken, position: SourcePosition) -> Self {
+        Self { token, position }
+    }
+
+    pub fn token(&self) -> HtmlToken {
+        self.token.clone()
+    }
+
+    pub fn position(&self) -> SourcePosition {
+        self.position
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum State {
     Data,
@@ -158,26 +649,118 @@ pub enum State {
     AttributeValueUnquoted,
     AfterAttributeValueQuoted,
     SelfClosingStartTag,
+    Rcdata,
+    RcdataLessThanSign,
+    RcdataEndTagOpen,
+    RcdataEndTagName,
+    Rawtext,
+    RawtextLessThanSign,
+    RawtextEndTagOpen,
+    RawtextEndTagName,
     ScriptData,
     ScriptDataLessThanSign,
     ScriptDataEndTagOpen,
     ScriptDataEndTagName,
-    TemporaryBuffer,
+    Plaintext,
+    MarkupDeclarationOpen,
+    CommentStart,
+    CommentStartDash,
+    Comment,
+    CommentLessThanSign,
+    CommentLessThanSignBang,
+    CommentLessThanSignBangDash,
+    CommentEndDash,
+    CommentEnd,
+    CommentEndBang,
+    BogusComment,
+    Doctype,
+    BeforeDoctypeName,
+    DoctypeName,
+    AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+    CdataSection,
+    CdataSectionBracket,
+    CdataSectionEnd,
+    CharacterReference,
+    NamedCharacterReference,
+    AmbiguousAmpersand,
+    NumericCharacterReference,
+    HexadecimalCharacterReferenceStart,
+    DecimalCharacterReferenceStart,
+    HexadecimalCharacterReference,
+    DecimalCharacterReference,
+}
+
+impl State {
+    fn is_text(&self) -> bool {
+        matches!(
+            self,
+            State::Data | State::Rcdata | State::Rawtext | State::ScriptData | State::Plaintext
+        )
+    }
+
+    // Where the less-than sign and end tag states of RCDATA, RAWTEXT and script data go back to
+    // when the characters turn out not to be an appropriate end tag.
+    fn text_state(&self) -> State {
+        match self {
+            State::RcdataLessThanSign | State::RcdataEndTagOpen | State::RcdataEndTagName => {
+                State::Rcdata
+            }
+            State::RawtextLessThanSign | State::RawtextEndTagOpen | State::RawtextEndTagName => {
+                State::Rawtext
+            }
+            _ => State::ScriptData,
+        }
+    }
 }
 
 impl Iterator for HtmlTokenizer {
     type Item = HtmlToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
-            return None;
+        if self.pos >= self.input.len() && self.pending_chars.is_empty() && !self.reconsume {
+            if self.state.is_text() {
+                self.token_start = self.input.len();
+                return None;
+            }
+            // In the other states the end of the input still completes or reports the token
+            // being built, which a streaming tokenizer only does once it is finished.
+            if !self.complete {
+                return None;
+            }
         }
 
         loop {
+            if let Some(c) = self.pending_chars.pop_front() {
+                return Some(HtmlToken::Char(c));
+            }
+            if !self.reconsume && self.pos >= self.input.len() {
+                if !self.complete {
+                    return None;
+                }
+                return self.emit_eof();
+            }
             let c = match self.reconsume {
                 true => self.reconsume_input(),
                 false => self.consume_next_input(),
             };
+            self.current = self.pos - 1;
+            // Every token starts with a character consumed in one of the text states: a tag,
+            // comment or DOCTYPE with its `<`, a character reference with its `&`.
+            if self.state.is_text() || self.state == State::CdataSection {
+                self.token_start = self.current;
+            }
             match self.state {
                 State::Data => {
                     if c == '<' {
@@ -185,8 +768,15 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '&' {
+                        self.return_state = State::Data;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
+                    if c == '\0' {
+                        // Passed on as it is; the tree builder drops it.
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
                     }
 
                     return Some(HtmlToken::Char(c));
@@ -197,6 +787,11 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '!' {
+                        self.state = State::MarkupDeclarationOpen;
+                        continue;
+                    }
+
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
                         self.state = State::TagName;
@@ -204,27 +799,41 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '?' {
+                        self.error(ParseErrorKind::UnexpectedQuestionMarkInsteadOfTagName);
+                        self.create_comment();
+                        self.reconsume = true;
+                        self.state = State::BogusComment;
+                        continue;
                     }
 
+                    // Not a tag after all, as in `a < b`.
+                    self.error(ParseErrorKind::InvalidFirstCharacterOfTagName);
                     self.reconsume = true;
                     self.state = State::Data;
+                    return Some(HtmlToken::Char('<'));
                 }
                 State::EndTagOpen => {
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
                         self.state = State::TagName;
                         self.create_tag(false);
                         continue;
                     }
+
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingEndTagName);
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    self.error(ParseErrorKind::InvalidFirstCharacterOfTagName);
+                    self.reconsume = true;
+                    self.create_comment();
+                    self.state = State::BogusComment;
                 }
                 State::TagName => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -239,50 +848,67 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if c.is_ascii_uppercase() {
-                        self.append_tag_name(c.to_ascii_lowercase());
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_tag_name('\u{fffd}');
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
-                    self.append_tag_name(c);
+                    self.append_tag_name(c.to_ascii_lowercase());
                 }
                 State::BeforeAttributeName => {
-                    if c == '/' || c == '>' || self.is_eof() {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '/' || c == '>' {
                         self.reconsume = true;
                         self.state = State::AfterAttributeName;
                         continue;
                     }
 
+                    if c == '=' {
+                        // The `=` becomes the first character of the attribute's name.
+                        self.error(ParseErrorKind::UnexpectedEqualsSignBeforeAttributeName);
+                        self.start_new_attribute();
+                        self.append_attribute(c, /*is_name*/ true);
+                        self.state = State::AttributeName;
+                        continue;
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeName;
                     self.start_new_attribute();
                 }
                 State::AttributeName => {
-                    if c == ' ' || c == '/' || c == '>' || self.is_eof() {
+                    if is_whitespace(c) || c == '/' || c == '>' {
+                        self.check_duplicate_attribute();
                         self.reconsume = true;
                         self.state = State::AfterAttributeName;
                         continue;
                     }
 
                     if c == '=' {
+                        self.check_duplicate_attribute();
                         self.state = State::BeforeAttributeValue;
                         continue;
                     }
 
-                    if c.is_ascii_uppercase() {
-                        self.append_attribute(c.to_ascii_lowercase(), /*is_name*/ true);
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute('\u{fffd}', /*is_name*/ true);
                         continue;
                     }
 
-                    self.append_attribute(c, /*is_name*/ true)
+                    if c == '"' || c == '\'' || c == '<' {
+                        self.error(ParseErrorKind::UnexpectedCharacterInAttributeName);
+                    }
+
+                    self.append_attribute(c.to_ascii_lowercase(), /*is_name*/ true)
                 }
 
                 State::AfterAttributeName => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         continue;
                     }
 
@@ -301,17 +927,13 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
                     self.reconsume = true;
                     self.state = State::AttributeName;
                     self.start_new_attribute();
                 }
 
                 State::BeforeAttributeValue => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         continue;
                     }
 
@@ -325,54 +947,74 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingAttributeValue);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeValueUnquoted;
                 }
 
-                State::AttributeValueDoubleQuoted => {
-                    if c == '"' {
+                State::AttributeValueDoubleQuoted | State::AttributeValueSingleQuoted => {
+                    let quote = if self.state == State::AttributeValueDoubleQuoted {
+                        '"'
+                    } else {
+                        '\''
+                    };
+                    if c == quote {
                         self.state = State::AfterAttributeValueQuoted;
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '&' {
+                        self.return_state = self.state.clone();
+                        self.state = State::CharacterReference;
+                        continue;
                     }
-                    self.append_attribute(c, /*is_name*/ false);
-                }
 
-                State::AttributeValueSingleQuoted => {
-                    if c == '\'' {
-                        self.state = State::AfterAttributeValueQuoted;
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute('\u{fffd}', /*is_name*/ false);
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
                     self.append_attribute(c, /*is_name*/ false);
                 }
 
                 State::AttributeValueUnquoted => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
 
+                    if c == '&' {
+                        self.return_state = State::AttributeValueUnquoted;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
                     if c == '>' {
                         self.state = State::Data;
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute('\u{fffd}', /*is_name*/ false);
+                        continue;
+                    }
+
+                    if matches!(c, '"' | '\'' | '<' | '=' | '`') {
+                        self.error(ParseErrorKind::UnexpectedCharacterInUnquotedAttributeValue);
                     }
 
                     self.append_attribute(c, /*is_name*/ false);
                 }
 
                 State::AfterAttributeValueQuoted => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -387,11 +1029,9 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
+                    self.error(ParseErrorKind::MissingWhitespaceBetweenAttributes);
                     self.reconsume = true;
-                    self.state = State::BeforeAttributeValue;
+                    self.state = State::BeforeAttributeName;
                 }
 
                 State::SelfClosingStartTag => {
@@ -400,106 +1040,753 @@ impl Iterator for HtmlTokenizer {
                         self.state = State::Data;
                         return self.take_latest_token();
                     }
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+
+                    self.error(ParseErrorKind::UnexpectedSolidusInTag);
+                    self.reconsume = true;
+                    self.state = State::BeforeAttributeName;
+                }
+
+                State::Rcdata => {
+                    if c == '&' {
+                        self.return_state = State::Rcdata;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
+                    if c == '<' {
+                        self.state = State::RcdataLessThanSign;
+                        continue;
+                    }
+
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        return Some(HtmlToken::Char('\u{FFFD}'));
                     }
+
+                    return Some(HtmlToken::Char(c));
                 }
 
-                State::ScriptData => {
+                State::Rawtext | State::ScriptData => {
                     if c == '<' {
-                        self.state = State::ScriptDataLessThanSign;
+                        self.state = match self.state {
+                            State::Rawtext => State::RawtextLessThanSign,
+                            _ => State::ScriptDataLessThanSign,
+                        };
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        return Some(HtmlToken::Char('\u{FFFD}'));
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+
+                State::Plaintext => {
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        return Some(HtmlToken::Char('\u{FFFD}'));
                     }
 
                     return Some(HtmlToken::Char(c));
                 }
 
-                State::ScriptDataLessThanSign => {
+                State::RcdataLessThanSign
+                | State::RawtextLessThanSign
+                | State::ScriptDataLessThanSign => {
                     if c == '/' {
                         self.buf = String::new();
-                        self.state = State::ScriptDataEndTagOpen;
+                        self.state = match self.state {
+                            State::RcdataLessThanSign => State::RcdataEndTagOpen,
+                            State::RawtextLessThanSign => State::RawtextEndTagOpen,
+                            _ => State::ScriptDataEndTagOpen,
+                        };
                         continue;
                     }
 
                     self.reconsume = true;
-                    self.state = State::ScriptData;
+                    self.state = self.state.text_state();
                     return Some(HtmlToken::Char('<'));
                 }
 
-                State::ScriptDataEndTagOpen => {
+                State::RcdataEndTagOpen
+                | State::RawtextEndTagOpen
+                | State::ScriptDataEndTagOpen => {
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
-                        self.state = State::ScriptDataEndTagName;
                         self.create_tag(false);
+                        self.state = match self.state {
+                            State::RcdataEndTagOpen => State::RcdataEndTagName,
+                            State::RawtextEndTagOpen => State::RawtextEndTagName,
+                            _ => State::ScriptDataEndTagName,
+                        };
                         continue;
                     }
 
                     self.reconsume = true;
-                    self.state = State::ScriptData;
-
+                    self.state = self.state.text_state();
+                    self.pending_chars.push_back('/');
                     return Some(HtmlToken::Char('<'));
                 }
 
-                State::ScriptDataEndTagName => {
-                    if c == '>' {
-                        self.state = State::Data;
-                        return self.take_latest_token();
+                State::RcdataEndTagName
+                | State::RawtextEndTagName
+                | State::ScriptDataEndTagName => {
+                    if self.is_appropriate_end_tag() {
+                        if is_whitespace(c) {
+                            self.state = State::BeforeAttributeName;
+                            continue;
+                        }
+
+                        if c == '/' {
+                            self.state = State::SelfClosingStartTag;
+                            continue;
+                        }
+
+                        if c == '>' {
+                            self.state = State::Data;
+                            return self.take_latest_token();
+                        }
                     }
 
-                    if c.is_ascii_uppercase() {
-                        self.buf.push(c);
+                    if c.is_ascii_alphabetic() {
                         self.append_tag_name(c.to_ascii_lowercase());
+                        self.buf.push(c);
                         continue;
                     }
 
-                    self.state = State::TemporaryBuffer;
-                    self.buf = String::from("</") + &self.buf;
-                    self.buf.push(c);
-                    continue;
+                    // Not the end of the element after all: everything since the `<` is text.
+                    self.latest_token = None;
+                    self.pending_chars.push_back('/');
+                    self.pending_chars.extend(self.buf.chars());
+                    self.reconsume = true;
+                    self.state = self.state.text_state();
+                    return Some(HtmlToken::Char('<'));
                 }
 
-                State::TemporaryBuffer => {
+                State::MarkupDeclarationOpen => {
+                    if self.is_cut_short("--", false)
+                        || self.is_cut_short("DOCTYPE", true)
+                        || self.is_cut_short("[CDATA[", false)
+                    {
+                        self.reconsume = true;
+                        return None;
+                    }
+
+                    if self.consume_if_next("--", false) {
+                        self.create_comment();
+                        self.state = State::CommentStart;
+                        continue;
+                    }
+
+                    if self.consume_if_next("DOCTYPE", true) {
+                        self.state = State::Doctype;
+                        continue;
+                    }
+
+                    if self.consume_if_next("[CDATA[", false) {
+                        if self.cdata_allowed {
+                            self.state = State::CdataSection;
+                            continue;
+                        }
+                        self.error(ParseErrorKind::CdataInHtmlContent);
+                        self.create_comment();
+                        self.append_comment("[CDATA[");
+                        self.state = State::BogusComment;
+                        continue;
+                    }
+
+                    self.error(ParseErrorKind::IncorrectlyOpenedComment);
                     self.reconsume = true;
+                    self.create_comment();
+                    self.state = State::BogusComment;
+                }
 
-                    if self.buf.chars().count() == 0 {
-                        self.state = State::ScriptData;
+                State::CommentStart => {
+                    if c == '-' {
+                        self.state = State::CommentStartDash;
                         continue;
                     }
 
-                    let c = self
-                        .buf
-                        .chars()
-                        .nth(0)
-                        .expect("self.buf should have at least 1 char");
-                    self.buf.remove(0);
-                    return Some(HtmlToken::Char(c));
+                    if c == '>' {
+                        self.error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
                 }
-            }
-        }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::alloc::string::ToString;
-    use alloc::vec;
+                State::CommentStartDash => {
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
 
-    #[test]
-    fn test_empty() {
-        let html = "".to_string();
-        let mut tokenizer = HtmlTokenizer::new(html);
-        assert!(tokenizer.next().is_none())
-    }
+                    if c == '>' {
+                        self.error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
 
-    #[test]
-    fn test_start_and_end_tag() {
-        let html = "<body></body>".to_string();
-        let mut tokenizer = HtmlTokenizer::new(html);
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::Comment => {
+                    if c == '<' {
+                        self.append_comment("<");
+                        self.state = State::CommentLessThanSign;
+                        continue;
+                    }
+
+                    if c == '-' {
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_comment("\u{fffd}");
+                        continue;
+                    }
+
+                    self.append_comment(c.encode_utf8(&mut [0; 4]));
+                }
+
+                State::CommentLessThanSign => {
+                    if c == '!' {
+                        self.append_comment("!");
+                        self.state = State::CommentLessThanSignBang;
+                        continue;
+                    }
+
+                    if c == '<' {
+                        self.append_comment("<");
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentLessThanSignBang => {
+                    if c == '-' {
+                        self.state = State::CommentLessThanSignBangDash;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentLessThanSignBangDash => {
+                    // A nested `<!--` is not worth a state of its own; `--` after it is treated
+                    // as the end of the comment either way.
+                    if c == '-' {
+                        if !self.complete && self.pos >= self.input.len() {
+                            self.reconsume = true;
+                            return None;
+                        }
+                        if self.input.get(self.pos).is_some_and(|&n| n != '>') {
+                            self.error(ParseErrorKind::NestedComment);
+                        }
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CommentEndDash;
+                }
+
+                State::CommentEndDash => {
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentEnd => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '!' {
+                        self.state = State::CommentEndBang;
+                        continue;
+                    }
+
+                    if c == '-' {
+                        self.append_comment("-");
+                        continue;
+                    }
+
+                    self.append_comment("--");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::CommentEndBang => {
+                    if c == '-' {
+                        self.append_comment("--!");
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.error(ParseErrorKind::IncorrectlyClosedComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("--!");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+
+                State::BogusComment => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_comment("\u{fffd}");
+                        continue;
+                    }
+
+                    self.append_comment(c.encode_utf8(&mut [0; 4]));
+                }
+
+                State::Doctype => {
+                    if !is_whitespace(c) {
+                        if c != '>' {
+                            self.error(ParseErrorKind::MissingWhitespaceBeforeDoctypeName);
+                        }
+                        self.reconsume = true;
+                    }
+                    self.state = State::BeforeDoctypeName;
+                }
+
+                State::BeforeDoctypeName => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    self.create_doctype();
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingDoctypeName);
+                        self.set_force_quirks();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_field(DoctypeField::Name, c.to_ascii_lowercase());
+                    self.state = State::DoctypeName;
+                }
+
+                State::DoctypeName => {
+                    if is_whitespace(c) {
+                        self.state = State::AfterDoctypeName;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_field(DoctypeField::Name, c.to_ascii_lowercase());
+                }
+
+                State::AfterDoctypeName => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_cut_short("PUBLIC", true) || self.is_cut_short("SYSTEM", true) {
+                        self.reconsume = true;
+                        return None;
+                    }
+
+                    if self.consume_if_next("PUBLIC", true) {
+                        self.state = State::AfterDoctypePublicKeyword;
+                        continue;
+                    }
+
+                    if self.consume_if_next("SYSTEM", true) {
+                        self.state = State::AfterDoctypeSystemKeyword;
+                        continue;
+                    }
+
+                    self.error(ParseErrorKind::InvalidCharacterSequenceAfterDoctypeName);
+                    self.set_force_quirks();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::AfterDoctypePublicKeyword | State::BeforeDoctypePublicIdentifier => {
+                    if is_whitespace(c) {
+                        self.state = State::BeforeDoctypePublicIdentifier;
+                        continue;
+                    }
+
+                    if c == '"' || c == '\'' {
+                        if self.state == State::AfterDoctypePublicKeyword {
+                            self.error(ParseErrorKind::MissingWhitespaceAfterDoctypePublicKeyword);
+                        }
+                        self.start_doctype_field(DoctypeField::PublicId);
+                        self.state = if c == '"' {
+                            State::DoctypePublicIdentifierDoubleQuoted
+                        } else {
+                            State::DoctypePublicIdentifierSingleQuoted
+                        };
+                        continue;
+                    }
+
+                    self.set_force_quirks();
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingDoctypePublicIdentifier);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.error(ParseErrorKind::MissingQuoteBeforeDoctypePublicIdentifier);
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::DoctypePublicIdentifierDoubleQuoted
+                | State::DoctypePublicIdentifierSingleQuoted => {
+                    let quote = if self.state == State::DoctypePublicIdentifierDoubleQuoted {
+                        '"'
+                    } else {
+                        '\''
+                    };
+                    if c == quote {
+                        self.state = State::AfterDoctypePublicIdentifier;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.error(ParseErrorKind::AbruptDoctypePublicIdentifier);
+                        self.set_force_quirks();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_field(DoctypeField::PublicId, c);
+                }
+
+                State::AfterDoctypePublicIdentifier
+                | State::BetweenDoctypePublicAndSystemIdentifiers => {
+                    if is_whitespace(c) {
+                        self.state = State::BetweenDoctypePublicAndSystemIdentifiers;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '"' || c == '\'' {
+                        if self.state == State::AfterDoctypePublicIdentifier {
+                            self.error(
+                                ParseErrorKind::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+                            );
+                        }
+                        self.start_doctype_field(DoctypeField::SystemId);
+                        self.state = if c == '"' {
+                            State::DoctypeSystemIdentifierDoubleQuoted
+                        } else {
+                            State::DoctypeSystemIdentifierSingleQuoted
+                        };
+                        continue;
+                    }
+
+                    self.error(ParseErrorKind::MissingQuoteBeforeDoctypeSystemIdentifier);
+                    self.set_force_quirks();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::AfterDoctypeSystemKeyword | State::BeforeDoctypeSystemIdentifier => {
+                    if is_whitespace(c) {
+                        self.state = State::BeforeDoctypeSystemIdentifier;
+                        continue;
+                    }
+
+                    if c == '"' || c == '\'' {
+                        if self.state == State::AfterDoctypeSystemKeyword {
+                            self.error(ParseErrorKind::MissingWhitespaceAfterDoctypeSystemKeyword);
+                        }
+                        self.start_doctype_field(DoctypeField::SystemId);
+                        self.state = if c == '"' {
+                            State::DoctypeSystemIdentifierDoubleQuoted
+                        } else {
+                            State::DoctypeSystemIdentifierSingleQuoted
+                        };
+                        continue;
+                    }
+
+                    self.set_force_quirks();
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingDoctypeSystemIdentifier);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.error(ParseErrorKind::MissingQuoteBeforeDoctypeSystemIdentifier);
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::DoctypeSystemIdentifierDoubleQuoted
+                | State::DoctypeSystemIdentifierSingleQuoted => {
+                    let quote = if self.state == State::DoctypeSystemIdentifierDoubleQuoted {
+                        '"'
+                    } else {
+                        '\''
+                    };
+                    if c == quote {
+                        self.state = State::AfterDoctypeSystemIdentifier;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.error(ParseErrorKind::AbruptDoctypeSystemIdentifier);
+                        self.set_force_quirks();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_field(DoctypeField::SystemId, c);
+                }
+
+                State::AfterDoctypeSystemIdentifier => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    // Unlike the other errors in a DOCTYPE, trailing garbage does not force quirks.
+                    self.error(ParseErrorKind::UnexpectedCharacterAfterDoctypeSystemIdentifier);
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+
+                State::BogusDoctype => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+                }
+
+                State::CdataSection => {
+                    if c == ']' {
+                        self.state = State::CdataSectionBracket;
+                        continue;
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+
+                State::CdataSectionBracket => {
+                    if c == ']' {
+                        self.state = State::CdataSectionEnd;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                    return Some(HtmlToken::Char(']'));
+                }
+
+                State::CdataSectionEnd => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    if c == ']' {
+                        return Some(HtmlToken::Char(']'));
+                    }
+
+                    // One of the pending brackets is emitted here, the other by the bracket state
+                    // before it reconsumes this character.
+                    self.reconsume = true;
+                    self.state = State::CdataSectionBracket;
+                    return Some(HtmlToken::Char(']'));
+                }
+
+                State::CharacterReference => {
+                    self.buf = String::from("&");
+                    if c.is_ascii_alphanumeric() {
+                        self.reconsume = true;
+                        self.state = State::NamedCharacterReference;
+                        continue;
+                    }
+
+                    if c == '#' {
+                        self.buf.push(c);
+                        self.state = State::NumericCharacterReference;
+                        continue;
+                    }
+
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+
+                State::NamedCharacterReference => {
+                    if self.needs_more_input_for_name() {
+                        self.reconsume = true;
+                        return None;
+                    }
+                    match self.consume_named_character_reference() {
+                        Some((name, value)) => {
+                            self.buf.push_str(&name);
+                            // For historical reasons, a reference without its semicolon inside
+                            // an attribute value is kept as written when more of a name or an
+                            // `=` follows, so that query strings such as `?a=1&copy=2` survive.
+                            let next = self.input.get(self.pos).copied();
+                            let keep_as_written = self.consumed_as_part_of_attribute()
+                                && !name.ends_with(';')
+                                && next.is_some_and(|n| n == '=' || n.is_ascii_alphanumeric());
+                            if !keep_as_written {
+                                if !name.ends_with(';') {
+                                    // Reported at the character after the name.
+                                    self.current = self.pos;
+                                    self.error(
+                                        ParseErrorKind::MissingSemicolonAfterCharacterReference,
+                                    );
+                                }
+                                self.buf = String::from(value);
+                            }
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.state = self.return_state.clone();
+                        }
+                        None => {
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.reconsume = true;
+                            self.state = State::AmbiguousAmpersand;
+                        }
+                    }
+                }
+
+                State::AmbiguousAmpersand => {
+                    if c.is_ascii_alphanumeric() {
+                        if self.consumed_as_part_of_attribute() {
+                            self.append_attribute(c, /*is_name*/ false);
+                            continue;
+                        }
+                        return Some(HtmlToken::Char(c));
+                    }
+
+                    if c == ';' {
+                        self.error(ParseErrorKind::UnknownNamedCharacterReference);
+                    }
+
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+
+                State::NumericCharacterReference => {
+                    self.character_reference_code = 0;
+                    if c == 'x' || c == 'X' {
+                        self.buf.push(c);
+                        self.state = State::HexadecimalCharacterReferenceStart;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::DecimalCharacterReferenceStart;
+                }
+
+                State::HexadecimalCharacterReferenceStart
+                | State::DecimalCharacterReferenceStart => {
+                    let hexadecimal = self.state == State::HexadecimalCharacterReferenceStart;
+                    if (hexadecimal && c.is_ascii_hexdigit()) || c.is_ascii_digit() {
+                        self.reconsume = true;
+                        self.state = if hexadecimal {
+                            State::HexadecimalCharacterReference
+                        } else {
+                            State::DecimalCharacterReference
+                        };
+                        continue;
+                    }
+
+                    // `&#` or `&#x` without any digits is not a reference at all.
+                    self.error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+
+                State::HexadecimalCharacterReference | State::DecimalCharacterReference => {
+                    let radix = if self.state == State::HexadecimalCharacterReference {
+                        16
+                    } else {
+                        10
+                    };
+                    if let Some(digit) = c.to_digit(radix) {
+                        // Anything past the last code point is out of range all the same.
+                        self.character_reference_code =
+                            core::cmp::min(self.character_reference_code * radix + digit, 0x110000);
+                        continue;
+                    }
+
+                    if c != ';' {
+                        self.error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+                        self.reconsume = true;
+                    }
+                    self.finish_numeric_character_reference();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_empty() {
+        let html = "".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        assert!(tokenizer.next().is_none())
+    }
+
+    #[test]
+    fn test_start_and_end_tag() {
+        let html = "<body></body>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
         let expected = [
             HtmlToken::StartTag {
                 tag: "body".to_string(),
@@ -596,4 +1883,423 @@ mod tests {
             assert_eq!(tokenizer.next(), Some(e));
         }
     }
+
+    fn tokens(html: &str) -> Vec<HtmlToken> {
+        HtmlTokenizer::new(html.to_string()).collect()
+    }
+
+    fn doctype(
+        name: Option<&str>,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+        force_quirks: bool,
+    ) -> HtmlToken {
+        HtmlToken::Doctype {
+            name: name.map(|s| s.to_string()),
+            public_id: public_id.map(|s| s.to_string()),
+            system_id: system_id.map(|s| s.to_string()),
+            force_quirks,
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            tokens("<!-- a -- b --><!----><!--->x"),
+            vec![
+                HtmlToken::Comment(" a -- b ".to_string()),
+                HtmlToken::Comment("".to_string()),
+                HtmlToken::Comment("".to_string()),
+                HtmlToken::Char('x'),
+            ]
+        );
+        assert_eq!(
+            tokens("<!--a<!--b--!><!--c--->"),
+            vec![
+                HtmlToken::Comment("a<!--b".to_string()),
+                HtmlToken::Comment("c-".to_string()),
+            ]
+        );
+        // Anything else after `<!` and `</` closed by `>` is a bogus comment.
+        assert_eq!(
+            tokens("<!ELEMENT br EMPTY></ x></><![CDATA[x]]>"),
+            vec![
+                HtmlToken::Comment("ELEMENT br EMPTY".to_string()),
+                HtmlToken::Comment(" x".to_string()),
+                HtmlToken::Comment("[CDATA[x]]".to_string()),
+            ]
+        );
+        // An unterminated comment runs to the end of the input.
+        assert_eq!(
+            tokens("<!-- open"),
+            vec![HtmlToken::Comment(" open".to_string())]
+        );
+        assert_eq!(tokens("<!"), vec![HtmlToken::Comment("".to_string())]);
+    }
+
+    #[test]
+    fn test_doctype() {
+        assert_eq!(
+            tokens("<!DOCTYPE html>"),
+            vec![doctype(Some("html"), None, None, false)]
+        );
+        assert_eq!(
+            tokens("<!doctype HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\"\n \"http://www.w3.org/TR/html4/strict.dtd\">"),
+            vec![doctype(
+                Some("html"),
+                Some("-//W3C//DTD HTML 4.01//EN"),
+                Some("http://www.w3.org/TR/html4/strict.dtd"),
+                false
+            )]
+        );
+        assert_eq!(
+            tokens("<!DOCTYPE html SYSTEM 'about:legacy-compat'>"),
+            vec![doctype(
+                Some("html"),
+                None,
+                Some("about:legacy-compat"),
+                false
+            )]
+        );
+        assert_eq!(tokens("<!DOCTYPE>"), vec![doctype(None, None, None, true)]);
+        assert_eq!(
+            tokens("<!DOCTYPE html bogus>"),
+            vec![doctype(Some("html"), None, None, true)]
+        );
+        assert_eq!(
+            tokens("<!DOCTYPE html PUBLIC \"unterminated>x"),
+            vec![
+                doctype(Some("html"), Some("unterminated"), None, true),
+                HtmlToken::Char('x'),
+            ]
+        );
+        assert_eq!(
+            tokens("<!DOCTYPE html"),
+            vec![doctype(Some("html"), None, None, true)]
+        );
+    }
+
+    #[test]
+    fn test_cdata_section() {
+        let mut tokenizer = HtmlTokenizer::new("<![CDATA[a]b]]c]]]>".to_string());
+        tokenizer.set_cdata_allowed(true);
+        let chars: String = tokenizer
+            .filter_map(|t| match t {
+                HtmlToken::Char(c) => Some(c),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chars, "a]b]]c]");
+    }
+
+    fn text(html: &str) -> String {
+        tokens(html)
+            .into_iter()
+            .filter_map(|t| match t {
+                HtmlToken::Char(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn attribute_value(html: &str) -> String {
+        match tokens(html).into_iter().next() {
+            Some(HtmlToken::StartTag { attributes, .. }) => attributes[0].value(),
+            t => panic!("expected a start tag but got {:?}", t),
+        }
+    }
+
+    #[test]
+    fn test_character_references() {
+        assert_eq!(text("a &amp; b &lt;p&gt;"), "a & b <p>");
+        assert_eq!(
+            text("&nbsp;&copy&NotEqualTilde;"),
+            "\u{a0}\u{a9}\u{2242}\u{338}"
+        );
+        // The longest name wins, and legacy names also match without their semicolon.
+        assert_eq!(
+            text("&notin; &notit; &amp;amp;"),
+            "\u{2209} \u{ac}it; &amp;"
+        );
+        // Unknown names and bare ampersands are left alone.
+        assert_eq!(text("&bogus; & &;"), "&bogus; & &;");
+        assert_eq!(text("&#x3042;&#12354;&#X41&#65x"), "ああAAx");
+        assert_eq!(
+            text("&#0;&#x110000;&#xD800;&#x80;&#x81;"),
+            "\u{fffd}\u{fffd}\u{fffd}\u{20ac}\u{81}"
+        );
+        assert_eq!(text("&#;&#x;&#xg"), "&#;&#x;&#xg");
+        // References cut short by the end of the input.
+        assert_eq!(text("x&"), "x&");
+        assert_eq!(text("&#"), "&#");
+        assert_eq!(text("&#x41"), "A");
+    }
+
+    #[test]
+    fn test_character_references_in_attributes() {
+        assert_eq!(
+            attribute_value("<a title=\"Tom &amp; Jerry\">"),
+            "Tom & Jerry"
+        );
+        assert_eq!(attribute_value("<a title='&lt;&#62;'>"), "<>");
+        assert_eq!(attribute_value("<a title=&quot;x&quot;>"), "\"x\"");
+        // Legacy references followed by `=` or alphanumerics stay as written in attributes.
+        assert_eq!(
+            attribute_value("<a href=\"?a=1&copy=2&not;&notx\">"),
+            "?a=1&copy=2\u{ac}&notx"
+        );
+        assert_eq!(attribute_value("<a href=\"?x&copy;=1\">"), "?x\u{a9}=1");
+    }
+
+    // Tokenizes `html`, switching into `state` after the first (start tag) token the way the tree
+    // builder does, and returns the text before the next tag together with that tag.
+    fn text_element(html: &str, state: State) -> (String, Option<HtmlToken>) {
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        tokenizer.next();
+        tokenizer.switch_to(state);
+        let mut text = String::new();
+        for token in tokenizer {
+            match token {
+                HtmlToken::Char(c) => text.push(c),
+                HtmlToken::Eof => break,
+                t => return (text, Some(t)),
+            }
+        }
+        (text, None)
+    }
+
+    #[test]
+    fn test_rawtext_and_rcdata() {
+        let end = |tag: &str| {
+            Some(HtmlToken::EndTag {
+                tag: tag.to_string(),
+            })
+        };
+        assert_eq!(
+            text_element("<style>a > b { x: '<p>' }</style>", State::Rawtext),
+            ("a > b { x: '<p>' }".to_string(), end("style"))
+        );
+        // Only the end tag of the element itself ends the text, in any case and with attributes.
+        assert_eq!(
+            text_element("<xmp></p></xmpx></XMP a=\"b\">", State::Rawtext),
+            ("</p></xmpx>".to_string(), end("xmp"))
+        );
+        assert_eq!(
+            text_element(
+                "<script>if (a<b) x = '</div>';</script >",
+                State::ScriptData
+            ),
+            ("if (a<b) x = '</div>';".to_string(), end("script"))
+        );
+        // RCDATA decodes character references, RAWTEXT does not.
+        assert_eq!(
+            text_element("<title>&lt;b&gt; &amp</title>", State::Rcdata),
+            ("<b> &".to_string(), end("title"))
+        );
+        assert_eq!(
+            text_element("<noframes>&lt;</noframes>", State::Rawtext),
+            ("&lt;".to_string(), end("noframes"))
+        );
+        // Unterminated elements run to the end of the input.
+        assert_eq!(
+            text_element("<textarea>a</textare", State::Rcdata),
+            ("a</textare".to_string(), None)
+        );
+        assert_eq!(
+            text_element("<style>a<", State::Rawtext),
+            ("a<".to_string(), None)
+        );
+        assert_eq!(
+            text_element("<style>a</", State::Rawtext),
+            ("a</".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn test_plaintext() {
+        assert_eq!(
+            text_element("<plaintext>a</plaintext><b>&amp;", State::Plaintext),
+            ("a</plaintext><b>&amp;".to_string(), None)
+        );
+    }
+
+    fn error_names(html: &str) -> Vec<(&'static str, usize, usize)> {
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        for _ in tokenizer.by_ref() {}
+        tokenizer
+            .errors()
+            .iter()
+            .map(|e| (e.kind().name(), e.position().line(), e.position().column()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(error_names("<p>ok</p>&amp;"), vec![]);
+        assert_eq!(
+            error_names("a\0b\n</p x=1 />"),
+            vec![
+                ("unexpected-null-character", 1, 2),
+                ("end-tag-with-trailing-solidus", 2, 10),
+                ("end-tag-with-attributes", 2, 10),
+            ]
+        );
+        assert_eq!(
+            error_names("<a href=x href='y'title=z><?php ?></>"),
+            vec![
+                ("duplicate-attribute", 1, 15),
+                ("missing-whitespace-between-attributes", 1, 19),
+                ("unexpected-question-mark-instead-of-tag-name", 1, 28),
+                ("missing-end-tag-name", 1, 37),
+            ]
+        );
+        assert_eq!(
+            error_names("&copy &#0; &#x110000; &bogus; &#;"),
+            vec![
+                ("missing-semicolon-after-character-reference", 1, 6),
+                ("null-character-reference", 1, 10),
+                ("character-reference-outside-unicode-range", 1, 21),
+                ("unknown-named-character-reference", 1, 29),
+                ("absence-of-digits-in-numeric-character-reference", 1, 33),
+            ]
+        );
+        assert_eq!(
+            error_names("<!DOCTYPEhtml><!-->x<!-- <!-- a --!><!x"),
+            vec![
+                ("missing-whitespace-before-doctype-name", 1, 10),
+                ("abrupt-closing-of-empty-comment", 1, 19),
+                ("nested-comment", 1, 29),
+                ("incorrectly-closed-comment", 1, 36),
+                ("incorrectly-opened-comment", 1, 39),
+            ]
+        );
+        assert_eq!(error_names("<p class"), vec![("eof-in-tag", 1, 9)]);
+        assert_eq!(error_names("<!-- x"), vec![("eof-in-comment", 1, 7)]);
+    }
+
+    #[test]
+    fn test_invalid_tag_openings() {
+        // A `<` that does not open a tag is text, and an unfinished tag is dropped.
+        assert_eq!(
+            tokens("a < b<"),
+            vec![
+                HtmlToken::Char('a'),
+                HtmlToken::Char(' '),
+                HtmlToken::Char('<'),
+                HtmlToken::Char(' '),
+                HtmlToken::Char('b'),
+                HtmlToken::Char('<'),
+            ]
+        );
+        assert_eq!(tokens("x<p a"), vec![HtmlToken::Char('x'), HtmlToken::Eof]);
+        // Tabs and newlines separate attributes like spaces do.
+        let attributes = match tokens("<a\thref=x\ntitle=y>").remove(0) {
+            HtmlToken::StartTag { attributes, .. } => attributes,
+            t => panic!("expected a start tag but got {:?}", t),
+        };
+        let names: Vec<String> = attributes.iter().map(|a| a.name()).collect();
+        assert_eq!(names, vec!["href".to_string(), "title".to_string()]);
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let mut tokenizer = HtmlTokenizer::new("<p>a\n  <!--c-->&amp;</p>".to_string());
+        let mut positions = Vec::new();
+        while let Some(token) = tokenizer.next_positioned() {
+            let position = token.position();
+            positions.push((token.token(), position.line(), position.column()));
+        }
+        let position_of = |token: &HtmlToken| {
+            positions
+                .iter()
+                .find(|(t, _, _)| t == token)
+                .map(|(_, line, column)| (*line, *column))
+        };
+        let p = HtmlToken::StartTag {
+            tag: "p".to_string(),
+            self_closing: false,
+            attributes: Vec::new(),
+        };
+        assert_eq!(position_of(&p), Some((1, 1)));
+        assert_eq!(position_of(&HtmlToken::Char('a')), Some((1, 4)));
+        assert_eq!(
+            position_of(&HtmlToken::Comment("c".to_string())),
+            Some((2, 3))
+        );
+        assert_eq!(position_of(&HtmlToken::Char('&')), Some((2, 11)));
+        assert_eq!(
+            position_of(&HtmlToken::EndTag {
+                tag: "p".to_string()
+            }),
+            Some((2, 16))
+        );
+    }
+
+    fn streamed(chunks: &[String]) -> (Vec<PositionedToken>, Vec<ParseError>) {
+        let mut tokenizer = HtmlTokenizer::streaming();
+        let mut tokens = Vec::new();
+        for chunk in chunks {
+            tokenizer.feed(chunk);
+            while let Some(token) = tokenizer.next_positioned() {
+                tokens.push(token);
+            }
+        }
+        tokenizer.finish();
+        while let Some(token) = tokenizer.next_positioned() {
+            tokens.push(token);
+        }
+        (tokens, tokenizer.errors())
+    }
+
+    #[test]
+    fn test_streaming() {
+        let inputs = [
+            "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\">\n<p class=a id='b'>x</p>",
+            "<!-- a <!-- b --><!--->&notin; &not &copy=<a href=\"?a=1&copy=2&amp;\">",
+            "<title>a&lt;</tit</title><![CDATA[x]]><?pi>&#x41;&#66 </p a=1>",
+            "<p>a\n  <!-- c",
+            "<div a=1 a=2>&unknown;</div x>&",
+        ];
+        for html in inputs {
+            let mut tokenizer = HtmlTokenizer::new(html.to_string());
+            let mut expected = Vec::new();
+            while let Some(token) = tokenizer.next_positioned() {
+                expected.push(token);
+            }
+            let expected_errors = tokenizer.errors();
+            for at in 0..=html.chars().count() {
+                let head = html.chars().take(at).collect();
+                let tail = html.chars().skip(at).collect();
+                let (tokens, errors) = streamed(&[head, tail]);
+                assert_eq!(tokens, expected, "{:?} split at {}", html, at);
+                assert_eq!(errors, expected_errors, "{:?} split at {}", html, at);
+            }
+            let chars: Vec<String> = html.chars().map(String::from).collect();
+            assert_eq!(streamed(&chars), (expected, expected_errors), "{:?}", html);
+        }
+    }
+
+    #[test]
+    fn test_streaming_waits_for_input() {
+        let mut tokenizer = HtmlTokenizer::streaming();
+        tokenizer.feed("a<!-");
+        assert_eq!(tokenizer.next(), Some(HtmlToken::Char('a')));
+        // Whether this is a comment is only known from the next piece.
+        assert_eq!(tokenizer.next(), None);
+        tokenizer.feed("-b-->");
+        assert_eq!(tokenizer.next(), Some(HtmlToken::Comment("b".to_string())));
+        tokenizer.feed("<p");
+        assert_eq!(tokenizer.next(), None);
+        tokenizer.finish();
+        assert_eq!(tokenizer.next(), Some(HtmlToken::Eof));
+        assert_eq!(tokenizer.next(), None);
+        assert_eq!(
+            tokenizer
+                .errors()
+                .iter()
+                .map(|e| e.kind())
+                .collect::<Vec<_>>(),
+            vec![ParseErrorKind::EofInTag]
+        );
+    }
 }
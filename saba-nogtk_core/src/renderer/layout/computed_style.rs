@@ -1,4 +1,4 @@
-use core::cell::RefCell;
+use core::{cell::RefCell, str::FromStr};
 
 use alloc::{
     format,
@@ -18,6 +18,7 @@ pub struct ComputedStyle {
     display: Option<DisplayType>,
     font_size: Option<FontSize>,
     text_decoration: Option<TextDecoration>,
+    white_space: Option<WhiteSpace>,
     height: Option<f64>,
     width: Option<f64>,
 }
@@ -30,6 +31,7 @@ impl ComputedStyle {
             display: None,
             font_size: None,
             text_decoration: None,
+            white_space: None,
             height: None,
             width: None,
         }
@@ -70,6 +72,14 @@ impl ComputedStyle {
             .expect("failed to access text_decoration")
     }
 
+    pub fn set_white_space(&mut self, white_space: WhiteSpace) {
+        self.white_space = Some(white_space);
+    }
+
+    pub fn white_space(&self) -> WhiteSpace {
+        self.white_space.expect("failed to access white_space")
+    }
+
     pub fn set_height(&mut self, height: f64) {
         self.height = Some(height);
     }
@@ -103,6 +113,9 @@ impl ComputedStyle {
             {
                 self.text_decoration = Some(parent_style.text_decoration());
             }
+            if self.white_space.is_none() && parent_style.white_space() != WhiteSpace::Normal {
+                self.white_space = Some(parent_style.white_space());
+            }
         }
 
         if self.background_color.is_none() {
@@ -120,6 +133,9 @@ impl ComputedStyle {
         if self.text_decoration.is_none() {
             self.text_decoration = Some(TextDecoration::default(node));
         }
+        if self.white_space.is_none() {
+            self.white_space = Some(WhiteSpace::default(node));
+        }
         if self.height.is_none() {
             self.height = Some(0.0);
         }
@@ -261,6 +277,20 @@ impl DisplayType {
         match &node.borrow().kind() {
             NodeKind::Document => Self::Block,
             NodeKind::Element(element) => {
+                // These never render, wherever they appear in the document. Nested browsing
+                // contexts are not supported, so an iframe renders nothing either.
+                if matches!(
+                    element.kind(),
+                    ElementKind::Link
+                        | ElementKind::Title
+                        | ElementKind::Script
+                        | ElementKind::Style
+                        | ElementKind::Noframes
+                        | ElementKind::Iframe
+                        | ElementKind::Noembed
+                ) {
+                    return Self::DisplayNone;
+                }
                 if element.is_block_element() {
                     Self::Block
                 } else {
@@ -268,6 +298,7 @@ impl DisplayType {
                 }
             }
             NodeKind::Text(_) => Self::Inline,
+            NodeKind::Comment(_) => Self::DisplayNone,
         }
     }
 
@@ -301,3 +332,39 @@ impl TextDecoration {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WhiteSpace {
+    Normal,
+    Pre,
+}
+
+impl WhiteSpace {
+    fn default(node: &Rc<RefCell<Node>>) -> Self {
+        match &node.borrow().kind() {
+            NodeKind::Element(element) => match element.kind() {
+                ElementKind::Pre
+                | ElementKind::Xmp
+                | ElementKind::Plaintext
+                | ElementKind::Textarea => Self::Pre,
+                _ => Self::Normal,
+            },
+            _ => Self::Normal,
+        }
+    }
+}
+
+impl FromStr for WhiteSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Self::Normal),
+            "pre" => Ok(Self::Pre),
+            _ => Err(Error::UnexpectedInput(format!(
+                "white-space {:?} is not supported yet",
+                s
+            ))),
+        }
+    }
+}
@@ -14,11 +14,11 @@ use crate::{
     display_item::DisplayItem,
     renderer::{
         css::cssom::{ComponentValue, Declaration, Selector, StyleSheet},
-        dom::node::{Node, NodeKind},
+        dom::node::{ElementKind, Node, NodeKind},
     },
 };
 
-use super::computed_style::{Color, ComputedStyle, DisplayType, FontSize};
+use super::computed_style::{Color, ComputedStyle, DisplayType, FontSize, WhiteSpace};
 
 #[derive(Debug, Clone)]
 pub struct LayoutObject {
@@ -88,6 +88,27 @@ impl LayoutObject {
         self.size
     }
 
+    fn is_image(&self) -> bool {
+        self.node.borrow().element_kind() == Some(ElementKind::Img)
+    }
+
+    // An image takes the size its width and height attributes give it, as the pixels
+    // themselves are not decoded.
+    fn image_size(&self) -> LayoutSize {
+        let element = self.node.borrow().get_element();
+        let dimension = |name: &str| {
+            element
+                .as_ref()
+                .and_then(|e| e.get_attribute(name))
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .unwrap_or(0)
+        };
+        LayoutSize::new(
+            core::cmp::min(dimension("width"), CONTENT_AREA_WIDTH),
+            dimension("height"),
+        )
+    }
+
     pub fn is_node_selected(&self, selector: &Selector) -> bool {
         match &self.node_kind() {
             NodeKind::Element(e) => match selector {
@@ -149,6 +170,13 @@ impl LayoutObject {
                         self.style.set_color(color);
                     }
                 }
+                "white-space" => {
+                    if let ComponentValue::Ident(value) = &declaration.value {
+                        if let Ok(white_space) = value.parse::<WhiteSpace>() {
+                            self.style.set_white_space(white_space);
+                        }
+                    }
+                }
                 "display" => {
                     if let ComponentValue::Ident(value) = declaration.value {
                         let display_type = match DisplayType::from_str(&value) {
@@ -174,6 +202,7 @@ impl LayoutObject {
     pub fn update_kind(&mut self) {
         match self.node_kind() {
             NodeKind::Document => panic!("should not create a layout object for a document node"),
+            NodeKind::Comment(_) => panic!("should not create a layout object for a comment node"),
             NodeKind::Element(_) => {
                 let display = self.style.display();
                 match display {
@@ -217,6 +246,9 @@ impl LayoutObject {
                 }
                 size.set_height(height);
             }
+            LayoutObjectKind::Inline if self.is_image() => {
+                self.size = self.image_size();
+            }
             LayoutObjectKind::Inline => {
                 let mut width = 0;
                 let mut height = 0;
@@ -243,6 +275,14 @@ impl LayoutObject {
                         FontSize::XLarge => 2,
                         FontSize::XXLarge => 3,
                     };
+                    if self.style.white_space() == WhiteSpace::Pre {
+                        let lines = preformatted_lines(&t, CHAR_WIDTH * ratio);
+                        let longest = lines.iter().map(|l| l.chars().count()).max();
+                        size.set_width(CHAR_WIDTH * ratio * longest.unwrap_or(0) as i64);
+                        size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio * lines.len() as i64);
+                        self.size = size;
+                        return;
+                    }
                     let width = CHAR_WIDTH * ratio * t.len() as i64;
                     if width > CONTENT_AREA_WIDTH {
                         size.set_width(CONTENT_AREA_WIDTH);
@@ -314,7 +354,21 @@ impl LayoutObject {
                 }
             }
             LayoutObjectKind::Inline => {
-                todo!()
+                if self.is_image() {
+                    let src = self
+                        .node
+                        .borrow()
+                        .get_element()
+                        .and_then(|e| e.get_attribute("src"))
+                        .unwrap_or_default();
+                    return vec![DisplayItem::Image {
+                        src,
+                        style: self.style(),
+                        layout_point: self.point(),
+                        layout_size: self.size(),
+                    }];
+                }
+                // An inline box draws nothing itself; its text children are painted separately.
             }
             LayoutObjectKind::Text => {
                 if let NodeKind::Text(t) = self.node_kind() {
@@ -324,6 +378,21 @@ impl LayoutObject {
                         FontSize::XLarge => 2,
                         FontSize::XXLarge => 3,
                     };
+                    if self.style.white_space() == WhiteSpace::Pre {
+                        let line_height = CHAR_HEIGHT_WITH_PADDING * ratio;
+                        return preformatted_lines(&t, CHAR_WIDTH * ratio)
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, line)| DisplayItem::Text {
+                                text: line,
+                                style: self.style(),
+                                layout_point: LayoutPoint::new(
+                                    self.point().x(),
+                                    self.point().y() + line_height * i as i64,
+                                ),
+                            })
+                            .collect();
+                    }
                     let plain_text = t
                         .replace("\n", " ")
                         .split(' ')
@@ -477,3 +546,33 @@ fn find_index_for_line_break(line: String, max_index: usize) -> usize {
     }
     max_index
 }
+
+// Splits preformatted text at its line breaks, expanding tabs and hard-wrapping lines that do not
+// fit the content area. Spaces are kept as they are.
+fn preformatted_lines(text: &str, char_width: i64) -> Vec<String> {
+    let max_chars = core::cmp::max(CONTENT_AREA_WIDTH / char_width, 1) as usize;
+    let mut lines = Vec::new();
+    for raw_line in text.split('\n') {
+        let mut line = String::new();
+        for c in raw_line.trim_end_matches('\r').chars() {
+            if c == '\t' {
+                let width = 8 - line.chars().count() % 8;
+                line.extend(core::iter::repeat(' ').take(width));
+            } else {
+                line.push(c);
+            }
+        }
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        for chunk in chars.chunks(max_chars) {
+            lines.push(chunk.iter().collect());
+        }
+    }
+    // A final line break does not start another line.
+    if text.ends_with('\n') {
+        lines.pop();
+    }
+    lines
+}
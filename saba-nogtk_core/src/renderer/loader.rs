@@ -0,0 +1,413 @@
+use core::cell::RefCell;
+
+use alloc::{
+    format,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    encoding::Encoding,
+    error::Error,
+    http::{HttpRequest, HttpResponse, Method},
+    url::Url,
+};
+
+use super::dom::{
+    api::get_elements,
+    node::{ElementKind, Node},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResourceKind {
+    Stylesheet,
+    Image,
+    Script,
+}
+
+impl ResourceKind {
+    fn accept(&self) -> &'static str {
+        match self {
+            ResourceKind::Stylesheet => "text/css,*/*;q=0.1",
+            ResourceKind::Image => "image/*,*/*;q=0.8",
+            ResourceKind::Script => "*/*",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceState {
+    Pending,
+    Loaded,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Resource {
+    kind: ResourceKind,
+    url: Url,
+    state: ResourceState,
+    response: Option<HttpResponse>,
+}
+
+impl Resource {
+    fn new(kind: ResourceKind, url: Url) -> Self {
+        Self {
+            kind,
+            url,
+            state: ResourceState::Pending,
+            response: None,
+        }
+    }
+
+    pub fn kind(&self) -> ResourceKind {
+        self.kind
+    }
+
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    pub fn state(&self) -> ResourceState {
+        self.state.clone()
+    }
+
+    pub fn response(&self) -> Option<HttpResponse> {
+        self.response.clone()
+    }
+
+    pub fn request(&self) -> Result<HttpRequest, Error> {
+        let mut request = HttpRequest::new(Method::Get, self.url.clone());
+        request.add_header("Accept", self.kind.accept())?;
+        Ok(request)
+    }
+}
+
+fn is_stylesheet_link(node: &Node) -> bool {
+    let rel = match node.get_element().and_then(|e| e.get_attribute("rel")) {
+        Some(rel) => rel.to_ascii_lowercase(),
+        None => return false,
+    };
+    let mut tokens = rel.split_ascii_whitespace();
+    // Alternate stylesheets are only applied when the user picks them.
+    tokens.clone().any(|t| t == "stylesheet") && !tokens.any(|t| t == "alternate")
+}
+
+// The URL an element refers to, if it names a subresource.
+fn subresource_reference(node: &Node) -> Option<(ResourceKind, String)> {
+    let element = node.get_element()?;
+    let (kind, attribute) = match element.kind() {
+        ElementKind::Link if is_stylesheet_link(node) => (ResourceKind::Stylesheet, "href"),
+        ElementKind::Img => (ResourceKind::Image, "src"),
+        ElementKind::Script => (ResourceKind::Script, "src"),
+        _ => return None,
+    };
+    let reference = element.get_attribute(attribute)?;
+    let reference = reference.trim();
+    if reference.is_empty() {
+        return None;
+    }
+    Some((kind, reference.to_string()))
+}
+
+// Decodes a stylesheet from its BOM, then the Content-Type charset, then the document's encoding.
+fn decode_stylesheet(response: &HttpResponse, document_encoding: Option<Encoding>) -> String {
+    let body = response.body();
+    let encoding = Encoding::from_bom(&body)
+        .or_else(|| {
+            response
+                .content_type()
+                .and_then(|c| c.charset())
+                .and_then(|charset| Encoding::for_label(&charset))
+        })
+        .or(document_encoding)
+        .unwrap_or(Encoding::Utf8);
+    encoding.decode(&body)
+}
+
+// Tracks the stylesheets, images and scripts a document refers to.
+#[derive(Debug, Clone)]
+pub struct ResourceLoader {
+    resources: Vec<Resource>,
+}
+
+impl ResourceLoader {
+    pub fn new() -> Self {
+        Self {
+            resources: Vec::new(),
+        }
+    }
+
+    pub fn resources(&self) -> Vec<Resource> {
+        self.resources.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.resources.clear();
+    }
+
+    pub fn resource(&self, url: &Url) -> Option<Resource> {
+        self.resources.iter().find(|r| r.url == *url).cloned()
+    }
+
+    pub fn pending(&self) -> Vec<Resource> {
+        self.resources
+            .iter()
+            .filter(|r| r.state == ResourceState::Pending)
+            .cloned()
+            .collect()
+    }
+
+    // The first resource still waiting to be fetched. A pending resource has no response yet,
+    // so copying it out is cheap.
+    pub fn next_pending(&self) -> Option<Resource> {
+        self.resources
+            .iter()
+            .find(|r| r.state == ResourceState::Pending)
+            .cloned()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.resources
+            .iter()
+            .all(|r| r.state != ResourceState::Pending)
+    }
+
+    // Registers every subresource in the document that is not known yet and returns how many
+    // were added. References that do not resolve against `base_url` are skipped.
+    pub fn discover(&mut self, document: Rc<RefCell<Node>>, base_url: &Url) -> usize {
+        let elements = get_elements(
+            document,
+            &[ElementKind::Link, ElementKind::Img, ElementKind::Script],
+        );
+        let mut added = 0;
+        for element in elements {
+            let Some((kind, reference)) = subresource_reference(&element.borrow()) else {
+                continue;
+            };
+            let Ok(url) = base_url.resolve(&reference) else {
+                continue;
+            };
+            if self
+                .resources
+                .iter()
+                .any(|r| r.url == url && r.kind == kind)
+            {
+                continue;
+            }
+            self.resources.push(Resource::new(kind, url));
+            added += 1;
+        }
+        added
+    }
+
+    // Records the outcome of fetching `url` and returns the kinds of the resources it completed.
+    pub fn complete(
+        &mut self,
+        url: &Url,
+        result: Result<HttpResponse, Error>,
+    ) -> Vec<ResourceKind> {
+        let mut kinds = Vec::new();
+        for resource in self
+            .resources
+            .iter_mut()
+            .filter(|r| r.url == *url && r.state == ResourceState::Pending)
+        {
+            resource.state = match &result {
+                Ok(response) if (200..300).contains(&response.status_code()) => {
+                    resource.response = Some(response.clone());
+                    ResourceState::Loaded
+                }
+                Ok(response) => ResourceState::Failed(format!(
+                    "the server responded with status {}",
+                    response.status_code()
+                )),
+                Err(e) => ResourceState::Failed(format!("{:?}", e)),
+            };
+            kinds.push(resource.kind);
+        }
+        kinds
+    }
+
+    // The text of the loaded stylesheet for `url`.
+    pub fn stylesheet_text(
+        &self,
+        url: &Url,
+        document_encoding: Option<Encoding>,
+    ) -> Option<String> {
+        let resource = self
+            .resources
+            .iter()
+            .find(|r| r.kind == ResourceKind::Stylesheet && r.url == *url)?;
+        let response = resource.response.as_ref()?;
+        Some(decode_stylesheet(response, document_encoding))
+    }
+
+    pub(crate) fn stylesheet_url(node: &Node, base_url: &Url) -> Option<Url> {
+        match subresource_reference(node) {
+            Some((ResourceKind::Stylesheet, reference)) => base_url.resolve(&reference).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ResourceLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::{parser::HtmlParser, token::HtmlTokenizer};
+    use alloc::vec;
+
+    fn document(html: &str) -> Rc<RefCell<Node>> {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let document = window.borrow().document();
+        document
+    }
+
+    fn url(s: &str) -> Url {
+        Url::new(s.to_string())
+            .parse()
+            .expect("failed to parse url")
+    }
+
+    fn response(raw: &str) -> HttpResponse {
+        HttpResponse::new(raw.as_bytes()).expect("failed to parse response")
+    }
+
+    #[test]
+    fn test_discover() {
+        let dom = document(
+            r#"<html><head>
+            <link rel="stylesheet" href="css/main.css">
+            <link rel="alternate stylesheet" href="css/alt.css">
+            <link rel="icon" href="favicon.ico">
+            <script src="/app.js"></script>
+            </head><body>
+            <img src="logo.png"><img src="logo.png"><img src="">
+            <script>inline()</script>
+            </body></html>"#,
+        );
+        let mut loader = ResourceLoader::new();
+        assert_eq!(
+            loader.discover(dom.clone(), &url("http://example.com/docs/index.html")),
+            3
+        );
+        let found: Vec<(ResourceKind, String)> = loader
+            .resources()
+            .iter()
+            .map(|r| (r.kind(), r.url().href()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    ResourceKind::Stylesheet,
+                    "http://example.com/docs/css/main.css".to_string()
+                ),
+                (
+                    ResourceKind::Script,
+                    "http://example.com/app.js".to_string()
+                ),
+                (
+                    ResourceKind::Image,
+                    "http://example.com/docs/logo.png".to_string()
+                ),
+            ]
+        );
+        assert!(loader
+            .resources()
+            .iter()
+            .all(|r| r.state() == ResourceState::Pending));
+
+        // Discovering again adds nothing new.
+        assert_eq!(
+            loader.discover(dom, &url("http://example.com/docs/index.html")),
+            0
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let dom = document(
+            r#"<link rel=stylesheet href="a.css"><body><img src="b.png"><script src="c.js"></script>"#,
+        );
+        let mut loader = ResourceLoader::new();
+        loader.discover(dom, &url("http://example.com/"));
+        assert!(!loader.is_complete());
+        assert_eq!(
+            loader.next_pending().map(|r| r.url().href()),
+            Some("http://example.com/a.css".to_string())
+        );
+
+        let kinds = loader.complete(
+            &url("http://example.com/a.css"),
+            Ok(response(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/css\r\n\r\np { color: red; }",
+            )),
+        );
+        assert_eq!(kinds, vec![ResourceKind::Stylesheet]);
+        loader.complete(
+            &url("http://example.com/b.png"),
+            Ok(response("HTTP/1.1 404 Not Found\r\n\r\n")),
+        );
+        loader.complete(
+            &url("http://example.com/c.js"),
+            Err(Error::Network("connection reset".to_string())),
+        );
+        assert!(loader.is_complete());
+        assert!(loader.pending().is_empty());
+        assert!(loader.next_pending().is_none());
+
+        let states: Vec<ResourceState> = loader.resources().iter().map(|r| r.state()).collect();
+        assert_eq!(states[0], ResourceState::Loaded);
+        assert_eq!(
+            states[1],
+            ResourceState::Failed("the server responded with status 404".to_string())
+        );
+        assert!(matches!(&states[2], ResourceState::Failed(m) if m.contains("connection reset")));
+        assert_eq!(
+            loader.stylesheet_text(&url("http://example.com/a.css"), None),
+            Some("p { color: red; }".to_string())
+        );
+
+        // A result for a resource that is no longer pending changes nothing.
+        assert!(loader
+            .complete(
+                &url("http://example.com/a.css"),
+                Err(Error::Other("late".to_string()))
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_stylesheet_encoding() {
+        let dom = document(r#"<link rel="stylesheet" href="s.css">"#);
+        let mut loader = ResourceLoader::new();
+        loader.discover(dom, &url("http://example.com/"));
+        let mut raw =
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/css; charset=Shift_JIS\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[b'/', b'*', 0x93, 0xfa, b'*', b'/']);
+        loader.complete(
+            &url("http://example.com/s.css"),
+            Ok(HttpResponse::new(&raw).unwrap()),
+        );
+        assert_eq!(
+            loader.stylesheet_text(&url("http://example.com/s.css"), Some(Encoding::EucJp)),
+            Some("/*日*/".to_string())
+        );
+    }
+}
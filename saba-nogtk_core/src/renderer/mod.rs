@@ -2,4 +2,5 @@ pub mod css;
 pub mod dom;
 pub mod html;
 pub mod layout;
+pub mod loader;
 pub mod page;
@@ -2,39 +2,197 @@ use core::cell::RefCell;
 
 use alloc::{
     rc::{Rc, Weak},
-    string::String,
+    string::{String, ToString},
     vec::Vec,
 };
 
-use crate::{browser::Browser, display_item::DisplayItem, http::HttpResponse};
+use crate::{
+    browser::Browser,
+    display_item::DisplayItem,
+    encoding::{decode_html, detect_encoding_early, detect_text_encoding, Encoding, StreamDecoder},
+    error::Error,
+    fetch::{Fetcher, ResponseSink},
+    http::{sanitize_filename, HttpRequest, HttpResponse},
+    image::image_dimensions,
+    mime::{is_image_type, is_xml_type, sniff_mime_type},
+    url::{percent_decode, Url, UrlError},
+};
 
 use super::{
     css::{
         cssom::{CssParser, StyleSheet},
         token::CssTokenizer,
     },
-    dom::{api::get_style_content, node::Window},
-    html::{parser::HtmlParser, token::HtmlTokenizer},
+    dom::{
+        api::{get_document_base_url, get_elements},
+        node::{ElementKind, NodeKind, Window},
+        synthetic::{download_document, image_document, text_document},
+    },
+    html::{diagnostics::ParseError, parser::HtmlParser, token::HtmlTokenizer},
     layout::layout_view::LayoutView,
+    loader::{Resource, ResourceKind, ResourceLoader},
 };
 
+// How a response is presented, decided from its computed MIME type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DocumentKind {
+    Html,
+    Text,
+    Image,
+    Download,
+}
+
+impl DocumentKind {
+    pub fn for_mime_type(mime_type: &str) -> Self {
+        match mime_type {
+            "text/html" | "application/xhtml+xml" => DocumentKind::Html,
+            "application/json" | "application/javascript" | "application/ecmascript" => {
+                DocumentKind::Text
+            }
+            _ if mime_type.starts_with("text/")
+                || mime_type.ends_with("+json")
+                || is_xml_type(mime_type) =>
+            {
+                DocumentKind::Text
+            }
+            _ if is_image_type(mime_type) => DocumentKind::Image,
+            _ => DocumentKind::Download,
+        }
+    }
+}
+
+// A response that cannot be displayed and is offered for saving instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    url: Option<Url>,
+    filename: String,
+    mime_type: String,
+    body: Vec<u8>,
+}
+
+impl Download {
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    pub fn filename(&self) -> String {
+        self.filename.clone()
+    }
+
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+}
+
+// The name to save a download under: the one Content-Disposition suggests, then the last
+// segment of the URL path.
+fn download_filename(response: &HttpResponse, url: Option<&Url>) -> String {
+    if let Some(filename) = response.filename() {
+        return filename;
+    }
+    url.and_then(|url| url.path_segments().pop())
+        .and_then(|segment| sanitize_filename(&String::from_utf8_lossy(&percent_decode(&segment))))
+        .unwrap_or_else(|| "download".to_string())
+}
+
+// The MIME type a response is handled as, from its Content-Type and the start of its body.
+fn computed_mime_type(response: &HttpResponse, body: &[u8]) -> String {
+    let no_sniff = response
+        .header_value("X-Content-Type-Options")
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("nosniff"));
+    sniff_mime_type(
+        response.header_value("Content-Type").as_deref(),
+        no_sniff,
+        body,
+    )
+}
+
+// How much of a streamed document is parsed between repaints. Each repaint styles and lays out
+// the whole document again.
+const REPAINT_INTERVAL: usize = 8 * 1024;
+
+// A response whose body is still arriving. An HTML document is parsed from the moment its
+// encoding is known; anything else waits for the whole body.
+#[derive(Debug, Clone)]
+struct IncomingResponse {
+    head: HttpResponse,
+    // The body received before parsing could start.
+    body: Vec<u8>,
+    received: usize,
+    // The bytes parsed since the last repaint.
+    unpainted: usize,
+    parser: Option<(HtmlParser, StreamDecoder)>,
+}
+
+impl IncomingResponse {
+    fn new(head: HttpResponse) -> Self {
+        Self {
+            head,
+            body: Vec::new(),
+            received: 0,
+            unpainted: 0,
+            parser: None,
+        }
+    }
+
+    // Starts parsing the body received so far, if it can be. Returns the MIME type and the
+    // encoding of the document when it does.
+    fn start_parsing(&mut self) -> Option<(String, Encoding)> {
+        // A response labelled as HTML is never sniffed as anything else, so the label is enough.
+        let mime_type = computed_mime_type(&self.head, &[]);
+        if DocumentKind::for_mime_type(&mime_type) != DocumentKind::Html {
+            return None;
+        }
+        let charset = self.head.content_type().and_then(|c| c.charset());
+        let encoding = detect_encoding_early(&self.body, charset.as_deref())?;
+
+        let mut decoder = StreamDecoder::new(encoding);
+        let mut parser = HtmlParser::new(HtmlTokenizer::streaming());
+        parser.feed(&decoder.decode(&core::mem::take(&mut self.body)));
+        self.parser = Some((parser, decoder));
+        Some((mime_type, encoding))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Page {
     browser: Weak<RefCell<Browser>>,
+    url: Option<Url>,
+    mime_type: Option<String>,
+    document_kind: Option<DocumentKind>,
+    download: Option<Download>,
+    encoding: Option<Encoding>,
+    from_cache: bool,
     frame: Option<Rc<RefCell<Window>>>,
+    resource_loader: ResourceLoader,
     style: Option<StyleSheet>,
     layout_view: Option<LayoutView>,
     display_items: Vec<DisplayItem>,
+    parse_errors: Vec<ParseError>,
+    incoming: Option<IncomingResponse>,
 }
 
 impl Page {
     pub fn new() -> Self {
         Self {
             browser: Weak::new(),
+            url: None,
+            mime_type: None,
+            document_kind: None,
+            download: None,
+            encoding: None,
+            from_cache: false,
             frame: None,
+            resource_loader: ResourceLoader::new(),
             style: None,
             layout_view: None,
             display_items: Vec::new(),
+            parse_errors: Vec::new(),
+            incoming: None,
         }
     }
 
@@ -42,21 +200,246 @@ impl Page {
         self.browser = browser;
     }
 
+    pub fn set_url(&mut self, url: Url) {
+        self.url = Some(url);
+    }
+
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    pub fn mime_type(&self) -> Option<String> {
+        self.mime_type.clone()
+    }
+
+    pub fn document_kind(&self) -> Option<DocumentKind> {
+        self.document_kind
+    }
+
+    pub fn download(&self) -> Option<Download> {
+        self.download.clone()
+    }
+
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    pub fn from_cache(&self) -> bool {
+        self.from_cache
+    }
+
+    // What the HTML parser found wrong with the current document.
+    pub fn parse_errors(&self) -> Vec<ParseError> {
+        self.parse_errors.clone()
+    }
+
+    pub fn base_url(&self) -> Option<Url> {
+        let document_url = self.url.as_ref()?;
+        match &self.frame {
+            Some(frame) => Some(get_document_base_url(
+                frame.borrow().document(),
+                document_url,
+            )),
+            None => Some(document_url.clone()),
+        }
+    }
+
+    pub fn resolve_url(&self, reference: &str) -> Result<Url, UrlError> {
+        match self.base_url() {
+            Some(base) => base.resolve(reference),
+            None => Url::new(reference.to_string()).parse(),
+        }
+    }
+
+    pub fn display_items(&self) -> Vec<DisplayItem> {
+        self.display_items.clone()
+    }
+
+    pub fn resources(&self) -> Vec<Resource> {
+        self.resource_loader.resources()
+    }
+
+    pub fn pending_resources(&self) -> Vec<Resource> {
+        self.resource_loader.pending()
+    }
+
+    // Loads the document and then every subresource it refers to. Subresources that fail to
+    // load are recorded as failed and do not fail the page. An HTML document is painted as it
+    // arrives when the fetcher can stream it.
+    pub fn load(&mut self, fetcher: &mut dyn Fetcher, request: &HttpRequest) -> Result<(), Error> {
+        self.incoming = None;
+        let response = match fetcher.fetch_streaming(request, self) {
+            Ok(response) => response,
+            Err(e) => {
+                // What was shown of a document cut off partway stays on the page.
+                self.incoming = None;
+                return Err(e);
+            }
+        };
+        if response.url().is_none() {
+            self.url = Some(request.url());
+        }
+        self.finish_response(response);
+        self.load_resources(fetcher);
+        Ok(())
+    }
+
+    pub fn load_resources(&mut self, fetcher: &mut dyn Fetcher) {
+        while let Some(resource) = self.resource_loader.next_pending() {
+            let result = resource.request().and_then(|mut request| {
+                if let Some(url) = self.url.clone() {
+                    request.set_initiator(url);
+                }
+                fetcher.fetch(&request)
+            });
+            self.receive_resource(&resource.url(), result);
+        }
+    }
+
+    // Records a fetched subresource and updates the rendering it affects.
+    pub fn receive_resource(&mut self, url: &Url, result: Result<HttpResponse, Error>) {
+        let kinds = self.resource_loader.complete(url, result);
+        if kinds.contains(&ResourceKind::Stylesheet) {
+            self.update_style();
+        }
+        // Scripts are not executed, so they do not change the rendering.
+        if kinds
+            .iter()
+            .any(|k| matches!(k, ResourceKind::Stylesheet | ResourceKind::Image))
+        {
+            self.set_layout_view();
+            self.paint_tree();
+        }
+    }
+
+    // Ends a response that was passed in through `ResponseSink`; `response` is the whole of it.
+    // Unless its body was parsed as it arrived, it is handled as by `receive_response`.
+    pub fn finish_response(&mut self, response: HttpResponse) {
+        let (mut parser, mut decoder) = match self.incoming.take() {
+            Some(IncomingResponse {
+                parser: Some(parser),
+                received,
+                ..
+            }) if received == response.body().len() => parser,
+            _ => return self.receive_response(response),
+        };
+        parser.feed(&decoder.finish());
+        parser.construct_tree();
+        if let Some(url) = response.url() {
+            self.url = Some(url);
+        }
+        self.from_cache = response.from_cache();
+        self.parse_errors = parser.errors();
+        self.refresh();
+    }
+
     pub fn receive_response(&mut self, response: HttpResponse) {
-        self.create_frame(response.body());
+        if let Some(url) = response.url() {
+            self.url = Some(url);
+        }
+        self.from_cache = response.from_cache();
+        let body = response.body();
+        let mime_type = computed_mime_type(&response, &body);
+        let document_kind = DocumentKind::for_mime_type(&mime_type);
+        let charset = response.content_type().and_then(|c| c.charset());
+        self.encoding = None;
+        self.download = None;
+        self.parse_errors = Vec::new();
+
+        match document_kind {
+            DocumentKind::Html => {
+                let (html, encoding) = decode_html(&body, charset.as_deref());
+                self.encoding = Some(encoding);
+                let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+                self.create_frame(parser.construct_tree());
+                self.parse_errors = parser.errors();
+            }
+            DocumentKind::Text => {
+                let encoding = detect_text_encoding(&body, charset.as_deref());
+                self.encoding = Some(encoding);
+                self.create_frame(text_document(&encoding.decode(&body)));
+            }
+            DocumentKind::Image => {
+                let src = self.url.as_ref().map(|url| url.href()).unwrap_or_default();
+                self.create_frame(image_document(&src, image_dimensions(&body)));
+                // The image is the response itself, so it never needs to be fetched again.
+                if let Some(url) = self.url.clone() {
+                    self.resource_loader.complete(&url, Ok(response));
+                }
+            }
+            DocumentKind::Download => {
+                let filename = download_filename(&response, self.url.as_ref());
+                let href = self.url.as_ref().map(|url| url.href()).unwrap_or_default();
+                self.create_frame(download_document(&href, &filename, &mime_type, body.len()));
+                self.download = Some(Download {
+                    url: self.url.clone(),
+                    filename,
+                    mime_type: mime_type.clone(),
+                    body,
+                });
+            }
+        }
+        self.mime_type = Some(mime_type);
+        self.document_kind = Some(document_kind);
         self.set_layout_view();
         self.paint_tree();
     }
 
-    fn create_frame(&mut self, html: String) {
-        let html_tokenizer = HtmlTokenizer::new(html);
-        let frame = HtmlParser::new(html_tokenizer).construct_tree();
+    fn create_frame(&mut self, frame: Rc<RefCell<Window>>) {
         let dom = frame.borrow().document();
-        let style = get_style_content(dom);
-        let css_tokenizer = CssTokenizer::new(style);
-        let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
         self.frame = Some(frame);
-        self.style = Some(cssom);
+
+        self.resource_loader = ResourceLoader::new();
+        if let Some(base_url) = self.base_url() {
+            self.resource_loader.discover(dom, &base_url);
+        }
+        self.update_style();
+    }
+
+    // Builds the style sheet from the inline styles and the loaded linked stylesheets, in the
+    // order they appear in the document.
+    fn update_style(&mut self) {
+        let dom = match &self.frame {
+            Some(frame) => frame.borrow().document(),
+            None => return,
+        };
+        let base_url = self.base_url();
+
+        let mut style = String::new();
+        for node in get_elements(dom, &[ElementKind::Style, ElementKind::Link]) {
+            let node = node.borrow();
+            let text = match node.element_kind() {
+                Some(ElementKind::Style) => match node.first_child().map(|c| c.borrow().kind()) {
+                    Some(NodeKind::Text(text)) => Some(text),
+                    _ => None,
+                },
+                _ => base_url
+                    .as_ref()
+                    .and_then(|base| ResourceLoader::stylesheet_url(&node, base))
+                    .and_then(|url| self.resource_loader.stylesheet_text(&url, self.encoding)),
+            };
+            if let Some(text) = text {
+                style.push_str(&text);
+                style.push('\n');
+            }
+        }
+
+        let css_tokenizer = CssTokenizer::new(style);
+        self.style = Some(CssParser::new(css_tokenizer).parse_stylesheet());
+    }
+
+    // Brings the resources and the rendering up to date with a document that has grown.
+    fn refresh(&mut self) {
+        let dom = match &self.frame {
+            Some(frame) => frame.borrow().document(),
+            None => return,
+        };
+        if let Some(base_url) = self.base_url() {
+            self.resource_loader.discover(dom, &base_url);
+        }
+        self.update_style();
+        self.set_layout_view();
+        self.paint_tree();
     }
 
     fn set_layout_view(&mut self) {
@@ -79,3 +462,447 @@ impl Page {
         }
     }
 }
+
+impl ResponseSink for Page {
+    fn receive_head(&mut self, response: &HttpResponse) {
+        self.incoming = Some(IncomingResponse::new(response.clone()));
+    }
+
+    // Parses, lays out and paints as much of an HTML document as has arrived, repainting every
+    // `REPAINT_INTERVAL` bytes. The page keeps showing the previous document until parsing starts.
+    fn receive_body(&mut self, bytes: &[u8]) {
+        let Some(incoming) = self.incoming.as_mut() else {
+            return;
+        };
+        incoming.received += bytes.len();
+        if let Some((parser, decoder)) = incoming.parser.as_mut() {
+            parser.feed(&decoder.decode(bytes));
+            incoming.unpainted += bytes.len();
+            if incoming.unpainted >= REPAINT_INTERVAL {
+                incoming.unpainted = 0;
+                self.refresh();
+            }
+            return;
+        }
+
+        incoming.body.extend_from_slice(bytes);
+        let Some((mime_type, encoding)) = incoming.start_parsing() else {
+            return;
+        };
+        let head = incoming.head.clone();
+        let frame = match &incoming.parser {
+            Some((parser, _)) => parser.window(),
+            None => return,
+        };
+        if let Some(url) = head.url() {
+            self.url = Some(url);
+        }
+        self.from_cache = head.from_cache();
+        self.mime_type = Some(mime_type);
+        self.document_kind = Some(DocumentKind::Html);
+        self.encoding = Some(encoding);
+        self.download = None;
+        self.parse_errors = Vec::new();
+        self.create_frame(frame);
+        self.set_layout_view();
+        self.paint_tree();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{painted_texts, Fixture, FixtureFetcher};
+    use crate::http::Method;
+    use crate::renderer::loader::ResourceState;
+    use alloc::{format, vec};
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::new(
+            Method::Get,
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    #[test]
+    fn test_page_loads_subresources() {
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .add_html(
+                "http://example.com/index.html",
+                r#"<html><head>
+                <link rel="stylesheet" href="/css/site.css">
+                </head><body>
+                <p class="ad">buy now</p><p>article</p>
+                <img src="missing.png"><script src="app.js"></script><script>hidden()</script>
+                </body></html>"#,
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/css/site.css",
+                Fixture::with_status(
+                    200,
+                    &[("Content-Type", "text/css")],
+                    b".ad { display: none; }",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/missing.png",
+                Fixture::with_status(404, &[], b"").unwrap(),
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/app.js",
+                Fixture::with_status(200, &[("Content-Type", "text/javascript")], b"run()")
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+        page.borrow_mut()
+            .load(&mut fetcher, &get("http://example.com/index.html"))
+            .expect("failed to load the page");
+
+        let page = page.borrow();
+        assert_eq!(painted_texts(&page), vec!["article".to_string()]);
+        assert!(page.pending_resources().is_empty());
+        let states: Vec<(String, ResourceState)> = page
+            .resources()
+            .iter()
+            .map(|r| (r.url().path(), r.state()))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("/css/site.css".to_string(), ResourceState::Loaded),
+                (
+                    "/missing.png".to_string(),
+                    ResourceState::Failed("the server responded with status 404".to_string())
+                ),
+                ("/app.js".to_string(), ResourceState::Loaded),
+            ]
+        );
+        let accepts: Vec<Option<String>> = fetcher
+            .requests()
+            .iter()
+            .map(|r| r.header_value("Accept"))
+            .collect();
+        assert_eq!(accepts[1], Some("text/css,*/*;q=0.1".to_string()));
+    }
+
+    #[test]
+    fn test_page_dispatches_on_mime_type() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 40, 0, 0, 0, 30]);
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .add(
+                "http://example.com/notes.txt",
+                Fixture::with_status(
+                    200,
+                    &[("Content-Type", "text/plain; charset=utf-8")],
+                    b"<p>not  markup</p>\n\tindented",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/logo",
+                Fixture::with_status(200, &[("Content-Type", "image/gif")], &png).unwrap(),
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/files/report%20v2.zip",
+                Fixture::with_status(200, &[], b"PK\x03\x04data").unwrap(),
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/strict",
+                Fixture::with_status(
+                    200,
+                    &[
+                        ("Content-Type", "text/plain"),
+                        ("X-Content-Type-Options", "nosniff"),
+                    ],
+                    b"<html>",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+
+        page.borrow_mut()
+            .load(&mut fetcher, &get("http://example.com/notes.txt"))
+            .unwrap();
+        assert_eq!(page.borrow().document_kind(), Some(DocumentKind::Text));
+        assert_eq!(
+            painted_texts(&page.borrow()),
+            vec![
+                "<p>not  markup</p>".to_string(),
+                "        indented".to_string()
+            ]
+        );
+
+        page.borrow_mut()
+            .load(&mut fetcher, &get("http://example.com/logo"))
+            .unwrap();
+        assert_eq!(page.borrow().mime_type(), Some("image/png".to_string()));
+        assert_eq!(page.borrow().document_kind(), Some(DocumentKind::Image));
+        let images: Vec<(String, i64, i64)> = page
+            .borrow()
+            .display_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Image {
+                    src, layout_size, ..
+                } => Some((src, layout_size.width(), layout_size.height())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            images,
+            vec![("http://example.com/logo".to_string(), 40, 30)]
+        );
+        // The image was not fetched a second time.
+        assert_eq!(page.borrow().resources()[0].state(), ResourceState::Loaded);
+        assert_eq!(fetcher.requests().len(), 2);
+
+        page.borrow_mut()
+            .load(
+                &mut fetcher,
+                &get("http://example.com/files/report%20v2.zip"),
+            )
+            .unwrap();
+        let download = page.borrow().download().expect("should offer a download");
+        assert_eq!(download.filename(), "report v2.zip");
+        assert_eq!(download.mime_type(), "application/zip");
+        assert_eq!(download.body(), b"PK\x03\x04data".to_vec());
+        assert_eq!(
+            painted_texts(&page.borrow()),
+            vec![
+                "report v2.zip".to_string(),
+                "This application/zip file (8 bytes) cannot be displayed.".to_string(),
+                "Download".to_string()
+            ]
+        );
+
+        page.borrow_mut()
+            .load(&mut fetcher, &get("http://example.com/strict"))
+            .unwrap();
+        assert_eq!(page.borrow().document_kind(), Some(DocumentKind::Text));
+        assert!(page.borrow().download().is_none());
+        assert_eq!(painted_texts(&page.borrow()), vec!["<html>".to_string()]);
+    }
+
+    #[test]
+    fn test_download_filename_stays_in_the_download_directory() {
+        let response = HttpResponse::new(b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+        let url = |url: &str| Url::new(url.to_string()).parse().unwrap();
+        assert_eq!(
+            download_filename(
+                &response,
+                Some(&url("http://example.com/a/..%2F..%2Fevil.sh"))
+            ),
+            "evil.sh"
+        );
+        assert_eq!(
+            download_filename(&response, Some(&url("http://example.com/%2E%2E"))),
+            "download"
+        );
+
+        let response = HttpResponse::new(
+            b"HTTP/1.1 200 OK\r\nContent-Disposition: attachment; filename=\"../x\"\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            download_filename(&response, Some(&url("http://example.com/y"))),
+            "x"
+        );
+    }
+
+    // Records what the page shows after each piece of the body.
+    struct PaintRecorder<'a> {
+        page: &'a mut Page,
+        paints: Vec<Vec<String>>,
+    }
+
+    impl ResponseSink for PaintRecorder<'_> {
+        fn receive_head(&mut self, response: &HttpResponse) {
+            self.page.receive_head(response);
+        }
+
+        fn receive_body(&mut self, bytes: &[u8]) {
+            self.page.receive_body(bytes);
+            self.paints.push(painted_texts(self.page));
+        }
+    }
+
+    fn load_streaming(
+        fetcher: &mut FixtureFetcher,
+        page: &mut Page,
+        url: &str,
+    ) -> Vec<Vec<String>> {
+        let mut recorder = PaintRecorder {
+            page,
+            paints: Vec::new(),
+        };
+        let response = fetcher
+            .fetch_streaming(&get(url), &mut recorder)
+            .expect("failed to fetch");
+        let paints = recorder.paints;
+        page.finish_response(response);
+        paints
+    }
+
+    #[test]
+    fn test_page_paints_as_the_body_arrives() {
+        let mut fetcher = FixtureFetcher::new();
+        fetcher
+            .add_html("http://example.com/", "<p>one</p><p>two</p><p>three</p>")
+            .unwrap();
+        let long = format!(
+            "<p>one</p><p>{}</p><p>three</p>",
+            "two ".repeat(REPAINT_INTERVAL / 2)
+        );
+        fetcher.add_html("http://example.com/long", &long).unwrap();
+        let mut unlabelled_long = vec![
+            b'<', b'p', b'>', 0x93, 0xfa, 0x96, 0x7b, b'<', b'/', b'p', b'>',
+        ];
+        unlabelled_long.extend_from_slice(format!("<p>{}</p>", "x".repeat(2000)).as_bytes());
+        fetcher
+            .add(
+                "http://example.com/unlabelled-long",
+                Fixture::with_status(200, &[("Content-Type", "text/html")], &unlabelled_long)
+                    .unwrap(),
+            )
+            .unwrap();
+        fetcher
+            .add(
+                "http://example.com/unlabelled",
+                Fixture::with_status(
+                    200,
+                    &[("Content-Type", "text/html")],
+                    &[
+                        b'<', b'p', b'>', 0x93, 0xfa, 0x96, 0x7b, b'<', b'/', b'p', b'>',
+                    ],
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        fetcher.set_chunk_size(Some(5));
+
+        let mut page = Page::new();
+        let paints = load_streaming(&mut fetcher, &mut page, "http://example.com/");
+        assert_eq!(paints[0], vec!["on".to_string()]);
+        assert!(paints.iter().all(|paint| paint == &paints[0]));
+        assert_eq!(
+            painted_texts(&page),
+            vec!["one".to_string(), "two".to_string(), "three".to_string()]
+        );
+        assert_eq!(page.document_kind(), Some(DocumentKind::Html));
+        assert_eq!(
+            page.url().map(|u| u.href()),
+            Some("http://example.com/".to_string())
+        );
+
+        // Later pieces are only painted every `REPAINT_INTERVAL` bytes.
+        fetcher.set_chunk_size(Some(1024));
+        let mut page = Page::new();
+        let mut shown = load_streaming(&mut fetcher, &mut page, "http://example.com/long");
+        shown.dedup();
+        assert_eq!(shown.len(), 2);
+        assert!(shown[0].len() < shown[1].len());
+        assert!(!shown[1].contains(&"three".to_string()));
+        assert_eq!(painted_texts(&page).last(), Some(&"three".to_string()));
+        fetcher.set_chunk_size(Some(5));
+
+        // Without a label the encoding is guessed once the first 1024 bytes are there, so a
+        // shorter document is not shown before it is all there.
+        let mut page = Page::new();
+        let paints = load_streaming(&mut fetcher, &mut page, "http://example.com/unlabelled");
+        assert!(paints.iter().all(|paint| paint.is_empty()));
+        assert_eq!(painted_texts(&page), vec!["日本".to_string()]);
+        assert_eq!(page.encoding(), Some(Encoding::ShiftJis));
+
+        let mut page = Page::new();
+        let paints = load_streaming(
+            &mut fetcher,
+            &mut page,
+            "http://example.com/unlabelled-long",
+        );
+        let first_shown = paints.iter().position(|paint| !paint.is_empty()).unwrap();
+        assert!(first_shown < paints.len() - 1);
+        assert_eq!(paints[first_shown][0], "日本");
+        assert_eq!(page.encoding(), Some(Encoding::ShiftJis));
+
+        // `load` streams too, with the same result as when the body comes in one piece.
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+        page.borrow_mut()
+            .load(&mut fetcher, &get("http://example.com/"))
+            .unwrap();
+        assert_eq!(
+            painted_texts(&page.borrow()),
+            vec!["one".to_string(), "two".to_string(), "three".to_string()]
+        );
+    }
+
+    // Sends the start of a document and then loses the connection.
+    struct CutOffFetcher;
+
+    impl Fetcher for CutOffFetcher {
+        fn fetch(&mut self, _request: &HttpRequest) -> Result<HttpResponse, Error> {
+            Err(Error::Network("connection reset".to_string()))
+        }
+
+        fn fetch_streaming(
+            &mut self,
+            request: &HttpRequest,
+            sink: &mut dyn ResponseSink,
+        ) -> Result<HttpResponse, Error> {
+            let mut head = HttpResponse::new(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n",
+            )
+            .unwrap();
+            head.set_url(request.url());
+            sink.receive_head(&head);
+            sink.receive_body(b"<p>partial</p><p>cut");
+            self.fetch(request)
+        }
+    }
+
+    #[test]
+    fn test_page_load_cut_off() {
+        let mut page = Page::new();
+        let result = page.load(&mut CutOffFetcher, &get("http://example.com/"));
+        assert_eq!(
+            result.unwrap_err(),
+            Error::Network("connection reset".to_string())
+        );
+        assert!(page.incoming.is_none());
+        assert_eq!(
+            painted_texts(&page),
+            vec!["partial".to_string(), "cut".to_string()]
+        );
+
+        // Nothing more is added to the document once the load has failed.
+        page.receive_body(b"ting</p>");
+        assert_eq!(
+            painted_texts(&page),
+            vec!["partial".to_string(), "cut".to_string()]
+        );
+    }
+}
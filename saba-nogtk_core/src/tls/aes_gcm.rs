@@ -0,0 +1,283 @@
+use alloc::vec::Vec;
+
+// AES-128 (FIPS 197) in Galois/Counter Mode (NIST SP 800-38D), the one AEAD this TLS client
+// offers. The S-box is computed at compile time from the field inverse and affine transform.
+
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let high = a & 0x80;
+        a <<= 1;
+        if high != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+const fn make_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut x = 0usize;
+    while x < 256 {
+        // The multiplicative inverse is x^254 in GF(2^8); zero maps to zero.
+        let mut inverse = 1u8;
+        let mut i = 0;
+        while i < 254 {
+            inverse = gf_mul(inverse, x as u8);
+            i += 1;
+        }
+        if x == 0 {
+            inverse = 0;
+        }
+        let b = inverse;
+        sbox[x] =
+            b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63;
+        x += 1;
+    }
+    sbox
+}
+
+const SBOX: [u8; 256] = make_sbox();
+
+pub(crate) struct Aes128 {
+    round_keys: [[u8; 16]; 11],
+}
+
+impl Aes128 {
+    pub(crate) fn new(key: &[u8; 16]) -> Self {
+        let mut words = [[0u8; 4]; 44];
+        for i in 0..4 {
+            words[i].copy_from_slice(&key[4 * i..4 * i + 4]);
+        }
+        let mut rcon = 1u8;
+        for i in 4..44 {
+            let mut temp = words[i - 1];
+            if i % 4 == 0 {
+                temp = [
+                    SBOX[temp[1] as usize] ^ rcon,
+                    SBOX[temp[2] as usize],
+                    SBOX[temp[3] as usize],
+                    SBOX[temp[0] as usize],
+                ];
+                rcon = gf_mul(rcon, 2);
+            }
+            let previous = words[i - 4];
+            for (word, (p, t)) in words[i].iter_mut().zip(previous.iter().zip(&temp)) {
+                *word = p ^ t;
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; 11];
+        for (round, round_key) in round_keys.iter_mut().enumerate() {
+            for j in 0..4 {
+                round_key[4 * j..4 * j + 4].copy_from_slice(&words[4 * round + j]);
+            }
+        }
+        Self { round_keys }
+    }
+
+    pub(crate) fn encrypt_block(&self, block: &[u8; 16]) -> [u8; 16] {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+        for round in 1..11 {
+            for b in state.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            shift_rows(&mut state);
+            if round != 10 {
+                mix_columns(&mut state);
+            }
+            add_round_key(&mut state, &self.round_keys[round]);
+        }
+        state
+    }
+}
+
+fn add_round_key(state: &mut [u8; 16], key: &[u8; 16]) {
+    for (s, k) in state.iter_mut().zip(key) {
+        *s ^= k;
+    }
+}
+
+// The state is column-major: byte 4 * c + r is row r of column c.
+fn shift_rows(state: &mut [u8; 16]) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 1..4 {
+            state[4 * c + r] = old[4 * ((c + r) % 4) + r];
+        }
+    }
+}
+
+fn mix_columns(state: &mut [u8; 16]) {
+    for c in 0..4 {
+        let col = [
+            state[4 * c],
+            state[4 * c + 1],
+            state[4 * c + 2],
+            state[4 * c + 3],
+        ];
+        for r in 0..4 {
+            state[4 * c + r] = gf_mul(col[r], 2)
+                ^ gf_mul(col[(r + 1) % 4], 3)
+                ^ col[(r + 2) % 4]
+                ^ col[(r + 3) % 4];
+        }
+    }
+}
+
+pub(crate) struct AesGcm {
+    cipher: Aes128,
+    h: u128,
+}
+
+impl AesGcm {
+    pub(crate) fn new(key: &[u8; 16]) -> Self {
+        let cipher = Aes128::new(key);
+        let h = u128::from_be_bytes(cipher.encrypt_block(&[0u8; 16]));
+        Self { cipher, h }
+    }
+
+    fn counter_block(nonce: &[u8; 12], counter: u32) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        block[..12].copy_from_slice(nonce);
+        block[12..].copy_from_slice(&counter.to_be_bytes());
+        block
+    }
+
+    fn ctr(&self, nonce: &[u8; 12], data: &mut [u8]) {
+        for (i, chunk) in data.chunks_mut(16).enumerate() {
+            let keystream = self
+                .cipher
+                .encrypt_block(&Self::counter_block(nonce, i as u32 + 2));
+            for (b, k) in chunk.iter_mut().zip(&keystream) {
+                *b ^= k;
+            }
+        }
+    }
+
+    fn tag(&self, nonce: &[u8; 12], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+        let mut y = 0u128;
+        for data in [aad, ciphertext] {
+            for chunk in data.chunks(16) {
+                let mut block = [0u8; 16];
+                block[..chunk.len()].copy_from_slice(chunk);
+                y = ghash_mul(y ^ u128::from_be_bytes(block), self.h);
+            }
+        }
+        let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+        y = ghash_mul(y ^ lengths, self.h);
+
+        let mask = self.cipher.encrypt_block(&Self::counter_block(nonce, 1));
+        (y ^ u128::from_be_bytes(mask)).to_be_bytes()
+    }
+
+    // Returns the ciphertext followed by the 16-byte tag.
+    pub(crate) fn seal(&self, nonce: &[u8; 12], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut out = plaintext.to_vec();
+        self.ctr(nonce, &mut out);
+        let tag = self.tag(nonce, aad, &out);
+        out.extend_from_slice(&tag);
+        out
+    }
+
+    // Checks the tag and decrypts, or returns None if the message was tampered with.
+    pub(crate) fn open(&self, nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        if sealed.len() < 16 {
+            return None;
+        }
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - 16);
+        let expected = self.tag(nonce, aad, ciphertext);
+        let difference = expected
+            .iter()
+            .zip(tag)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if difference != 0 {
+            return None;
+        }
+        let mut out = ciphertext.to_vec();
+        self.ctr(nonce, &mut out);
+        Some(out)
+    }
+}
+
+// Multiplication in GF(2^128) with GCM's reflected bit order.
+fn ghash_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut z = 0u128;
+    let mut v = y;
+    for i in 0..128 {
+        if x >> (127 - i) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_aes128_fips197() {
+        let key: [u8; 16] = from_hex("000102030405060708090a0b0c0d0e0f")
+            .try_into()
+            .unwrap();
+        let block: [u8; 16] = from_hex("00112233445566778899aabbccddeeff")
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            Aes128::new(&key).encrypt_block(&block).to_vec(),
+            from_hex("69c4e0d86a7b0430d8cdb78070b4c55a")
+        );
+    }
+
+    #[test]
+    fn test_gcm_empty_and_single_block() {
+        let gcm = AesGcm::new(&[0u8; 16]);
+        let nonce = [0u8; 12];
+        assert_eq!(
+            gcm.seal(&nonce, &[], &[]),
+            from_hex("58e2fccefa7e3061367f1d57a4e7455a")
+        );
+        assert_eq!(
+            gcm.seal(&nonce, &[], &[0u8; 16]),
+            from_hex("0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf")
+        );
+    }
+
+    #[test]
+    fn test_gcm_with_aad_round_trip() {
+        // Test case 4 from the GCM specification.
+        let key: [u8; 16] = from_hex("feffe9928665731c6d6a8f9467308308")
+            .try_into()
+            .unwrap();
+        let nonce: [u8; 12] = from_hex("cafebabefacedbaddecaf888").try_into().unwrap();
+        let plaintext = from_hex("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39");
+        let aad = from_hex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let gcm = AesGcm::new(&key);
+        let sealed = gcm.seal(&nonce, &aad, &plaintext);
+        assert_eq!(
+            sealed,
+            from_hex("42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e0915bc94fbc3221a5db94fae95ae7121a47")
+        );
+        assert_eq!(gcm.open(&nonce, &aad, &sealed), Some(plaintext));
+
+        let mut tampered = sealed.clone();
+        tampered[3] ^= 1;
+        assert_eq!(gcm.open(&nonce, &aad, &tampered), None);
+        assert_eq!(gcm.open(&nonce, &[], &sealed), None);
+    }
+}
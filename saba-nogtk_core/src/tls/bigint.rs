@@ -0,0 +1,318 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+// An arbitrary-precision unsigned integer for public-key operations. It is only used on public
+// values during signature verification, so it makes no attempt to run in constant time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BigUint {
+    // Little-endian 32-bit limbs without trailing zero limbs.
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub(crate) fn zero() -> Self {
+        Self { limbs: Vec::new() }
+    }
+
+    pub(crate) fn from_u32(value: u32) -> Self {
+        Self::from_limbs(vec![value])
+    }
+
+    fn from_limbs(mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self { limbs }
+    }
+
+    pub(crate) fn from_bytes_be(bytes: &[u8]) -> Self {
+        let mut limbs = Vec::with_capacity(bytes.len() / 4 + 1);
+        for chunk in bytes.rchunks(4) {
+            let mut limb = 0u32;
+            for &b in chunk {
+                limb = limb << 8 | b as u32;
+            }
+            limbs.push(limb);
+        }
+        Self::from_limbs(limbs)
+    }
+
+    // Returns the value as exactly `len` big-endian bytes, or None if it does not fit.
+    pub(crate) fn to_bytes_be(&self, len: usize) -> Option<Vec<u8>> {
+        if self.bits() > len * 8 {
+            return None;
+        }
+        let mut bytes = vec![0u8; len];
+        for (i, limb) in self.limbs.iter().enumerate() {
+            for j in 0..4 {
+                let index = i * 4 + j;
+                if index < len {
+                    bytes[len - 1 - index] = (limb >> (8 * j)) as u8;
+                }
+            }
+        }
+        Some(bytes)
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub(crate) fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() * 32 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub(crate) fn bit(&self, i: usize) -> bool {
+        self.limbs
+            .get(i / 32)
+            .is_some_and(|limb| limb >> (i % 32) & 1 == 1)
+    }
+
+    pub(crate) fn add(&self, other: &Self) -> Self {
+        let len = core::cmp::max(self.limbs.len(), other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for i in 0..len {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u64
+                + *other.limbs.get(i).unwrap_or(&0) as u64
+                + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        Self::from_limbs(limbs)
+    }
+
+    // Computes `self - other`, which must not be negative.
+    pub(crate) fn sub(&self, other: &Self) -> Self {
+        debug_assert!(*self >= *other);
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+        for i in 0..self.limbs.len() {
+            let mut diff = self.limbs[i] as i64 - *other.limbs.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if diff < 0 {
+                diff += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(diff as u32);
+        }
+        Self::from_limbs(limbs)
+    }
+
+    pub(crate) fn mul(&self, other: &Self) -> Self {
+        if self.is_zero() || other.is_zero() {
+            return Self::zero();
+        }
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let t = a as u64 * b as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        Self::from_limbs(limbs)
+    }
+
+    // Computes `self mod modulus` with Knuth's algorithm D.
+    pub(crate) fn rem(&self, modulus: &Self) -> Self {
+        assert!(!modulus.is_zero(), "division by zero");
+        if *self < *modulus {
+            return self.clone();
+        }
+
+        let v = &modulus.limbs;
+        let n = v.len();
+        if n == 1 {
+            let mut rem = 0u64;
+            for &limb in self.limbs.iter().rev() {
+                rem = (rem << 32 | limb as u64) % v[0] as u64;
+            }
+            return Self::from_u32(rem as u32);
+        }
+
+        // Normalize so that the top limb of the divisor has its high bit set.
+        let shift = v[n - 1].leading_zeros();
+        let vn = shift_left(v, shift, n);
+        let mut un = shift_left(&self.limbs, shift, self.limbs.len() + 1);
+        let m = self.limbs.len() - n;
+
+        for j in (0..=m).rev() {
+            let numerator = (un[j + n] as u64) << 32 | un[j + n - 1] as u64;
+            let mut qhat = numerator / vn[n - 1] as u64;
+            let mut rhat = numerator % vn[n - 1] as u64;
+            while qhat >> 32 != 0 || qhat * vn[n - 2] as u64 > (rhat << 32 | un[j + n - 2] as u64) {
+                qhat -= 1;
+                rhat += vn[n - 1] as u64;
+                if rhat >> 32 != 0 {
+                    break;
+                }
+            }
+
+            let mut borrow = 0i64;
+            for i in 0..n {
+                let product = qhat * vn[i] as u64;
+                let t = un[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+                un[i + j] = t as u32;
+                borrow = (product >> 32) as i64 - (t >> 32);
+            }
+            let t = un[j + n] as i64 - borrow;
+            un[j + n] = t as u32;
+
+            if t < 0 {
+                let mut carry = 0u64;
+                for i in 0..n {
+                    let sum = un[i + j] as u64 + vn[i] as u64 + carry;
+                    un[i + j] = sum as u32;
+                    carry = sum >> 32;
+                }
+                un[j + n] = un[j + n].wrapping_add(carry as u32);
+            }
+        }
+
+        let mut rem = vec![0u32; n];
+        for i in 0..n {
+            rem[i] = if shift == 0 {
+                un[i]
+            } else {
+                un[i] >> shift | un[i + 1] << (32 - shift)
+            };
+        }
+        Self::from_limbs(rem)
+    }
+
+    pub(crate) fn mod_add(&self, other: &Self, modulus: &Self) -> Self {
+        let sum = self.add(other);
+        if sum >= *modulus {
+            sum.sub(modulus)
+        } else {
+            sum
+        }
+    }
+
+    pub(crate) fn mod_sub(&self, other: &Self, modulus: &Self) -> Self {
+        if *self >= *other {
+            self.sub(other)
+        } else {
+            self.add(modulus).sub(other)
+        }
+    }
+
+    pub(crate) fn mod_mul(&self, other: &Self, modulus: &Self) -> Self {
+        self.mul(other).rem(modulus)
+    }
+
+    pub(crate) fn mod_pow(&self, exponent: &Self, modulus: &Self) -> Self {
+        let base = self.rem(modulus);
+        let mut result = Self::from_u32(1).rem(modulus);
+        for i in (0..exponent.bits()).rev() {
+            result = result.mod_mul(&result, modulus);
+            if exponent.bit(i) {
+                result = result.mod_mul(&base, modulus);
+            }
+        }
+        result
+    }
+
+    // The inverse modulo a prime, by Fermat's little theorem.
+    pub(crate) fn mod_inv_prime(&self, prime: &Self) -> Self {
+        self.mod_pow(&prime.sub(&Self::from_u32(2)), prime)
+    }
+}
+
+fn shift_left(limbs: &[u32], shift: u32, len: usize) -> Vec<u32> {
+    let mut shifted = vec![0u32; len];
+    let mut carry = 0u32;
+    for (i, &limb) in limbs.iter().enumerate() {
+        shifted[i] = if shift == 0 {
+            limb
+        } else {
+            limb << shift | carry
+        };
+        carry = if shift == 0 { 0 } else { limb >> (32 - shift) };
+    }
+    if limbs.len() < len {
+        shifted[limbs.len()] = carry;
+    }
+    shifted
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> BigUint {
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        BigUint::from_bytes_be(&bytes)
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let n = BigUint::from_bytes_be(&[0, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(n.bits(), 33);
+        assert_eq!(n.to_bytes_be(5), Some(vec![1, 2, 3, 4, 5]));
+        assert_eq!(n.to_bytes_be(7), Some(vec![0, 0, 1, 2, 3, 4, 5]));
+        assert_eq!(n.to_bytes_be(4), None);
+        assert!(BigUint::from_bytes_be(&[0, 0]).is_zero());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = from_hex("ffffffffffffffffffffffff");
+        let b = from_hex("01");
+        assert_eq!(a.add(&b), from_hex("01000000000000000000000000"));
+        assert_eq!(a.add(&b).sub(&b), a);
+        assert_eq!(
+            a.mul(&a),
+            from_hex("fffffffffffffffffffffffe000000000000000000000001")
+        );
+        assert!(a > b);
+    }
+
+    #[test]
+    fn test_rem() {
+        // Values cross-checked with Python's integers.
+        let a = from_hex("0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef");
+        let m = from_hex("fedcba9876543210fedcba98765433");
+        assert_eq!(a.rem(&m), from_hex("dbf07d33b5fa8dd905bc3e821404ed"));
+        assert_eq!(a.rem(&from_hex("fffffffb")), from_hex("8f5bebea"));
+        assert_eq!(m.rem(&a), m);
+    }
+
+    #[test]
+    fn test_mod_pow_and_inverse() {
+        let p = from_hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff");
+        let x = from_hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296");
+        let inverse = x.mod_inv_prime(&p);
+        assert_eq!(x.mod_mul(&inverse, &p), BigUint::from_u32(1));
+        assert_eq!(
+            BigUint::from_u32(3).mod_pow(&BigUint::from_u32(200), &from_hex("010001")),
+            from_hex("8f8e")
+        );
+    }
+}
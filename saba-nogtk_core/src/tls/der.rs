@@ -0,0 +1,160 @@
+// A minimal DER reader: just enough of X.690 to walk certificates.
+
+pub(crate) const BOOLEAN: u8 = 0x01;
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const UTC_TIME: u8 = 0x17;
+pub(crate) const GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const SEQUENCE: u8 = 0x30;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Element<'a> {
+    pub(crate) tag: u8,
+    pub(crate) content: &'a [u8],
+    // The full encoding including the tag and length, which is what signatures cover.
+    pub(crate) raw: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    pub(crate) fn next(&mut self) -> Option<Element<'a>> {
+        let tag = *self.data.first()?;
+        // Multi-byte tags never appear in the structures we read.
+        if tag & 0x1f == 0x1f {
+            return None;
+        }
+        let first = *self.data.get(1)? as usize;
+        let (len, header) = if first < 0x80 {
+            (first, 2)
+        } else {
+            let count = first & 0x7f;
+            if count == 0 || count > 4 {
+                return None;
+            }
+            let mut len = 0usize;
+            for i in 0..count {
+                len = len << 8 | *self.data.get(2 + i)? as usize;
+            }
+            // DER requires the shortest length encoding.
+            if len < 0x80 || (count > 1 && len >> (8 * (count - 1)) == 0) {
+                return None;
+            }
+            (len, 2 + count)
+        };
+        let end = header.checked_add(len)?;
+        if end > self.data.len() {
+            return None;
+        }
+        let element = Element {
+            tag,
+            content: &self.data[header..end],
+            raw: &self.data[..end],
+        };
+        self.data = &self.data[end..];
+        Some(element)
+    }
+
+    // Reads the next element, which must carry the given tag.
+    pub(crate) fn expect(&mut self, tag: u8) -> Option<Element<'a>> {
+        let element = self.next()?;
+        if element.tag == tag {
+            Some(element)
+        } else {
+            None
+        }
+    }
+
+    // Reads the next element only if it carries the given tag.
+    pub(crate) fn optional(&mut self, tag: u8) -> Option<Element<'a>> {
+        if self.peek_tag() == Some(tag) {
+            self.next()
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Element<'a> {
+    pub(crate) fn reader(&self) -> Reader<'a> {
+        Reader::new(self.content)
+    }
+
+    // The bytes of a BIT STRING, which must not have unused bits.
+    pub(crate) fn bit_string_bytes(&self) -> Option<&'a [u8]> {
+        match self.content.split_first() {
+            Some((0, bytes)) if self.tag == BIT_STRING => Some(bytes),
+            _ => None,
+        }
+    }
+
+    // The magnitude of a non-negative INTEGER without its leading zero bytes.
+    pub(crate) fn unsigned_integer(&self) -> Option<&'a [u8]> {
+        if self.tag != INTEGER || self.content.is_empty() || self.content[0] & 0x80 != 0 {
+            return None;
+        }
+        let start = self
+            .content
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(self.content.len());
+        Some(&self.content[start..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_read_nested() {
+        let data = [0x30, 0x06, 0x02, 0x01, 0x05, 0x04, 0x01, 0xaa, 0x05, 0x00];
+        let mut reader = Reader::new(&data);
+        let sequence = reader.expect(SEQUENCE).unwrap();
+        assert_eq!(sequence.raw, &data[..8]);
+        let mut inner = sequence.reader();
+        assert_eq!(inner.next().unwrap().unsigned_integer(), Some(&[5u8][..]));
+        assert_eq!(inner.optional(INTEGER), None);
+        assert_eq!(inner.expect(OCTET_STRING).unwrap().content, &[0xaa]);
+        assert!(inner.is_empty());
+        assert_eq!(reader.expect(0x05).unwrap().content, &[] as &[u8]);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_long_lengths() {
+        let mut data = vec![0x04, 0x81, 0x80];
+        data.extend_from_slice(&[7; 128]);
+        assert_eq!(Reader::new(&data).next().unwrap().content.len(), 128);
+
+        // Non-minimal and truncated lengths are rejected.
+        assert_eq!(Reader::new(&[0x04, 0x81, 0x05, 1, 2, 3, 4, 5]).next(), None);
+        assert_eq!(Reader::new(&[0x04, 0x05, 1, 2]).next(), None);
+    }
+
+    #[test]
+    fn test_integers() {
+        let element = Reader::new(&[0x02, 0x03, 0x00, 0x80, 0x01]).next().unwrap();
+        assert_eq!(element.unsigned_integer(), Some(&[0x80u8, 0x01][..]));
+        let negative = Reader::new(&[0x02, 0x01, 0xff]).next().unwrap();
+        assert_eq!(negative.unsigned_integer(), None);
+    }
+}
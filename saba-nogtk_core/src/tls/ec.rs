@@ -0,0 +1,327 @@
+use alloc::vec::Vec;
+
+use crate::tls::bigint::BigUint;
+
+// The NIST prime curves y^2 = x^3 - 3x + b used by ECDSA certificates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Curve {
+    P256,
+    P384,
+}
+
+struct CurveParams {
+    p: BigUint,
+    b: BigUint,
+    n: BigUint,
+    g: (BigUint, BigUint),
+}
+
+fn hex_to_biguint(hex: &str) -> BigUint {
+    let bytes: Vec<u8> = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |c: u8| (c as char).to_digit(16).unwrap_or(0) as u8;
+            digit(pair[0]) << 4 | digit(pair[1])
+        })
+        .collect();
+    BigUint::from_bytes_be(&bytes)
+}
+
+impl Curve {
+    pub(crate) fn field_len(&self) -> usize {
+        match self {
+            Curve::P256 => 32,
+            Curve::P384 => 48,
+        }
+    }
+
+    fn params(&self) -> CurveParams {
+        match self {
+            Curve::P256 => CurveParams {
+                p: hex_to_biguint(
+                    "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+                ),
+                b: hex_to_biguint(
+                    "5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b",
+                ),
+                n: hex_to_biguint(
+                    "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+                ),
+                g: (
+                    hex_to_biguint(
+                        "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296",
+                    ),
+                    hex_to_biguint(
+                        "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5",
+                    ),
+                ),
+            },
+            Curve::P384 => CurveParams {
+                p: hex_to_biguint(
+                    "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffffff0000000000000000ffffffff",
+                ),
+                b: hex_to_biguint(
+                    "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875ac656398d8a2ed19d2a85c8edd3ec2aef",
+                ),
+                n: hex_to_biguint(
+                    "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf581a0db248b0a77aecec196accc52973",
+                ),
+                g: (
+                    hex_to_biguint(
+                        "aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a385502f25dbf55296c3a545e3872760ab7",
+                    ),
+                    hex_to_biguint(
+                        "3617de4a96262c6f5d9e98bf9292dc29f8f41dbd289a147ce9da3113b5f0b8c00a60b1ce1d7e819d7a431d7c90ea0e5f",
+                    ),
+                ),
+            },
+        }
+    }
+}
+
+// A point in Jacobian coordinates. Z = 0 is the point at infinity.
+#[derive(Debug, Clone)]
+struct Point {
+    x: BigUint,
+    y: BigUint,
+    z: BigUint,
+}
+
+impl Point {
+    fn infinity() -> Self {
+        Self {
+            x: BigUint::from_u32(1),
+            y: BigUint::from_u32(1),
+            z: BigUint::zero(),
+        }
+    }
+
+    fn from_affine(x: &BigUint, y: &BigUint) -> Self {
+        Self {
+            x: x.clone(),
+            y: y.clone(),
+            z: BigUint::from_u32(1),
+        }
+    }
+
+    fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+}
+
+struct Field<'a> {
+    p: &'a BigUint,
+}
+
+impl<'a> Field<'a> {
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a.mod_add(b, self.p)
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a.mod_sub(b, self.p)
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a.mod_mul(b, self.p)
+    }
+
+    fn small(&self, k: u32, a: &BigUint) -> BigUint {
+        a.mod_mul(&BigUint::from_u32(k), self.p)
+    }
+
+    // dbl-2001-b, which relies on a = -3.
+    fn double(&self, point: &Point) -> Point {
+        if point.is_infinity() || point.y.is_zero() {
+            return Point::infinity();
+        }
+        let delta = self.mul(&point.z, &point.z);
+        let gamma = self.mul(&point.y, &point.y);
+        let beta = self.mul(&point.x, &gamma);
+        let alpha = self.small(
+            3,
+            &self.mul(&self.sub(&point.x, &delta), &self.add(&point.x, &delta)),
+        );
+        let x = self.sub(&self.mul(&alpha, &alpha), &self.small(8, &beta));
+        let yz = self.add(&point.y, &point.z);
+        let z = self.sub(&self.sub(&self.mul(&yz, &yz), &gamma), &delta);
+        let y = self.sub(
+            &self.mul(&alpha, &self.sub(&self.small(4, &beta), &x)),
+            &self.small(8, &self.mul(&gamma, &gamma)),
+        );
+        Point { x, y, z }
+    }
+
+    fn add_points(&self, a: &Point, b: &Point) -> Point {
+        if a.is_infinity() {
+            return b.clone();
+        }
+        if b.is_infinity() {
+            return a.clone();
+        }
+        let z1z1 = self.mul(&a.z, &a.z);
+        let z2z2 = self.mul(&b.z, &b.z);
+        let u1 = self.mul(&a.x, &z2z2);
+        let u2 = self.mul(&b.x, &z1z1);
+        let s1 = self.mul(&self.mul(&a.y, &b.z), &z2z2);
+        let s2 = self.mul(&self.mul(&b.y, &a.z), &z1z1);
+        let h = self.sub(&u2, &u1);
+        let r = self.sub(&s2, &s1);
+        if h.is_zero() {
+            return if r.is_zero() {
+                self.double(a)
+            } else {
+                Point::infinity()
+            };
+        }
+        let hh = self.mul(&h, &h);
+        let hhh = self.mul(&h, &hh);
+        let v = self.mul(&u1, &hh);
+        let x = self.sub(&self.sub(&self.mul(&r, &r), &hhh), &self.small(2, &v));
+        let y = self.sub(&self.mul(&r, &self.sub(&v, &x)), &self.mul(&s1, &hhh));
+        let z = self.mul(&self.mul(&a.z, &b.z), &h);
+        Point { x, y, z }
+    }
+
+    fn affine_x(&self, point: &Point) -> BigUint {
+        let z_inv = point.z.mod_inv_prime(self.p);
+        self.mul(&point.x, &self.mul(&z_inv, &z_inv))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct PublicKey {
+    curve: Curve,
+    x: BigUint,
+    y: BigUint,
+}
+
+impl PublicKey {
+    // Decodes an uncompressed SEC 1 point and checks that it lies on the curve.
+    pub(crate) fn from_sec1(curve: Curve, bytes: &[u8]) -> Option<Self> {
+        let len = curve.field_len();
+        if bytes.len() != 1 + 2 * len || bytes[0] != 0x04 {
+            return None;
+        }
+        let params = curve.params();
+        let x = BigUint::from_bytes_be(&bytes[1..1 + len]);
+        let y = BigUint::from_bytes_be(&bytes[1 + len..]);
+        if x >= params.p || y >= params.p {
+            return None;
+        }
+
+        let field = Field { p: &params.p };
+        let lhs = field.mul(&y, &y);
+        let x3 = field.mul(&field.mul(&x, &x), &x);
+        let rhs = field.add(&field.sub(&x3, &field.small(3, &x)), &params.b);
+        if lhs != rhs {
+            return None;
+        }
+        Some(Self { curve, x, y })
+    }
+
+    // Verifies an ECDSA signature over a message digest, given the signature's r and s.
+    pub(crate) fn verify(&self, digest: &[u8], r: &BigUint, s: &BigUint) -> bool {
+        let params = self.curve.params();
+        let n = &params.n;
+        if r.is_zero() || s.is_zero() || r >= n || s >= n {
+            return false;
+        }
+
+        // A digest longer than the group order is truncated to its leftmost bits.
+        let n_bits = n.bits();
+        let digest = if digest.len() * 8 > n_bits {
+            &digest[..n_bits / 8]
+        } else {
+            digest
+        };
+        let e = BigUint::from_bytes_be(digest).rem(n);
+
+        let w = s.mod_inv_prime(n);
+        let u1 = e.mod_mul(&w, n);
+        let u2 = r.mod_mul(&w, n);
+
+        // Shamir's trick: compute u1 * G + u2 * Q in one pass over the bits.
+        let field = Field { p: &params.p };
+        let g = Point::from_affine(&params.g.0, &params.g.1);
+        let q = Point::from_affine(&self.x, &self.y);
+        let gq = field.add_points(&g, &q);
+        let mut point = Point::infinity();
+        for i in (0..core::cmp::max(u1.bits(), u2.bits())).rev() {
+            point = field.double(&point);
+            match (u1.bit(i), u2.bit(i)) {
+                (true, true) => point = field.add_points(&point, &gq),
+                (true, false) => point = field.add_points(&point, &g),
+                (false, true) => point = field.add_points(&point, &q),
+                (false, false) => {}
+            }
+        }
+        if point.is_infinity() {
+            return false;
+        }
+        field.affine_x(&point).rem(n) == *r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{sha256, sha384};
+    use alloc::vec;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_point_validation() {
+        let params = Curve::P256.params();
+        let mut point = vec![0x04];
+        point.extend_from_slice(&params.g.0.to_bytes_be(32).unwrap());
+        point.extend_from_slice(&params.g.1.to_bytes_be(32).unwrap());
+        assert!(PublicKey::from_sec1(Curve::P256, &point).is_some());
+
+        point[64] ^= 1;
+        assert!(PublicKey::from_sec1(Curve::P256, &point).is_none());
+        assert!(PublicKey::from_sec1(Curve::P384, &point).is_none());
+    }
+
+    #[test]
+    fn test_verify_p256() {
+        let key = PublicKey::from_sec1(
+            Curve::P256,
+            &from_hex("049352806aab34ff646090ddbafde647c01be837d75f7c3ac9d910e7fb9fe00d0c148fd331f8f11a59e2665d90cfd9e4a10265e77202f32fbd9ee46c62fb2d3ba4"),
+        )
+        .unwrap();
+        let r = BigUint::from_bytes_be(&from_hex(
+            "b1f61308acd5908a8d0408464f4a6f3abf39c0e90c63394deb9286862e5c02ba",
+        ));
+        let s = BigUint::from_bytes_be(&from_hex(
+            "2a0590b22e7a56547a6f833807cc76bf559fac6098d520833d037281353dfba4",
+        ));
+        assert!(key.verify(&sha256(b"sample"), &r, &s));
+        assert!(!key.verify(&sha256(b"samples"), &r, &s));
+        assert!(!key.verify(&sha256(b"sample"), &s, &r));
+    }
+
+    #[test]
+    fn test_verify_p384() {
+        let key = PublicKey::from_sec1(
+            Curve::P384,
+            &from_hex("0454379f492ad0930a39604cc660643a28096dc25eff33bee34b5c8335fec8c00ca7ab6ee37a5e421611e2cd5f5d73c248b8e9eb5003639762415c692066ed649e546ff3964ef4e8e9ee750c248fccd2be9bb34b7127229953df8ad4c123f26b5e"),
+        )
+        .unwrap();
+        let r = BigUint::from_bytes_be(&from_hex(
+            "da5802c1f7cc3ba202f1db1797e900df4cff746ba9204ee8ef5dc79020f7782cdb3a0d39092b6629ad21461a67552dca",
+        ));
+        let s = BigUint::from_bytes_be(&from_hex(
+            "d5001e66c10322d510c16ecc56410883162eab5b3ea7506ecc99c0c75137dc034512eebacf9caa9a8a5136ccc1128ba8",
+        ));
+        assert!(key.verify(&sha384(b"sample"), &r, &s));
+        assert!(!key.verify(&sha384(b"sample!"), &r, &s));
+    }
+}
@@ -0,0 +1,482 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::hash::{hmac_sha256, sha256, Sha256};
+use crate::random::RandomSource;
+use crate::tls::record::{expand_label, Cipher, RecordLayer, ALERT, CHANGE_CIPHER_SPEC, HANDSHAKE};
+use crate::tls::rsa::HashAlgorithm;
+use crate::tls::x25519;
+use crate::tls::x509::{is_ip_address, verify_chain, Certificate, SignatureScheme};
+
+pub(crate) const CLIENT_HELLO: u8 = 1;
+pub(crate) const SERVER_HELLO: u8 = 2;
+pub(crate) const NEW_SESSION_TICKET: u8 = 4;
+pub(crate) const ENCRYPTED_EXTENSIONS: u8 = 8;
+pub(crate) const CERTIFICATE: u8 = 11;
+pub(crate) const CERTIFICATE_REQUEST: u8 = 13;
+pub(crate) const CERTIFICATE_VERIFY: u8 = 15;
+pub(crate) const FINISHED: u8 = 20;
+pub(crate) const KEY_UPDATE: u8 = 24;
+
+const TLS_AES_128_GCM_SHA256: u16 = 0x1301;
+const X25519: u16 = 0x001d;
+
+const EXTENSION_SERVER_NAME: u16 = 0;
+const EXTENSION_SUPPORTED_GROUPS: u16 = 10;
+const EXTENSION_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXTENSION_ALPN: u16 = 16;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+const EXTENSION_KEY_SHARE: u16 = 51;
+
+const ALERT_HANDSHAKE_FAILURE: u8 = 40;
+const ALERT_BAD_CERTIFICATE: u8 = 42;
+
+// The signature schemes we can verify, in order of preference. The PKCS#1 v1.5 ones are only
+// acceptable in certificates, never in CertificateVerify.
+const SIGNATURE_SCHEMES: [u16; 8] = [
+    0x0403, 0x0503, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601,
+];
+
+// A ServerHello with this random value is really a HelloRetryRequest.
+const HELLO_RETRY_REQUEST: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+pub(crate) fn handshake_error(message: &str) -> Error {
+    Error::TlsHandshake(message.to_string())
+}
+
+// A cursor over a handshake message body.
+struct Parser<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() < len {
+            return Err(handshake_error("truncated handshake message"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24(&mut self) -> Result<usize, Error> {
+        let bytes = self.bytes(3)?;
+        Ok((bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize)
+    }
+
+    fn vector_u8(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    fn vector_u16(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+
+    fn vector_u24(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.u24()?;
+        self.bytes(len)
+    }
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_vector_u8(out: &mut Vec<u8>, data: &[u8]) {
+    out.push(data.len() as u8);
+    out.extend_from_slice(data);
+}
+
+fn push_vector_u16(out: &mut Vec<u8>, data: &[u8]) {
+    push_u16(out, data.len() as u16);
+    out.extend_from_slice(data);
+}
+
+fn push_extension(out: &mut Vec<u8>, extension_type: u16, data: &[u8]) {
+    push_u16(out, extension_type);
+    push_vector_u16(out, data);
+}
+
+pub(crate) fn handshake_message(message_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(body.len() + 4);
+    message.push(message_type);
+    message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    message.extend_from_slice(body);
+    message
+}
+
+fn client_hello(
+    random: &[u8; 32],
+    session_id: &[u8; 32],
+    key_share: &[u8; 32],
+    server_name: &str,
+) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_u16(&mut body, 0x0303);
+    body.extend_from_slice(random);
+    push_vector_u8(&mut body, session_id);
+    push_vector_u16(&mut body, &TLS_AES_128_GCM_SHA256.to_be_bytes());
+    push_vector_u8(&mut body, &[0]);
+
+    let mut extensions = Vec::new();
+    // SNI carries host names only, never address literals.
+    if !is_ip_address(server_name) {
+        let mut name = vec![0u8];
+        push_vector_u16(&mut name, server_name.as_bytes());
+        let mut list = Vec::new();
+        push_vector_u16(&mut list, &name);
+        push_extension(&mut extensions, EXTENSION_SERVER_NAME, &list);
+    }
+    let mut groups = Vec::new();
+    push_vector_u16(&mut groups, &X25519.to_be_bytes());
+    push_extension(&mut extensions, EXTENSION_SUPPORTED_GROUPS, &groups);
+
+    let schemes: Vec<u8> = SIGNATURE_SCHEMES
+        .iter()
+        .flat_map(|scheme| scheme.to_be_bytes())
+        .collect();
+    let mut signature_algorithms = Vec::new();
+    push_vector_u16(&mut signature_algorithms, &schemes);
+    push_extension(
+        &mut extensions,
+        EXTENSION_SIGNATURE_ALGORITHMS,
+        &signature_algorithms,
+    );
+
+    let mut protocols = Vec::new();
+    push_vector_u8(&mut protocols, b"http/1.1");
+    let mut alpn = Vec::new();
+    push_vector_u16(&mut alpn, &protocols);
+    push_extension(&mut extensions, EXTENSION_ALPN, &alpn);
+
+    let mut versions = Vec::new();
+    push_vector_u8(&mut versions, &0x0304u16.to_be_bytes());
+    push_extension(&mut extensions, EXTENSION_SUPPORTED_VERSIONS, &versions);
+
+    let mut share = Vec::new();
+    push_u16(&mut share, X25519);
+    push_vector_u16(&mut share, key_share);
+    let mut shares = Vec::new();
+    push_vector_u16(&mut shares, &share);
+    push_extension(&mut extensions, EXTENSION_KEY_SHARE, &shares);
+
+    push_vector_u16(&mut body, &extensions);
+    handshake_message(CLIENT_HELLO, &body)
+}
+
+// Parses a ServerHello and returns the server's x25519 key share.
+fn parse_server_hello(body: &[u8], session_id: &[u8; 32]) -> Result<[u8; 32], Error> {
+    let mut parser = Parser::new(body);
+    parser.u16()?;
+    if parser.bytes(32)? == HELLO_RETRY_REQUEST {
+        return Err(handshake_error(
+            "the server asked for a key share this client does not offer",
+        ));
+    }
+    if parser.vector_u8()? != session_id {
+        return Err(handshake_error("the server did not echo the session id"));
+    }
+    if parser.u16()? != TLS_AES_128_GCM_SHA256 {
+        return Err(handshake_error(
+            "the server chose an unsupported cipher suite",
+        ));
+    }
+    if parser.u8()? != 0 {
+        return Err(handshake_error("the server chose a compression method"));
+    }
+
+    let mut version = None;
+    let mut key_share = None;
+    let mut extensions = Parser::new(parser.vector_u16()?);
+    while !extensions.is_empty() {
+        let extension_type = extensions.u16()?;
+        let mut data = Parser::new(extensions.vector_u16()?);
+        match extension_type {
+            EXTENSION_SUPPORTED_VERSIONS => version = Some(data.u16()?),
+            EXTENSION_KEY_SHARE => {
+                if data.u16()? != X25519 {
+                    return Err(handshake_error("the server chose an unsupported group"));
+                }
+                let key = data.vector_u16()?;
+                if key.len() != 32 {
+                    return Err(handshake_error("malformed key share"));
+                }
+                let mut share = [0u8; 32];
+                share.copy_from_slice(key);
+                key_share = Some(share);
+            }
+            _ => {}
+        }
+    }
+
+    if version != Some(0x0304) {
+        return Err(handshake_error("the server does not support TLS 1.3"));
+    }
+    key_share.ok_or_else(|| handshake_error("the server sent no key share"))
+}
+
+fn parse_certificates(body: &[u8]) -> Result<Vec<Certificate>, Error> {
+    let mut parser = Parser::new(body);
+    parser.vector_u8()?;
+    let mut entries = Parser::new(parser.vector_u24()?);
+    let mut certificates = Vec::new();
+    while !entries.is_empty() {
+        certificates.push(Certificate::from_der(entries.vector_u24()?)?);
+        entries.vector_u16()?;
+    }
+    Ok(certificates)
+}
+
+fn certificate_verify_scheme(scheme: u16) -> Option<SignatureScheme> {
+    match scheme {
+        0x0403 => Some(SignatureScheme::Ecdsa(HashAlgorithm::Sha256)),
+        0x0503 => Some(SignatureScheme::Ecdsa(HashAlgorithm::Sha384)),
+        0x0804 => Some(SignatureScheme::RsaPss(HashAlgorithm::Sha256)),
+        0x0805 => Some(SignatureScheme::RsaPss(HashAlgorithm::Sha384)),
+        0x0806 => Some(SignatureScheme::RsaPss(HashAlgorithm::Sha512)),
+        _ => None,
+    }
+}
+
+fn finished_mac(base_secret: &[u8], transcript: &Sha256) -> [u8; 32] {
+    let finished_key = expand_label(base_secret, "finished", &[], 32);
+    hmac_sha256(&finished_key, &transcript.clone().finish())
+}
+
+// Takes the first complete handshake message, header included, out of `buffer`. Messages may be
+// split across records or packed several to a record.
+pub(crate) fn take_handshake_message(buffer: &mut Vec<u8>) -> Option<(u8, Vec<u8>)> {
+    if buffer.len() < 4 {
+        return None;
+    }
+    let len = (buffer[1] as usize) << 16 | (buffer[2] as usize) << 8 | buffer[3] as usize;
+    if buffer.len() < 4 + len {
+        return None;
+    }
+    let message: Vec<u8> = buffer.drain(..4 + len).collect();
+    Some((message[0], message))
+}
+
+struct HandshakeReader {
+    buffer: Vec<u8>,
+}
+
+impl HandshakeReader {
+    fn next(&mut self, layer: &mut RecordLayer) -> Result<(u8, Vec<u8>), Error> {
+        loop {
+            if let Some(message) = take_handshake_message(&mut self.buffer) {
+                return Ok(message);
+            }
+            match layer.read_record()? {
+                Some((HANDSHAKE, data)) => self.buffer.extend_from_slice(&data),
+                Some((CHANGE_CIPHER_SPEC, _)) => {}
+                Some((ALERT, data)) => {
+                    return Err(Error::TlsHandshake(format!(
+                        "the server sent alert {}",
+                        data.get(1).copied().unwrap_or(0)
+                    )))
+                }
+                Some(_) => return Err(handshake_error("unexpected record during the handshake")),
+                None => return Err(handshake_error("the server closed the connection")),
+            }
+        }
+    }
+}
+
+// Runs a full TLS 1.3 handshake over `layer`, leaving it ready for application data.
+pub(crate) fn client_handshake(
+    layer: &mut RecordLayer,
+    server_name: &str,
+    roots: &[Certificate],
+    random: &mut dyn RandomSource,
+    now: u64,
+) -> Result<(), Error> {
+    let mut secrets = [0u8; 96];
+    random.fill(&mut secrets)?;
+    let mut client_random = [0u8; 32];
+    let mut session_id = [0u8; 32];
+    let mut private_key = [0u8; 32];
+    client_random.copy_from_slice(&secrets[..32]);
+    session_id.copy_from_slice(&secrets[32..64]);
+    private_key.copy_from_slice(&secrets[64..]);
+
+    let hello = client_hello(
+        &client_random,
+        &session_id,
+        &x25519::public_key(&private_key),
+        server_name,
+    );
+    layer.write_record(HANDSHAKE, &hello)?;
+    let mut transcript = Sha256::new();
+    transcript.update(&hello);
+
+    let mut reader = HandshakeReader { buffer: Vec::new() };
+    let (message_type, message) = reader.next(layer)?;
+    if message_type != SERVER_HELLO {
+        return Err(handshake_error("expected a ServerHello"));
+    }
+    let server_share = parse_server_hello(&message[4..], &session_id)?;
+    transcript.update(&message);
+
+    let shared_secret = x25519::x25519(&private_key, &server_share);
+    if shared_secret == [0u8; 32] {
+        return Err(handshake_error("the server sent a degenerate key share"));
+    }
+
+    // The key schedule from RFC 8446 section 7.1, without a pre-shared key.
+    let empty_hash = sha256(&[]);
+    let early_secret = hmac_sha256(&[0u8; 32], &[0u8; 32]);
+    let derived = expand_label(&early_secret, "derived", &empty_hash, 32);
+    let handshake_secret = hmac_sha256(&derived, &shared_secret);
+    let hello_hash = transcript.clone().finish();
+    let client_handshake_secret = expand_label(&handshake_secret, "c hs traffic", &hello_hash, 32);
+    let server_handshake_secret = expand_label(&handshake_secret, "s hs traffic", &hello_hash, 32);
+    layer.set_read_cipher(Cipher::new(&server_handshake_secret));
+    layer.set_write_cipher(Cipher::new(&client_handshake_secret));
+
+    let result = server_flight(
+        layer,
+        &mut reader,
+        &mut transcript,
+        server_name,
+        roots,
+        now,
+        &server_handshake_secret,
+    );
+    let certificate_request_context = match result {
+        Ok(context) => context,
+        Err(e) => {
+            // Tell the server why we are giving up; the connection is dead either way.
+            let description = match e {
+                Error::InvalidCertificate(_) => ALERT_BAD_CERTIFICATE,
+                _ => ALERT_HANDSHAKE_FAILURE,
+            };
+            let _ = layer.write_record(ALERT, &[2, description]);
+            return Err(e);
+        }
+    };
+
+    let derived = expand_label(&handshake_secret, "derived", &empty_hash, 32);
+    let master_secret = hmac_sha256(&derived, &[0u8; 32]);
+    let finished_hash = transcript.clone().finish();
+    let client_application_secret =
+        expand_label(&master_secret, "c ap traffic", &finished_hash, 32);
+    let server_application_secret =
+        expand_label(&master_secret, "s ap traffic", &finished_hash, 32);
+
+    layer.write_plain_record(CHANGE_CIPHER_SPEC, &[1])?;
+    if let Some(context) = certificate_request_context {
+        // We have no client certificate, so answer a request with an empty list.
+        let mut body = Vec::new();
+        push_vector_u8(&mut body, &context);
+        body.extend_from_slice(&[0, 0, 0]);
+        let message = handshake_message(CERTIFICATE, &body);
+        layer.write_record(HANDSHAKE, &message)?;
+        transcript.update(&message);
+    }
+    let verify_data = finished_mac(&client_handshake_secret, &transcript);
+    layer.write_record(HANDSHAKE, &handshake_message(FINISHED, &verify_data))?;
+
+    layer.set_read_cipher(Cipher::new(&server_application_secret));
+    layer.set_write_cipher(Cipher::new(&client_application_secret));
+    Ok(())
+}
+
+// Processes the server's encrypted flight up to and including its Finished message. Returns the
+// certificate request context if the server asked for a client certificate.
+fn server_flight(
+    layer: &mut RecordLayer,
+    reader: &mut HandshakeReader,
+    transcript: &mut Sha256,
+    server_name: &str,
+    roots: &[Certificate],
+    now: u64,
+    server_handshake_secret: &[u8],
+) -> Result<Option<Vec<u8>>, Error> {
+    let (message_type, message) = reader.next(layer)?;
+    if message_type != ENCRYPTED_EXTENSIONS {
+        return Err(handshake_error("expected EncryptedExtensions"));
+    }
+    transcript.update(&message);
+
+    let (mut message_type, mut message) = reader.next(layer)?;
+    let mut certificate_request_context = None;
+    if message_type == CERTIFICATE_REQUEST {
+        let context = Parser::new(&message[4..]).vector_u8()?.to_vec();
+        certificate_request_context = Some(context);
+        transcript.update(&message);
+        (message_type, message) = reader.next(layer)?;
+    }
+
+    if message_type != CERTIFICATE {
+        return Err(handshake_error("expected a Certificate"));
+    }
+    let chain = parse_certificates(&message[4..])?;
+    verify_chain(&chain, roots, server_name, now)?;
+    transcript.update(&message);
+
+    let (message_type, message) = reader.next(layer)?;
+    if message_type != CERTIFICATE_VERIFY {
+        return Err(handshake_error("expected a CertificateVerify"));
+    }
+    let mut parser = Parser::new(&message[4..]);
+    let scheme = certificate_verify_scheme(parser.u16()?)
+        .ok_or_else(|| handshake_error("the server used an unsupported signature scheme"))?;
+    let signature = parser.vector_u16()?;
+    let mut signed = Vec::with_capacity(130);
+    signed.extend_from_slice(&[0x20; 64]);
+    signed.extend_from_slice(b"TLS 1.3, server CertificateVerify\0");
+    signed.extend_from_slice(&transcript.clone().finish());
+    if !chain[0].public_key().verify(scheme, &signed, signature) {
+        return Err(handshake_error(
+            "the server's handshake signature is invalid",
+        ));
+    }
+    transcript.update(&message);
+
+    let (message_type, message) = reader.next(layer)?;
+    if message_type != FINISHED {
+        return Err(handshake_error("expected Finished"));
+    }
+    let expected = finished_mac(server_handshake_secret, transcript);
+    let difference = expected
+        .iter()
+        .zip(&message[4..])
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if message.len() != 4 + expected.len() || difference != 0 {
+        return Err(handshake_error("the server's Finished message is invalid"));
+    }
+    transcript.update(&message);
+
+    if !reader.buffer.is_empty() {
+        return Err(handshake_error(
+            "unexpected data after the server's Finished",
+        ));
+    }
+    Ok(certificate_request_context)
+}
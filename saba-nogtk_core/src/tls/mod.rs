@@ -0,0 +1,294 @@
+mod aes_gcm;
+mod bigint;
+mod der;
+mod ec;
+mod handshake;
+mod record;
+mod rsa;
+mod x25519;
+mod x509;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::clock::Clock;
+use crate::error::Error;
+use crate::random::RandomSource;
+use crate::tls::handshake::{
+    client_handshake, handshake_message, take_handshake_message, KEY_UPDATE, NEW_SESSION_TICKET,
+};
+use crate::tls::record::{RecordLayer, ALERT, APPLICATION_DATA, HANDSHAKE};
+use crate::tls::x509::{parse_pem, Certificate};
+use crate::transport::{Stream, Transport};
+
+const ALERT_CLOSE_NOTIFY: u8 = 0;
+
+// The certificate authorities a TlsClient trusts.
+#[derive(Clone, Default)]
+pub struct RootStore {
+    roots: Vec<Certificate>,
+}
+
+impl RootStore {
+    pub fn new() -> Self {
+        Self { roots: Vec::new() }
+    }
+
+    pub fn add_der(&mut self, der: &[u8]) -> Result<(), Error> {
+        self.roots.push(Certificate::from_der(der)?);
+        Ok(())
+    }
+
+    // Adds every certificate in a PEM bundle and returns how many were added. Certificates this
+    // client cannot use, such as ones with unsupported key types, are skipped.
+    pub fn add_pem(&mut self, pem: &str) -> Result<usize, Error> {
+        let mut added = 0;
+        for der in parse_pem(pem)? {
+            if self.add_der(&der).is_ok() {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+// A TLS 1.3 client offering TLS_AES_128_GCM_SHA256 with an x25519 key exchange.
+pub struct TlsClient<R: RandomSource, C: Clock> {
+    root_store: RootStore,
+    random: R,
+    clock: C,
+}
+
+impl<R: RandomSource, C: Clock> TlsClient<R, C> {
+    pub fn new(root_store: RootStore, random: R, clock: C) -> Self {
+        Self {
+            root_store,
+            random,
+            clock,
+        }
+    }
+
+    pub fn root_store(&self) -> RootStore {
+        self.root_store.clone()
+    }
+
+    pub fn set_root_store(&mut self, root_store: RootStore) {
+        self.root_store = root_store;
+    }
+
+    // Runs the handshake over `stream` and checks that the server's certificate is valid for
+    // `server_name`.
+    pub fn connect(
+        &mut self,
+        stream: Box<dyn Stream>,
+        server_name: &str,
+    ) -> Result<TlsStream, Error> {
+        let mut layer = RecordLayer::new(stream);
+        client_handshake(
+            &mut layer,
+            server_name,
+            &self.root_store.roots,
+            &mut self.random,
+            self.clock.now(),
+        )?;
+        Ok(TlsStream {
+            layer,
+            plaintext: Vec::new(),
+            handshake_buffer: Vec::new(),
+            close_notify_received: false,
+        })
+    }
+}
+
+pub struct TlsStream {
+    layer: RecordLayer,
+    plaintext: Vec<u8>,
+    handshake_buffer: Vec<u8>,
+    close_notify_received: bool,
+}
+
+impl TlsStream {
+    // Handles messages the server may send at any time after the handshake.
+    fn process_handshake_messages(&mut self) -> Result<(), Error> {
+        while let Some((message_type, message)) = take_handshake_message(&mut self.handshake_buffer)
+        {
+            match message_type {
+                // Session resumption is not supported, so tickets are simply dropped.
+                NEW_SESSION_TICKET => {}
+                KEY_UPDATE => {
+                    if !self.handshake_buffer.is_empty() {
+                        return Err(Error::Network(
+                            "TLS KeyUpdate was not aligned to a record".to_string(),
+                        ));
+                    }
+                    self.layer.update_read_cipher();
+                    if message.get(4) == Some(&1) {
+                        self.layer
+                            .write_record(HANDSHAKE, &handshake_message(KEY_UPDATE, &[0]))?;
+                        self.layer.update_write_cipher();
+                    }
+                }
+                _ => {
+                    return Err(Error::Network(format!(
+                        "unexpected TLS handshake message {}",
+                        message_type
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Stream for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        while self.plaintext.is_empty() {
+            match self.layer.read_record()? {
+                Some((APPLICATION_DATA, data)) => self.plaintext = data,
+                Some((HANDSHAKE, data)) => {
+                    self.handshake_buffer.extend_from_slice(&data);
+                    self.process_handshake_messages()?;
+                }
+                Some((ALERT, data)) => {
+                    if data.get(1) == Some(&ALERT_CLOSE_NOTIFY) {
+                        self.close_notify_received = true;
+                        return Ok(0);
+                    }
+                    return Err(Error::Network(format!(
+                        "the server sent TLS alert {}",
+                        data.get(1).copied().unwrap_or(0)
+                    )));
+                }
+                Some(_) => {}
+                None if self.close_notify_received => return Ok(0),
+                // Only close_notify proves that the server meant to end the data here. Without
+                // it an attacker could have cut the connection short.
+                None => {
+                    return Err(Error::Network(
+                        "the connection was closed without a TLS close_notify".to_string(),
+                    ))
+                }
+            }
+        }
+        let n = core::cmp::min(buf.len(), self.plaintext.len());
+        buf[..n].copy_from_slice(&self.plaintext[..n]);
+        self.plaintext.drain(..n);
+        Ok(n)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if self.layer.is_closed() {
+            return Err(Error::Network("the TLS connection is closed".to_string()));
+        }
+        self.layer.write_record(APPLICATION_DATA, buf)?;
+        Ok(buf.len())
+    }
+}
+
+// Wraps another transport so that `secure` can run TLS over the streams it opens.
+pub struct TlsTransport<T: Transport, R: RandomSource, C: Clock> {
+    inner: T,
+    client: TlsClient<R, C>,
+}
+
+impl<T: Transport, R: RandomSource, C: Clock> TlsTransport<T, R, C> {
+    pub fn new(inner: T, client: TlsClient<R, C>) -> Self {
+        Self { inner, client }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn client_mut(&mut self) -> &mut TlsClient<R, C> {
+        &mut self.client
+    }
+}
+
+impl<T: Transport, R: RandomSource, C: Clock> Transport for TlsTransport<T, R, C> {
+    fn connect(&mut self, host: &str, port: u16) -> Result<Box<dyn Stream>, Error> {
+        self.inner.connect(host, port)
+    }
+
+    fn secure(
+        &mut self,
+        stream: Box<dyn Stream>,
+        server_name: &str,
+    ) -> Result<Box<dyn Stream>, Error> {
+        Ok(Box::new(self.client.connect(stream, server_name)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::record::Cipher;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    struct LoopbackStream {
+        data: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Stream for LoopbackStream {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let mut data = self.data.borrow_mut();
+            let n = core::cmp::min(buf.len(), data.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            data.drain(..n);
+            Ok(n)
+        }
+
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.data.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    // A stream that reads back what the server side wrote with `close_notify` or without.
+    fn stream_with(data: &[u8], close_notify: bool) -> TlsStream {
+        let mut layer = RecordLayer::new(Box::new(LoopbackStream {
+            data: Rc::new(RefCell::new(Vec::new())),
+        }));
+        layer.set_write_cipher(Cipher::new(&[3u8; 32]));
+        layer.set_read_cipher(Cipher::new(&[3u8; 32]));
+        layer.write_record(APPLICATION_DATA, data).unwrap();
+        if close_notify {
+            layer.write_record(ALERT, &[1, ALERT_CLOSE_NOTIFY]).unwrap();
+        }
+        TlsStream {
+            layer,
+            plaintext: Vec::new(),
+            handshake_buffer: Vec::new(),
+            close_notify_received: false,
+        }
+    }
+
+    #[test]
+    fn test_read_until_close_notify() {
+        let mut stream = stream_with(b"complete", true);
+        let mut buf = [0u8; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 8);
+        assert_eq!(&buf[..8], b"complete");
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_truncated_connection() {
+        let mut stream = stream_with(b"partial", false);
+        let mut buf = [0u8; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 7);
+        assert!(stream.read(&mut buf).is_err());
+    }
+}
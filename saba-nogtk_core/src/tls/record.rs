@@ -0,0 +1,317 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::hash::hmac_sha256;
+use crate::tls::aes_gcm::AesGcm;
+use crate::transport::Stream;
+
+pub(crate) const CHANGE_CIPHER_SPEC: u8 = 20;
+pub(crate) const ALERT: u8 = 21;
+pub(crate) const HANDSHAKE: u8 = 22;
+pub(crate) const APPLICATION_DATA: u8 = 23;
+
+const MAX_FRAGMENT: usize = 1 << 14;
+// A protected record may carry up to 256 bytes of padding and tag on top of the plaintext.
+const MAX_CIPHERTEXT: usize = MAX_FRAGMENT + 256;
+
+// HKDF-Expand-Label from RFC 8446 section 7.1, always over SHA-256.
+pub(crate) fn expand_label(secret: &[u8], label: &str, context: &[u8], len: usize) -> Vec<u8> {
+    let mut info = Vec::new();
+    info.extend_from_slice(&(len as u16).to_be_bytes());
+    info.push((6 + label.len()) as u8);
+    info.extend_from_slice(b"tls13 ");
+    info.extend_from_slice(label.as_bytes());
+    info.push(context.len() as u8);
+    info.extend_from_slice(context);
+
+    let mut out = Vec::with_capacity(len + 32);
+    let mut block = Vec::new();
+    let mut counter = 1u8;
+    while out.len() < len {
+        let mut input = block.clone();
+        input.extend_from_slice(&info);
+        input.push(counter);
+        block = hmac_sha256(secret, &input).to_vec();
+        out.extend_from_slice(&block);
+        counter += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+// The AEAD state for one direction of traffic.
+pub(crate) struct Cipher {
+    secret: Vec<u8>,
+    aead: AesGcm,
+    iv: [u8; 12],
+    sequence: u64,
+}
+
+impl Cipher {
+    pub(crate) fn new(secret: &[u8]) -> Self {
+        let mut key = [0u8; 16];
+        key.copy_from_slice(&expand_label(secret, "key", &[], 16));
+        let mut iv = [0u8; 12];
+        iv.copy_from_slice(&expand_label(secret, "iv", &[], 12));
+        Self {
+            secret: secret.to_vec(),
+            aead: AesGcm::new(&key),
+            iv,
+            sequence: 0,
+        }
+    }
+
+    // The keys that follow a KeyUpdate message.
+    pub(crate) fn next_generation(&self) -> Self {
+        Self::new(&expand_label(&self.secret, "traffic upd", &[], 32))
+    }
+
+    fn nonce(&mut self) -> [u8; 12] {
+        let mut nonce = self.iv;
+        for (n, s) in nonce[4..].iter_mut().zip(self.sequence.to_be_bytes()) {
+            *n ^= s;
+        }
+        self.sequence += 1;
+        nonce
+    }
+}
+
+pub(crate) struct RecordLayer {
+    stream: Box<dyn Stream>,
+    buffer: Vec<u8>,
+    read_cipher: Option<Cipher>,
+    write_cipher: Option<Cipher>,
+    closed: bool,
+}
+
+impl RecordLayer {
+    pub(crate) fn new(stream: Box<dyn Stream>) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            read_cipher: None,
+            write_cipher: None,
+            closed: false,
+        }
+    }
+
+    pub(crate) fn set_read_cipher(&mut self, cipher: Cipher) {
+        self.read_cipher = Some(cipher);
+    }
+
+    pub(crate) fn set_write_cipher(&mut self, cipher: Cipher) {
+        self.write_cipher = Some(cipher);
+    }
+
+    pub(crate) fn update_read_cipher(&mut self) {
+        if let Some(cipher) = &self.read_cipher {
+            self.read_cipher = Some(cipher.next_generation());
+        }
+    }
+
+    pub(crate) fn update_write_cipher(&mut self) {
+        if let Some(cipher) = &self.write_cipher {
+            self.write_cipher = Some(cipher.next_generation());
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // Fills the buffer to at least `len` bytes, returning false on a clean end of stream.
+    fn fill(&mut self, len: usize) -> Result<bool, Error> {
+        while self.buffer.len() < len {
+            let mut chunk = [0u8; 4096];
+            let n = self.stream.read(&mut chunk)?;
+            if n == 0 {
+                return Ok(false);
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+        Ok(true)
+    }
+
+    // Reads the next record and returns its content type and plaintext, or None once the peer
+    // has closed the connection.
+    pub(crate) fn read_record(&mut self) -> Result<Option<(u8, Vec<u8>)>, Error> {
+        if self.closed {
+            return Ok(None);
+        }
+        if !self.fill(5)? {
+            self.closed = true;
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+            return Err(Error::Network("truncated TLS record".to_string()));
+        }
+        let content_type = self.buffer[0];
+        let len = u16::from_be_bytes([self.buffer[3], self.buffer[4]]) as usize;
+        if len > MAX_CIPHERTEXT {
+            return Err(Error::Network("oversized TLS record".to_string()));
+        }
+        if !self.fill(5 + len)? {
+            self.closed = true;
+            return Err(Error::Network("truncated TLS record".to_string()));
+        }
+        let header: Vec<u8> = self.buffer[..5].to_vec();
+        let fragment: Vec<u8> = self.buffer[5..5 + len].to_vec();
+        self.buffer.drain(..5 + len);
+
+        // ChangeCipherSpec only exists for middlebox compatibility and is never protected.
+        if content_type == CHANGE_CIPHER_SPEC {
+            return Ok(Some((content_type, fragment)));
+        }
+        let Some(cipher) = self.read_cipher.as_mut() else {
+            return Ok(Some((content_type, fragment)));
+        };
+        if content_type != APPLICATION_DATA {
+            return Err(Error::Network(format!(
+                "unexpected unprotected TLS record of type {}",
+                content_type
+            )));
+        }
+
+        let nonce = cipher.nonce();
+        let mut plaintext = cipher
+            .aead
+            .open(&nonce, &header, &fragment)
+            .ok_or_else(|| Error::Network("TLS record failed authentication".to_string()))?;
+        // The real content type is the last non-zero byte; zeros after it are padding.
+        let end = plaintext
+            .iter()
+            .rposition(|&b| b != 0)
+            .ok_or_else(|| Error::Network("TLS record has no content type".to_string()))?;
+        let inner_type = plaintext[end];
+        plaintext.truncate(end);
+        Ok(Some((inner_type, plaintext)))
+    }
+
+    pub(crate) fn write_record(&mut self, content_type: u8, data: &[u8]) -> Result<(), Error> {
+        for fragment in data.chunks(MAX_FRAGMENT) {
+            let mut record = Vec::with_capacity(fragment.len() + 22);
+            match self.write_cipher.as_mut() {
+                Some(cipher) => {
+                    let mut inner = fragment.to_vec();
+                    inner.push(content_type);
+                    let len = inner.len() + 16;
+                    record.extend_from_slice(&[APPLICATION_DATA, 0x03, 0x03]);
+                    record.extend_from_slice(&(len as u16).to_be_bytes());
+                    let nonce = cipher.nonce();
+                    let sealed = cipher.aead.seal(&nonce, &record, &inner);
+                    record.extend_from_slice(&sealed);
+                }
+                None => {
+                    record.extend_from_slice(&[content_type, 0x03, 0x03]);
+                    record.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+                    record.extend_from_slice(fragment);
+                }
+            }
+            self.stream.write_all(&record)?;
+        }
+        Ok(())
+    }
+
+    // Writes a record that bypasses the cipher, as the compatibility ChangeCipherSpec must.
+    pub(crate) fn write_plain_record(
+        &mut self,
+        content_type: u8,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let mut record = Vec::with_capacity(data.len() + 5);
+        record.extend_from_slice(&[content_type, 0x03, 0x03]);
+        record.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        record.extend_from_slice(data);
+        self.stream.write_all(&record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::to_hex;
+    use alloc::rc::Rc;
+    use alloc::string::String;
+    use core::cell::RefCell;
+
+    struct LoopbackStream {
+        data: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Stream for LoopbackStream {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let mut data = self.data.borrow_mut();
+            let n = core::cmp::min(buf.len(), data.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            data.drain(..n);
+            Ok(n)
+        }
+
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.data.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_expand_label() {
+        // The client handshake key derivation from RFC 8448 section 3.
+        let secret = (0..64)
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(
+                    &"b3eddb126e067f35a780b3abf45e2d8f3b1a950738f52e9600746a0e27a55a21"[i..i + 2],
+                    16,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            to_hex(&expand_label(&secret, "key", &[], 16)),
+            String::from("dbfaa693d1762c5b666af5d950258d01")
+        );
+        assert_eq!(
+            to_hex(&expand_label(&secret, "iv", &[], 12)),
+            String::from("5bd3c71b836e0b76bb73265f")
+        );
+    }
+
+    #[test]
+    fn test_protected_round_trip() {
+        let data = Rc::new(RefCell::new(Vec::new()));
+        let mut layer = RecordLayer::new(Box::new(LoopbackStream { data: data.clone() }));
+        layer.set_write_cipher(Cipher::new(&[7u8; 32]));
+        layer.set_read_cipher(Cipher::new(&[7u8; 32]));
+
+        layer.write_record(HANDSHAKE, b"hello").unwrap();
+        layer.write_record(APPLICATION_DATA, &[0u8; 20000]).unwrap();
+        assert_eq!(data.borrow()[0], APPLICATION_DATA);
+
+        assert_eq!(
+            layer.read_record().unwrap(),
+            Some((HANDSHAKE, b"hello".to_vec()))
+        );
+        let (content_type, first) = layer.read_record().unwrap().unwrap();
+        assert_eq!(
+            (content_type, first.len()),
+            (APPLICATION_DATA, MAX_FRAGMENT)
+        );
+        let (_, second) = layer.read_record().unwrap().unwrap();
+        assert_eq!(second.len(), 20000 - MAX_FRAGMENT);
+        assert_eq!(layer.read_record().unwrap(), None);
+    }
+
+    #[test]
+    fn test_tampered_record() {
+        let data = Rc::new(RefCell::new(Vec::new()));
+        let mut layer = RecordLayer::new(Box::new(LoopbackStream { data: data.clone() }));
+        layer.set_write_cipher(Cipher::new(&[1u8; 32]));
+        layer.set_read_cipher(Cipher::new(&[1u8; 32]));
+        layer.write_record(APPLICATION_DATA, b"secret").unwrap();
+        data.borrow_mut()[7] ^= 0x40;
+        assert!(layer.read_record().is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use alloc::vec::Vec;
+
+use crate::hash::{sha256, sha384, sha512};
+use crate::tls::bigint::BigUint;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub(crate) fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => sha256(data).to_vec(),
+            HashAlgorithm::Sha384 => sha384(data).to_vec(),
+            HashAlgorithm::Sha512 => sha512(data).to_vec(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+
+    // The DER prefix of the DigestInfo structure that PKCS#1 v1.5 signs.
+    fn digest_info_prefix(&self) -> &'static [u8] {
+        match self {
+            HashAlgorithm::Sha256 => &[
+                0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+                0x01, 0x05, 0x00, 0x04, 0x20,
+            ],
+            HashAlgorithm::Sha384 => &[
+                0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+                0x02, 0x05, 0x00, 0x04, 0x30,
+            ],
+            HashAlgorithm::Sha512 => &[
+                0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+                0x03, 0x05, 0x00, 0x04, 0x40,
+            ],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct PublicKey {
+    n: BigUint,
+    e: BigUint,
+}
+
+impl PublicKey {
+    pub(crate) fn new(modulus: &[u8], exponent: &[u8]) -> Option<Self> {
+        let n = BigUint::from_bytes_be(modulus);
+        let e = BigUint::from_bytes_be(exponent);
+        // Anything smaller than 1024 bits is not worth trusting.
+        if n.bits() < 1024 || e.is_zero() {
+            return None;
+        }
+        Some(Self { n, e })
+    }
+
+    fn len(&self) -> usize {
+        self.n.bits().div_ceil(8)
+    }
+
+    // Applies the public key to the signature, returning the encoded message.
+    fn open(&self, signature: &[u8]) -> Option<Vec<u8>> {
+        if signature.len() != self.len() {
+            return None;
+        }
+        let s = BigUint::from_bytes_be(signature);
+        if s >= self.n {
+            return None;
+        }
+        s.mod_pow(&self.e, &self.n).to_bytes_be(self.len())
+    }
+
+    pub(crate) fn verify_pkcs1(
+        &self,
+        hash: HashAlgorithm,
+        message: &[u8],
+        signature: &[u8],
+    ) -> bool {
+        let Some(encoded) = self.open(signature) else {
+            return false;
+        };
+        let prefix = hash.digest_info_prefix();
+        let t_len = prefix.len() + hash.len();
+        if encoded.len() < t_len + 11 {
+            return false;
+        }
+
+        // EM = 0x00 || 0x01 || 0xff... || 0x00 || DigestInfo
+        let mut expected = Vec::with_capacity(encoded.len());
+        expected.extend_from_slice(&[0x00, 0x01]);
+        expected.resize(encoded.len() - t_len - 1, 0xff);
+        expected.push(0x00);
+        expected.extend_from_slice(prefix);
+        expected.extend_from_slice(&hash.digest(message));
+        encoded == expected
+    }
+
+    // RSASSA-PSS with MGF1 over the same hash and a salt as long as the hash, which is what
+    // TLS 1.3 requires.
+    pub(crate) fn verify_pss(&self, hash: HashAlgorithm, message: &[u8], signature: &[u8]) -> bool {
+        let Some(encoded) = self.open(signature) else {
+            return false;
+        };
+        let h_len = hash.len();
+        let em_bits = self.n.bits() - 1;
+        let em_len = em_bits.div_ceil(8);
+        // The encoded message fits in em_len bytes, so any extra leading byte must be zero.
+        let (leading, em) = encoded.split_at(encoded.len() - em_len);
+        if leading.iter().any(|&b| b != 0) || em_len < 2 * h_len + 2 || em[em_len - 1] != 0xbc {
+            return false;
+        }
+
+        let (masked_db, rest) = em.split_at(em_len - h_len - 1);
+        let h = &rest[..h_len];
+        let unused_bits = 8 * em_len - em_bits;
+        if unused_bits > 0 && masked_db[0] >> (8 - unused_bits) != 0 {
+            return false;
+        }
+
+        let mask = mgf1(hash, h, masked_db.len());
+        let mut db: Vec<u8> = masked_db.iter().zip(&mask).map(|(a, b)| a ^ b).collect();
+        if unused_bits > 0 {
+            db[0] &= 0xff >> unused_bits;
+        }
+
+        let padding_len = em_len - 2 * h_len - 2;
+        if db[..padding_len].iter().any(|&b| b != 0) || db[padding_len] != 0x01 {
+            return false;
+        }
+        let salt = &db[padding_len + 1..];
+
+        let mut m_prime = Vec::with_capacity(8 + 2 * h_len);
+        m_prime.extend_from_slice(&[0u8; 8]);
+        m_prime.extend_from_slice(&hash.digest(message));
+        m_prime.extend_from_slice(salt);
+        hash.digest(&m_prime) == h
+    }
+}
+
+fn mgf1(hash: HashAlgorithm, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + hash.len());
+    let mut counter = 0u32;
+    while mask.len() < len {
+        let mut block = seed.to_vec();
+        block.extend_from_slice(&counter.to_be_bytes());
+        mask.extend_from_slice(&hash.digest(&block));
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn key() -> PublicKey {
+        PublicKey::new(
+            &from_hex("b0e1a34627df96bfb62cc0b9f2cf6661024ab138eca24cf5565bb2aa31422d22bc24ba5ac40871e3cdd9665f459ea989264e86605132abe024fa4764cd45b04b0931c43962699e14b6c92fb7d35f35fdbb72d8c76b5865b619f67290d612b0c1994a37a644b427d2126edf95f0e4d8f000e3fe165d1843d5f312c78dae41edb9"),
+            &[0x01, 0x00, 0x01],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_pkcs1() {
+        let signature = from_hex("6bfa8ed36b2de660445ffba06c1466c057dd9cd90f98533b9add49e8ef0ad2b27d7a74f07aa8a3d661eff5368a24b2907685e792691a6f3cccbe23e201b73ea60e1a0e4775d6810e3b8864145b2f74ac4d5726fd25f0a4cf5c075d1d89ec63978e8a0c2186608c4e7084f1708cba7ad6a7fb92f91f825a8a5afa81b4ee3ca960");
+        assert!(key().verify_pkcs1(HashAlgorithm::Sha256, b"sample", &signature));
+        assert!(!key().verify_pkcs1(HashAlgorithm::Sha256, b"sample2", &signature));
+        assert!(!key().verify_pkcs1(HashAlgorithm::Sha384, b"sample", &signature));
+        assert!(!key().verify_pss(HashAlgorithm::Sha256, b"sample", &signature));
+    }
+
+    #[test]
+    fn test_verify_pss() {
+        let sha256_signature = from_hex("6f6e7760b34ee86ab9c9c628d757c0c2a7b042f419f965df7b5bf51b8265342767c4dbc89e72ee98646cfd992fb7486889e8c839e9b96c78149f6f2c507252ab6bf80c3eef058f092fad5841f44de8aa58660c2e2624f3672c358b08fd494478713e1878bce89a85cc106c14d663b1245e4f29c5d94b44a4e053eb87345db12b");
+        assert!(key().verify_pss(HashAlgorithm::Sha256, b"sample", &sha256_signature));
+        assert!(!key().verify_pss(HashAlgorithm::Sha256, b"Sample", &sha256_signature));
+
+        let sha384_signature = from_hex("9b01a91121b4a0d2817b236cbe01439b2dd29c0cbe1b3749f34394fb281c0a9dbeb1078abf368642e00374f7b46935c9b5c1d8b31d9506a1c4e9e85e0caee942c3aed0b4e5b0ae02d09ada479b197ab5bc6f40ee4d1d5e3ef91d3be738bd93a46926082f0cdf0a8fcff905d8651218bd485f204bd8ab823e31a9d09cb622c2ed");
+        assert!(key().verify_pss(HashAlgorithm::Sha384, b"sample", &sha384_signature));
+        assert!(!key().verify_pss(HashAlgorithm::Sha256, b"sample", &sha384_signature));
+    }
+
+    #[test]
+    fn test_rejects_short_modulus() {
+        assert!(PublicKey::new(&[0xff; 64], &[0x03]).is_none());
+    }
+}
@@ -0,0 +1,192 @@
+// X25519 key agreement (RFC 7748), following TweetNaCl's field arithmetic: an element of
+// GF(2^255 - 19) is sixteen 16-bit limbs held in i64s so that products never overflow.
+
+type Element = [i64; 16];
+
+const A24: Element = [0xdb41, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+fn carry(element: &mut Element) {
+    for i in 0..16 {
+        element[i] += 1 << 16;
+        let c = element[i] >> 16;
+        if i < 15 {
+            element[i + 1] += c - 1;
+        } else {
+            element[0] += 38 * (c - 1);
+        }
+        element[i] -= c << 16;
+    }
+}
+
+// Swaps p and q when bit is 1, without branching on it.
+fn swap(p: &mut Element, q: &mut Element, bit: i64) {
+    let mask = !(bit - 1);
+    for i in 0..16 {
+        let t = mask & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+fn pack(element: &Element) -> [u8; 32] {
+    let mut t = *element;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+    let mut m = [0i64; 16];
+    for _ in 0..2 {
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        swap(&mut t, &mut m, 1 - b);
+    }
+    let mut out = [0u8; 32];
+    for i in 0..16 {
+        out[2 * i] = t[i] as u8;
+        out[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    out
+}
+
+fn unpack(bytes: &[u8; 32]) -> Element {
+    let mut element = [0i64; 16];
+    for i in 0..16 {
+        element[i] = bytes[2 * i] as i64 + ((bytes[2 * i + 1] as i64) << 8);
+    }
+    element[15] &= 0x7fff;
+    element
+}
+
+fn add(a: &Element, b: &Element) -> Element {
+    let mut out = [0i64; 16];
+    for i in 0..16 {
+        out[i] = a[i] + b[i];
+    }
+    out
+}
+
+fn sub(a: &Element, b: &Element) -> Element {
+    let mut out = [0i64; 16];
+    for i in 0..16 {
+        out[i] = a[i] - b[i];
+    }
+    out
+}
+
+fn mul(a: &Element, b: &Element) -> Element {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut out = [0i64; 16];
+    out.copy_from_slice(&t[..16]);
+    carry(&mut out);
+    carry(&mut out);
+    out
+}
+
+fn invert(element: &Element) -> Element {
+    // a^(p - 2), where the exponent's bits are all set except bits 2 and 4.
+    let mut c = *element;
+    for i in (0..254).rev() {
+        c = mul(&c, &c);
+        if i != 2 && i != 4 {
+            c = mul(&c, element);
+        }
+    }
+    c
+}
+
+pub(crate) fn x25519(scalar: &[u8; 32], point: &[u8; 32]) -> [u8; 32] {
+    let mut z = *scalar;
+    z[31] = (z[31] & 127) | 64;
+    z[0] &= 248;
+
+    let x = unpack(point);
+    let mut a = [0i64; 16];
+    let mut b = x;
+    let mut c = [0i64; 16];
+    let mut d = [0i64; 16];
+    a[0] = 1;
+    d[0] = 1;
+
+    for i in (0..255).rev() {
+        let bit = ((z[i >> 3] >> (i & 7)) & 1) as i64;
+        swap(&mut a, &mut b, bit);
+        swap(&mut c, &mut d, bit);
+        let e = add(&a, &c);
+        a = sub(&a, &c);
+        c = add(&b, &d);
+        b = sub(&b, &d);
+        d = mul(&e, &e);
+        let f = mul(&a, &a);
+        a = mul(&c, &a);
+        c = mul(&b, &e);
+        let e = add(&a, &c);
+        a = sub(&a, &c);
+        b = mul(&a, &a);
+        c = sub(&d, &f);
+        a = mul(&c, &A24);
+        a = add(&a, &d);
+        c = mul(&c, &a);
+        a = mul(&d, &f);
+        d = mul(&b, &x);
+        b = mul(&e, &e);
+        swap(&mut a, &mut b, bit);
+        swap(&mut c, &mut d, bit);
+    }
+
+    pack(&mul(&a, &invert(&c)))
+}
+
+pub(crate) fn public_key(private_key: &[u8; 32]) -> [u8; 32] {
+    let mut base = [0u8; 32];
+    base[0] = 9;
+    x25519(private_key, &base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_rfc7748_function_vector() {
+        let scalar = from_hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let point = from_hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        assert_eq!(
+            x25519(&scalar, &point),
+            from_hex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552")
+        );
+    }
+
+    #[test]
+    fn test_rfc7748_key_agreement() {
+        let alice = from_hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = from_hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        assert_eq!(
+            public_key(&alice),
+            from_hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")
+        );
+        let shared = from_hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+        assert_eq!(x25519(&alice, &public_key(&bob)), shared);
+        assert_eq!(x25519(&bob, &public_key(&alice)), shared);
+    }
+}
@@ -0,0 +1,679 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::cookie::days_from_civil;
+use crate::error::Error;
+use crate::tls::bigint::BigUint;
+use crate::tls::der::{self, Element, Reader};
+use crate::tls::ec;
+use crate::tls::rsa::{self, HashAlgorithm};
+
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_RSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const OID_SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const OID_SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const OID_ECDSA_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+const OID_SUBJECT_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x0e];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const OID_CERTIFICATE_POLICIES: &[u8] = &[0x55, 0x1d, 0x20];
+const OID_AUTHORITY_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x23];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+
+// The longest chain of intermediates we are willing to walk.
+const MAX_CHAIN_DEPTH: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum SignatureScheme {
+    RsaPkcs1(HashAlgorithm),
+    RsaPss(HashAlgorithm),
+    Ecdsa(HashAlgorithm),
+}
+
+#[derive(Clone)]
+pub(crate) enum PublicKey {
+    Rsa(rsa::PublicKey),
+    Ec(ec::PublicKey),
+}
+
+impl PublicKey {
+    pub(crate) fn verify(&self, scheme: SignatureScheme, message: &[u8], signature: &[u8]) -> bool {
+        match (self, scheme) {
+            (PublicKey::Rsa(key), SignatureScheme::RsaPkcs1(hash)) => {
+                key.verify_pkcs1(hash, message, signature)
+            }
+            (PublicKey::Rsa(key), SignatureScheme::RsaPss(hash)) => {
+                key.verify_pss(hash, message, signature)
+            }
+            (PublicKey::Ec(key), SignatureScheme::Ecdsa(hash)) => {
+                // ECDSA signatures are a DER SEQUENCE of the two integers r and s.
+                let mut reader = Reader::new(signature);
+                let Some(sequence) = reader.expect(der::SEQUENCE) else {
+                    return false;
+                };
+                let mut values = sequence.reader();
+                let (Some(r), Some(s)) = (
+                    values.next().and_then(|e| e.unsigned_integer()),
+                    values.next().and_then(|e| e.unsigned_integer()),
+                ) else {
+                    return false;
+                };
+                if !reader.is_empty() || !values.is_empty() {
+                    return false;
+                }
+                key.verify(
+                    &hash.digest(message),
+                    &BigUint::from_bytes_be(r),
+                    &BigUint::from_bytes_be(s),
+                )
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Certificate {
+    tbs: Vec<u8>,
+    signature_scheme: SignatureScheme,
+    signature: Vec<u8>,
+    // Names are compared by their DER encoding.
+    issuer: Vec<u8>,
+    subject: Vec<u8>,
+    not_before: i64,
+    not_after: i64,
+    public_key: PublicKey,
+    is_ca: bool,
+    path_len: Option<usize>,
+    // None when the certificate has no keyUsage extension, which permits every use.
+    can_sign_certificates: Option<bool>,
+    dns_names: Vec<String>,
+    ip_addresses: Vec<Vec<u8>>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidCertificate(message.to_string())
+}
+
+fn hash_from_oid(oid: &[u8]) -> Option<HashAlgorithm> {
+    match oid {
+        OID_SHA256 => Some(HashAlgorithm::Sha256),
+        OID_SHA384 => Some(HashAlgorithm::Sha384),
+        OID_SHA512 => Some(HashAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
+fn parse_signature_algorithm(element: Element) -> Result<SignatureScheme, Error> {
+    let mut reader = element.reader();
+    let oid = reader
+        .expect(der::OID)
+        .ok_or_else(|| invalid("malformed signature algorithm"))?;
+    let scheme = match oid.content {
+        OID_SHA256_WITH_RSA => SignatureScheme::RsaPkcs1(HashAlgorithm::Sha256),
+        OID_SHA384_WITH_RSA => SignatureScheme::RsaPkcs1(HashAlgorithm::Sha384),
+        OID_SHA512_WITH_RSA => SignatureScheme::RsaPkcs1(HashAlgorithm::Sha512),
+        OID_ECDSA_SHA256 => SignatureScheme::Ecdsa(HashAlgorithm::Sha256),
+        OID_ECDSA_SHA384 => SignatureScheme::Ecdsa(HashAlgorithm::Sha384),
+        OID_ECDSA_SHA512 => SignatureScheme::Ecdsa(HashAlgorithm::Sha512),
+        OID_RSA_PSS => {
+            // RSASSA-PSS-params starts with [0] hashAlgorithm; SHA-1 is the default and unsupported.
+            let hash = reader
+                .expect(der::SEQUENCE)
+                .and_then(|params| params.reader().expect(0xa0))
+                .and_then(|hash| hash.reader().expect(der::SEQUENCE))
+                .and_then(|algorithm| algorithm.reader().expect(der::OID))
+                .and_then(|oid| hash_from_oid(oid.content))
+                .ok_or_else(|| invalid("unsupported RSA-PSS parameters"))?;
+            SignatureScheme::RsaPss(hash)
+        }
+        _ => return Err(invalid("unsupported signature algorithm")),
+    };
+    Ok(scheme)
+}
+
+fn parse_public_key(element: Element) -> Result<PublicKey, Error> {
+    let mut reader = element.reader();
+    let mut algorithm = reader
+        .expect(der::SEQUENCE)
+        .ok_or_else(|| invalid("malformed public key"))?
+        .reader();
+    let key_bytes = reader
+        .expect(der::BIT_STRING)
+        .and_then(|e| e.bit_string_bytes())
+        .ok_or_else(|| invalid("malformed public key"))?;
+    let oid = algorithm
+        .expect(der::OID)
+        .ok_or_else(|| invalid("malformed public key"))?;
+
+    match oid.content {
+        OID_RSA_ENCRYPTION => {
+            let key = Reader::new(key_bytes)
+                .expect(der::SEQUENCE)
+                .ok_or_else(|| invalid("malformed RSA public key"))?;
+            let mut values = key.reader();
+            let modulus = values.next().and_then(|e| e.unsigned_integer());
+            let exponent = values.next().and_then(|e| e.unsigned_integer());
+            match (modulus, exponent) {
+                (Some(modulus), Some(exponent)) => rsa::PublicKey::new(modulus, exponent)
+                    .map(PublicKey::Rsa)
+                    .ok_or_else(|| invalid("RSA key is too weak")),
+                _ => Err(invalid("malformed RSA public key")),
+            }
+        }
+        OID_EC_PUBLIC_KEY => {
+            let curve = match algorithm.expect(der::OID).map(|e| e.content) {
+                Some(OID_P256) => ec::Curve::P256,
+                Some(OID_P384) => ec::Curve::P384,
+                _ => return Err(invalid("unsupported elliptic curve")),
+            };
+            ec::PublicKey::from_sec1(curve, key_bytes)
+                .map(PublicKey::Ec)
+                .ok_or_else(|| invalid("malformed EC public key"))
+        }
+        _ => Err(invalid("unsupported public key algorithm")),
+    }
+}
+
+fn parse_digits(bytes: &[u8]) -> Option<i64> {
+    let mut value = 0i64;
+    for &b in bytes {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + (b - b'0') as i64;
+    }
+    Some(value)
+}
+
+// Converts a UTCTime or GeneralizedTime in the DER-mandated "Z" form to Unix seconds.
+fn parse_time(element: Element) -> Result<i64, Error> {
+    let text = element.content;
+    let (year, rest) = match element.tag {
+        der::UTC_TIME if text.len() == 13 => {
+            let year = parse_digits(&text[..2]).ok_or_else(|| invalid("malformed time"))?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &text[2..],
+            )
+        }
+        der::GENERALIZED_TIME if text.len() == 15 => (
+            parse_digits(&text[..4]).ok_or_else(|| invalid("malformed time"))?,
+            &text[4..],
+        ),
+        _ => return Err(invalid("malformed time")),
+    };
+    if rest[10] != b'Z' {
+        return Err(invalid("malformed time"));
+    }
+    let field = |i: usize| parse_digits(&rest[i..i + 2]).ok_or_else(|| invalid("malformed time"));
+    let (month, day) = (field(0)?, field(2)?);
+    let (hour, minute, second) = (field(4)?, field(6)?, field(8)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return Err(invalid("malformed time"));
+    }
+    Ok(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+impl Certificate {
+    pub(crate) fn from_der(data: &[u8]) -> Result<Self, Error> {
+        let malformed = || invalid("malformed certificate");
+        let mut outer = Reader::new(data);
+        let certificate = outer.expect(der::SEQUENCE).ok_or_else(malformed)?;
+        if !outer.is_empty() {
+            return Err(malformed());
+        }
+
+        let mut reader = certificate.reader();
+        let tbs = reader.expect(der::SEQUENCE).ok_or_else(malformed)?;
+        let signature_scheme =
+            parse_signature_algorithm(reader.expect(der::SEQUENCE).ok_or_else(malformed)?)?;
+        let signature = reader
+            .expect(der::BIT_STRING)
+            .and_then(|e| e.bit_string_bytes())
+            .ok_or_else(malformed)?;
+
+        let mut fields = tbs.reader();
+        // Only version 3 certificates carry the extensions we rely on.
+        let version = fields
+            .optional(0xa0)
+            .ok_or_else(|| invalid("unsupported version"))?;
+        if version.content != [0x02, 0x01, 0x02] {
+            return Err(invalid("unsupported version"));
+        }
+        fields.expect(der::INTEGER).ok_or_else(malformed)?;
+        let inner_scheme =
+            parse_signature_algorithm(fields.expect(der::SEQUENCE).ok_or_else(malformed)?)?;
+        if inner_scheme != signature_scheme {
+            return Err(invalid("mismatched signature algorithms"));
+        }
+        let issuer = fields.expect(der::SEQUENCE).ok_or_else(malformed)?;
+        let mut validity = fields.expect(der::SEQUENCE).ok_or_else(malformed)?.reader();
+        let not_before = parse_time(validity.next().ok_or_else(malformed)?)?;
+        let not_after = parse_time(validity.next().ok_or_else(malformed)?)?;
+        let subject = fields.expect(der::SEQUENCE).ok_or_else(malformed)?;
+        let public_key = parse_public_key(fields.expect(der::SEQUENCE).ok_or_else(malformed)?)?;
+        fields.optional(0x81);
+        fields.optional(0x82);
+
+        let mut certificate = Self {
+            tbs: tbs.raw.to_vec(),
+            signature_scheme,
+            signature: signature.to_vec(),
+            issuer: issuer.raw.to_vec(),
+            subject: subject.raw.to_vec(),
+            not_before,
+            not_after,
+            public_key,
+            is_ca: false,
+            path_len: None,
+            can_sign_certificates: None,
+            dns_names: Vec::new(),
+            ip_addresses: Vec::new(),
+        };
+
+        if let Some(extensions) = fields.optional(0xa3) {
+            let list = extensions
+                .reader()
+                .expect(der::SEQUENCE)
+                .ok_or_else(malformed)?;
+            let mut list = list.reader();
+            while !list.is_empty() {
+                let extension = list.expect(der::SEQUENCE).ok_or_else(malformed)?;
+                certificate.parse_extension(extension)?;
+            }
+        }
+        if !fields.is_empty() {
+            return Err(malformed());
+        }
+        Ok(certificate)
+    }
+
+    fn parse_extension(&mut self, extension: Element) -> Result<(), Error> {
+        let malformed = || invalid("malformed extension");
+        let mut reader = extension.reader();
+        let oid = reader.expect(der::OID).ok_or_else(malformed)?;
+        let critical = match reader.optional(der::BOOLEAN) {
+            Some(flag) => flag.content != [0],
+            None => false,
+        };
+        let value = reader.expect(der::OCTET_STRING).ok_or_else(malformed)?;
+        let mut value = Reader::new(value.content);
+
+        match oid.content {
+            OID_BASIC_CONSTRAINTS => {
+                let mut constraints = value.expect(der::SEQUENCE).ok_or_else(malformed)?.reader();
+                if let Some(ca) = constraints.optional(der::BOOLEAN) {
+                    self.is_ca = ca.content != [0];
+                }
+                if let Some(path_len) = constraints.optional(der::INTEGER) {
+                    let digits = path_len.unsigned_integer().ok_or_else(malformed)?;
+                    if digits.len() > 2 {
+                        return Err(malformed());
+                    }
+                    self.path_len = Some(digits.iter().fold(0, |n, &b| n << 8 | b as usize));
+                }
+            }
+            OID_KEY_USAGE => {
+                // Unused trailing bits are allowed here, so read the BIT STRING by hand.
+                let bits = value.expect(der::BIT_STRING).ok_or_else(malformed)?;
+                let key_cert_sign = bits.content.get(1).is_some_and(|b| b & 0x04 != 0);
+                self.can_sign_certificates = Some(key_cert_sign);
+            }
+            OID_SUBJECT_ALT_NAME => {
+                let mut names = value.expect(der::SEQUENCE).ok_or_else(malformed)?.reader();
+                while let Some(name) = names.next() {
+                    match name.tag {
+                        0x82 => self
+                            .dns_names
+                            .push(String::from_utf8_lossy(name.content).to_ascii_lowercase()),
+                        0x87 => self.ip_addresses.push(name.content.to_vec()),
+                        _ => {}
+                    }
+                }
+            }
+            OID_SUBJECT_KEY_IDENTIFIER
+            | OID_AUTHORITY_KEY_IDENTIFIER
+            | OID_CERTIFICATE_POLICIES
+            | OID_EXTENDED_KEY_USAGE => {}
+            _ if critical => return Err(invalid("unsupported critical extension")),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub(crate) fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    fn is_valid_at(&self, now: u64) -> bool {
+        let now = now as i64;
+        self.not_before <= now && now <= self.not_after
+    }
+
+    fn is_signed_by(&self, issuer: &Certificate) -> bool {
+        self.issuer == issuer.subject
+            && issuer
+                .public_key
+                .verify(self.signature_scheme, &self.tbs, &self.signature)
+    }
+
+    fn matches_server_name(&self, server_name: &str) -> bool {
+        if let Some(address) = parse_ip_address(server_name) {
+            return self.ip_addresses.iter().any(|ip| *ip == address);
+        }
+        let server_name = server_name.trim_end_matches('.').to_ascii_lowercase();
+        self.dns_names.iter().any(|pattern| {
+            match pattern.strip_prefix("*.") {
+                // A wildcard covers exactly one label.
+                Some(suffix) => match server_name.split_once('.') {
+                    Some((label, rest)) => !label.is_empty() && rest == suffix,
+                    None => false,
+                },
+                None => *pattern == server_name,
+            }
+        })
+    }
+}
+
+fn parse_ip_address(host: &str) -> Option<Vec<u8>> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.contains(':') {
+        // IPv6: expand a single "::" and read the eight 16-bit groups.
+        let (head, tail) = match host.split_once("::") {
+            Some((head, tail)) => (head, Some(tail)),
+            None => (host, None),
+        };
+        let parse_groups = |part: &str| -> Option<Vec<u16>> {
+            if part.is_empty() {
+                return Some(Vec::new());
+            }
+            part.split(':')
+                .map(|group| u16::from_str_radix(group, 16).ok())
+                .collect()
+        };
+        let head = parse_groups(head)?;
+        let tail = match tail {
+            Some(tail) => parse_groups(tail)?,
+            None => Vec::new(),
+        };
+        let missing = 8usize.checked_sub(head.len() + tail.len())?;
+        if missing == 0 && host.contains("::") {
+            return None;
+        }
+        let mut groups = head;
+        groups.resize(groups.len() + missing, 0);
+        groups.extend(tail);
+        if groups.len() != 8 {
+            return None;
+        }
+        return Some(groups.iter().flat_map(|g| g.to_be_bytes()).collect());
+    }
+
+    let octets: Vec<u8> = host
+        .split('.')
+        .map(|part| part.parse::<u8>().ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if octets.len() == 4 {
+        Some(octets)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn is_ip_address(host: &str) -> bool {
+    parse_ip_address(host).is_some()
+}
+
+// Checks that the chain sent by the server leads from a certificate for `server_name` to one of
+// the trusted roots. Extra or out-of-order certificates in the chain are tolerated.
+pub(crate) fn verify_chain(
+    chain: &[Certificate],
+    roots: &[Certificate],
+    server_name: &str,
+    now: u64,
+) -> Result<(), Error> {
+    let leaf = chain
+        .first()
+        .ok_or_else(|| invalid("the server sent no certificate"))?;
+    if !leaf.matches_server_name(server_name) {
+        return Err(Error::InvalidCertificate(format!(
+            "the certificate is not valid for {}",
+            server_name
+        )));
+    }
+
+    let mut current = leaf;
+    for depth in 0..=MAX_CHAIN_DEPTH {
+        if !current.is_valid_at(now) {
+            return Err(invalid("the certificate has expired or is not yet valid"));
+        }
+        let can_issue = |issuer: &Certificate| {
+            issuer.is_ca
+                && issuer.can_sign_certificates != Some(false)
+                && issuer.path_len.map_or(true, |len| depth <= len)
+        };
+
+        if let Some(root) = roots.iter().find(|root| current.is_signed_by(root)) {
+            if !root.is_valid_at(now) {
+                return Err(invalid("the root certificate has expired"));
+            }
+            if !can_issue(root) {
+                return Err(invalid("the root certificate may not issue certificates"));
+            }
+            return Ok(());
+        }
+
+        current = chain[1..]
+            .iter()
+            .find(|issuer| can_issue(issuer) && current.is_signed_by(issuer))
+            .ok_or_else(|| invalid("the certificate was not issued by a trusted authority"))?;
+    }
+    Err(invalid("the certificate chain is too long"))
+}
+
+pub(crate) fn parse_pem(pem: &str) -> Result<Vec<Vec<u8>>, Error> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find(BEGIN) {
+        let body = &rest[start + BEGIN.len()..];
+        let end = body
+            .find(END)
+            .ok_or_else(|| invalid("unterminated PEM block"))?;
+        let der =
+            crate::base64::decode(&body[..end]).ok_or_else(|| invalid("malformed PEM block"))?;
+        certificates.push(der);
+        rest = &body[end + END.len()..];
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // Generated with openssl alongside the fixtures in net/std/tests/fixtures.
+    const ROOT_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBvDCCAUGgAwIBAgIUVkXDsJ2MbcwEYlxFfDcD9T7Osd0wCgYIKoZIzj0EAwMw
+HDEaMBgGA1UEAwwRU2FiYSBUZXN0IFJvb3QgQ0EwIBcNMjYxMDE3MTkyMzMyWhgP
+MjEyNjA5MjMxOTIzMzJaMBwxGjAYBgNVBAMMEVNhYmEgVGVzdCBSb290IENBMHYw
+EAYHKoZIzj0CAQYFK4EEACIDYgAETlfXpkVmpecTc1UgbPuAc9Y5PUZPdvmhfr4u
+X2j/VvN7PFDq2ioVbHmyOYCZw4PUg++rlDrKT7l3ezNBjMK0BmZyJUs522OjqZjJ
+OHWOj5v2bIT2MAnLGhwFVNrEfeX6o0IwQDAPBgNVHRMBAf8EBTADAQH/MA4GA1Ud
+DwEB/wQEAwIBBjAdBgNVHQ4EFgQUSE6TV2hpWRqRwVXBRDZoegulS0owCgYIKoZI
+zj0EAwMDaQAwZgIxAPXcPX/YeeQZzljuconUOwx1XeeuX75V98AEpZBydwXbVT5F
+vx25tk2iyyc+mFU+lwIxAMUrcr7U9P04QS+gpyVgHVR4g8jcNegMj79oP6ABut4d
+ahzEJVhN/VR7C8FvciDRPQ==
+-----END CERTIFICATE-----";
+
+    const INTERMEDIATE_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIClDCCAhugAwIBAgIUEjBrBhrCv8Co2BOFgalgyk1zgpcwCgYIKoZIzj0EAwMw
+HDEaMBgGA1UEAwwRU2FiYSBUZXN0IFJvb3QgQ0EwIBcNMjYxMDE3MTkyMzMyWhgP
+MjEyNjA5MjMxOTIzMzJaMCQxIjAgBgNVBAMMGVNhYmEgVGVzdCBJbnRlcm1lZGlh
+dGUgQ0EwggEiMA0GCSqGSIb3DQEBAQUAA4IBDwAwggEKAoIBAQC07mdMt84zpg2q
+4LdRI5ceRjgqdvfxdExlcQX0U5Yu3aSo0L07n6VuIlfW4WUORMzNgj2Zofi3cUlW
+giPlqvgeMlhtYxYlAyooSLYtfO+3Eg+AY0D4gDVB3x2uy50tl1RX/PVaIF0FuIvQ
+TrzUZ1Dd4CR0yIePLsCXzGUxm3qtgvXx+uI/hi9Vr3D6jdX5hMEvd9xeiGXFleUc
+K91RgJ1brlRZLc/nDE1o1KKO4fHpBDYq8jL6mhF07CwASb9H2KaY2o/vbJtL3Pjk
+BVrHGGA1nH5yNxvXSaucjP8b2PxIE7Kbh1YGgJcdZxbPrAt9bFvTfGeRer/qN/lK
+BQZxn9ZfAgMBAAGjZjBkMBIGA1UdEwEB/wQIMAYBAf8CAQAwDgYDVR0PAQH/BAQD
+AgEGMB0GA1UdDgQWBBQVDYaOgO/90itgNNgA4lGWwXkgyDAfBgNVHSMEGDAWgBRI
+TpNXaGlZGpHBVcFENmh6C6VLSjAKBggqhkjOPQQDAwNnADBkAjAfaGxdqm3ybFfL
+Om18hzpphYti+Txqt+cbEKs7K37hrOH0755Z7zhK0Cg2rIR2YekCMHde/D1kJz0T
+zuh5chQMge/lyzuVp7KBIN2sYY/9mLKWtncoS+slXK0UaHmHrfpG+A==
+-----END CERTIFICATE-----";
+
+    const SERVER_EC: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIB+TCCAX+gAwIBAgIUEjBrBhrCv8Co2BOFgalgyk1zgpgwCgYIKoZIzj0EAwMw
+HDEaMBgGA1UEAwwRU2FiYSBUZXN0IFJvb3QgQ0EwIBcNMjYxMDE3MTkyMzMyWhgP
+MjEyNjA5MjMxOTIzMzJaMBQxEjAQBgNVBAMMCWxvY2FsaG9zdDBZMBMGByqGSM49
+AgEGCCqGSM49AwEHA0IABI8Tir1feG1qUJh1augwa4dhMLdaBPDXxKtqakHJt3Es
+qd1HBz9lKEZqBKGt74zytISE3+Tq8wynEscULyN4eu2jgaQwgaEwDAYDVR0TAQH/
+BAIwADAOBgNVHQ8BAf8EBAMCB4AwEwYDVR0lBAwwCgYIKwYBBQUHAwEwLAYDVR0R
+BCUwI4IJbG9jYWxob3N0ghAqLnRlc3QubG9jYWxob3N0hwR/AAABMB8GA1UdIwQY
+MBaAFEhOk1doaVkakcFVwUQ2aHoLpUtKMB0GA1UdDgQWBBTmWjzYWO5oOW/0+4If
+Pr+pJqzTXDAKBggqhkjOPQQDAwNoADBlAjEA5SyB438YhnyNUbSCgbAMUlynfSdF
+5esiOfFjLG+vUpH61zjS7+Mq5HVNBH3SY6VgAjA/BHqxRL+SqbHjUXIspZeOWZvh
+sXE/mn7gdyPwIFmn5Rzi89Jp7ZoW/N9hpoEqmDA=
+-----END CERTIFICATE-----";
+
+    const SERVER_RSA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIDbTCCAlWgAwIBAgIUCPEBoghBVsZdhFFAgq8xE3f9takwDQYJKoZIhvcNAQEL
+BQAwJDEiMCAGA1UEAwwZU2FiYSBUZXN0IEludGVybWVkaWF0ZSBDQTAgFw0yNjEw
+MTcxOTIzMzJaGA8yMTI2MDkyMzE5MjMzMlowFDESMBAGA1UEAwwJbG9jYWxob3N0
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEA3LDCyBi4modmvMT7yozx
+y+dmyktQp1EbKVJUL3gymZCC35OmyBe206jOPlOMTfgTnuCQN6B06A5eE8BslVxg
+/bCN3+7Vn8EC665I4zaNOZ81aLnBUOoC8v6Q371M8CvAT4t2GnLxUjffIXC6qlTg
+79xUXcUp/3D4HyCuI5A+YRO39QyFwqd4KyjCZE9y9rBzSgYvpIeEpiF4IjqXyIh/
+PGC30xFpQ1uXQohzwzdLw2+yHYv/UekazglOwjCYofQ2AejDkeGgESmDi1tUEmIJ
+1KR/BfjW9Cairx5WtCPhA/ldRFuqbzSlU3crIHg87019R63EcweM+G2Y87l5ndv1
+rwIDAQABo4GkMIGhMAwGA1UdEwEB/wQCMAAwDgYDVR0PAQH/BAQDAgeAMBMGA1Ud
+JQQMMAoGCCsGAQUFBwMBMCwGA1UdEQQlMCOCCWxvY2FsaG9zdIIQKi50ZXN0Lmxv
+Y2FsaG9zdIcEfwAAATAfBgNVHSMEGDAWgBQVDYaOgO/90itgNNgA4lGWwXkgyDAd
+BgNVHQ4EFgQUJpOSoM2ZQFvBvutNemN8FfxfsjMwDQYJKoZIhvcNAQELBQADggEB
+AJfsdOSaGP3CejSMmFd5oaIGh0dB2CnniWHuf06FUO/1xCXG4/o+YmWMT/ZtqCvH
+vUEw+AlQm4tpfzBMh6sk1qn2D71IFn+0Fz+Vd9PihstjSq15mwhUqN0vVcS3oBcn
+OcP6DZ9LjIxsBZ0FPc0CuyJ7SdVnYTLDpYA3qiaTIYmMPw4mF/rTpaXb3Q43MhJn
+2dwVnI34inqBpSrMsYIQzDWhYCpYZ7o4du4AFKrqJBxCO4BP4llJLU6Guj0g6s2P
+8WOcgpXCTtkN9pc5MArUlkMGZM7L3in0k9Ohw1cYE7SjfU3A46AjoBlJkEA0d4Nf
+b+VprnY/QG7KtiieoWPmJDQ=
+-----END CERTIFICATE-----";
+
+    // 2030-01-01T00:00:00Z, inside every fixture's validity period.
+    const NOW: u64 = 1_893_456_000;
+
+    fn load(pem: &str) -> Certificate {
+        Certificate::from_der(&parse_pem(pem).unwrap()[0]).unwrap()
+    }
+
+    #[test]
+    fn test_parse_certificates() {
+        let root = load(ROOT_CA);
+        assert!(root.is_ca);
+        assert_eq!(root.can_sign_certificates, Some(true));
+        assert_eq!(
+            root.signature_scheme,
+            SignatureScheme::Ecdsa(HashAlgorithm::Sha384)
+        );
+        assert!(matches!(root.public_key(), PublicKey::Ec(_)));
+        assert_eq!(root.issuer, root.subject);
+
+        let intermediate = load(INTERMEDIATE_CA);
+        assert_eq!(intermediate.path_len, Some(0));
+
+        let leaf = load(SERVER_RSA);
+        assert!(!leaf.is_ca);
+        assert!(matches!(leaf.public_key(), PublicKey::Rsa(_)));
+        assert_eq!(
+            leaf.signature_scheme,
+            SignatureScheme::RsaPkcs1(HashAlgorithm::Sha256)
+        );
+        assert_eq!(leaf.dns_names, vec!["localhost", "*.test.localhost"]);
+        assert_eq!(leaf.ip_addresses, vec![vec![127, 0, 0, 1]]);
+        assert_eq!(leaf.issuer, intermediate.subject);
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let roots = [load(ROOT_CA)];
+        let ec_chain = [load(SERVER_EC)];
+        assert_eq!(verify_chain(&ec_chain, &roots, "localhost", NOW), Ok(()));
+        assert_eq!(verify_chain(&ec_chain, &roots, "127.0.0.1", NOW), Ok(()));
+
+        let rsa_chain = [load(SERVER_RSA), load(INTERMEDIATE_CA)];
+        assert_eq!(verify_chain(&rsa_chain, &roots, "LocalHost.", NOW), Ok(()));
+        assert!(matches!(
+            verify_chain(&rsa_chain[..1], &roots, "localhost", NOW),
+            Err(Error::InvalidCertificate(_))
+        ));
+        assert!(matches!(
+            verify_chain(&rsa_chain, &[], "localhost", NOW),
+            Err(Error::InvalidCertificate(_))
+        ));
+    }
+
+    #[test]
+    fn test_server_name_and_validity() {
+        let roots = [load(ROOT_CA)];
+        let chain = [load(SERVER_EC)];
+        assert_eq!(
+            verify_chain(&chain, &roots, "a.test.localhost", NOW),
+            Ok(())
+        );
+        for name in [
+            "example.com",
+            "a.b.test.localhost",
+            "test.localhost",
+            "127.0.0.2",
+        ] {
+            assert!(verify_chain(&chain, &roots, name, NOW).is_err(), "{}", name);
+        }
+        // Before issuance and after expiry.
+        assert!(verify_chain(&chain, &roots, "localhost", 1_700_000_000).is_err());
+        assert!(verify_chain(&chain, &roots, "localhost", 5_000_000_000).is_err());
+    }
+
+    #[test]
+    fn test_tampered_certificate() {
+        let mut der = parse_pem(SERVER_EC).unwrap().remove(0);
+        // Flip a bit inside the subject public key, which the issuer's signature covers.
+        let len = der.len();
+        der[len - 120] ^= 1;
+        match Certificate::from_der(&der) {
+            Ok(leaf) => assert!(verify_chain(&[leaf], &[load(ROOT_CA)], "localhost", NOW).is_err()),
+            Err(e) => assert!(matches!(e, Error::InvalidCertificate(_))),
+        }
+        assert!(Certificate::from_der(&der[..len - 1]).is_err());
+    }
+
+    #[test]
+    fn test_parse_ip_address() {
+        assert_eq!(parse_ip_address("10.0.0.1"), Some(vec![10, 0, 0, 1]));
+        assert_eq!(parse_ip_address("256.0.0.1"), None);
+        assert_eq!(parse_ip_address("localhost"), None);
+        let mut loopback = vec![0u8; 16];
+        loopback[15] = 1;
+        assert_eq!(parse_ip_address("[::1]"), Some(loopback));
+        assert_eq!(parse_ip_address("1:2:3:4:5:6:7:8:9"), None);
+    }
+}
@@ -0,0 +1,100 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use crate::dns::{parse_ip_literal, Resolver};
+use crate::error::Error;
+
+pub trait Stream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error>;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let mut written = 0;
+        while written < buf.len() {
+            let n = self.write(&buf[written..])?;
+            if n == 0 {
+                return Err(Error::Network(
+                    "connection closed while writing".to_string(),
+                ));
+            }
+            written += n;
+        }
+        Ok(())
+    }
+}
+
+pub trait Transport {
+    fn connect(&mut self, host: &str, port: u16) -> Result<Box<dyn Stream>, Error>;
+
+    // Runs TLS over a stream this transport opened. Transports that cannot do so refuse, which
+    // makes https URLs fail cleanly.
+    fn secure(
+        &mut self,
+        _stream: Box<dyn Stream>,
+        server_name: &str,
+    ) -> Result<Box<dyn Stream>, Error> {
+        Err(Error::TlsHandshake(format!(
+            "TLS is not supported by this transport (connecting to {})",
+            server_name
+        )))
+    }
+}
+
+// Opens connections to numeric addresses. Paired with a resolver it makes up a transport.
+pub trait Connector {
+    fn connect_addr(&mut self, address: IpAddr, port: u16) -> Result<Box<dyn Stream>, Error>;
+}
+
+// Resolves host names itself and tries each returned address in turn until one connects.
+pub struct ResolvingTransport<C: Connector, R: Resolver> {
+    connector: C,
+    resolver: R,
+}
+
+impl<C: Connector, R: Resolver> ResolvingTransport<C, R> {
+    pub fn new(connector: C, resolver: R) -> Self {
+        Self {
+            connector,
+            resolver,
+        }
+    }
+
+    pub fn connector_mut(&mut self) -> &mut C {
+        &mut self.connector
+    }
+
+    pub fn resolver_mut(&mut self) -> &mut R {
+        &mut self.resolver
+    }
+}
+
+impl<C: Connector, R: Resolver> Transport for ResolvingTransport<C, R> {
+    fn connect(&mut self, host: &str, port: u16) -> Result<Box<dyn Stream>, Error> {
+        let addresses = match parse_ip_literal(host) {
+            Some(address) => Vec::from([address]),
+            None => self.resolver.resolve(host)?.addresses(),
+        };
+
+        let mut last_error = None;
+        for address in &addresses {
+            match self.connector.connect_addr(*address, port) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(match last_error {
+            Some(Error::Network(message)) if addresses.len() > 1 => Error::Network(format!(
+                "Failed to connect to any of the {} addresses of {}: {}",
+                addresses.len(),
+                host,
+                message
+            )),
+            Some(e) => e,
+            None => Error::Network(format!("No IP addresses found for {}", host)),
+        })
+    }
+}
@@ -0,0 +1,940 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::base64;
+use crate::error::Error;
+use crate::fetch::HttpFetcher;
+use crate::hash::sha1;
+use crate::http::{HttpRequest, HttpResponse, Method};
+use crate::random::RandomSource;
+use crate::transport::{Stream, Transport};
+use crate::url::Url;
+
+// Close codes from RFC 6455 section 7.4.1.
+pub const CLOSE_NORMAL: u16 = 1000;
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const CLOSE_UNSUPPORTED_DATA: u16 = 1003;
+pub const CLOSE_INVALID_PAYLOAD: u16 = 1007;
+pub const CLOSE_POLICY_VIOLATION: u16 = 1008;
+pub const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+pub const CLOSE_MANDATORY_EXTENSION: u16 = 1010;
+pub const CLOSE_INTERNAL_ERROR: u16 = 1011;
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024;
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xa => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xa,
+        }
+    }
+
+    pub fn is_control(&self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    fin: bool,
+    opcode: Opcode,
+    mask: Option<[u8; 4]>,
+    // Always the unmasked payload.
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(fin: bool, opcode: Opcode, payload: Vec<u8>) -> Self {
+        Self {
+            fin,
+            opcode,
+            mask: None,
+            payload,
+        }
+    }
+
+    pub fn fin(&self) -> bool {
+        self.fin
+    }
+
+    pub fn opcode(&self) -> Opcode {
+        self.opcode
+    }
+
+    pub fn mask(&self) -> Option<[u8; 4]> {
+        self.mask
+    }
+
+    pub fn set_mask(&mut self, mask: Option<[u8; 4]>) {
+        self.mask = mask;
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.payload.len() + 14);
+        out.push(if self.fin { 0x80 } else { 0 } | self.opcode.as_u8());
+
+        let mask_bit = if self.mask.is_some() { 0x80 } else { 0 };
+        let len = self.payload.len();
+        if len < 126 {
+            out.push(mask_bit | len as u8);
+        } else if len <= u16::MAX as usize {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+
+        match self.mask {
+            Some(mask) => {
+                out.extend_from_slice(&mask);
+                out.extend(
+                    self.payload
+                        .iter()
+                        .enumerate()
+                        .map(|(i, b)| b ^ mask[i % 4]),
+                );
+            }
+            None => out.extend_from_slice(&self.payload),
+        }
+        out
+    }
+}
+
+struct FrameHeader {
+    opcode: Opcode,
+    header_len: usize,
+    payload_len: u64,
+}
+
+// Splits a byte stream into frames. It checks the framing rules that hold for both peers and
+// leaves role-specific ones, such as who must mask, to the caller.
+#[derive(Debug, Clone, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn header(&self) -> Result<Option<FrameHeader>, Error> {
+        if self.buffer.len() < 2 {
+            return Ok(None);
+        }
+        let (first, second) = (self.buffer[0], self.buffer[1]);
+        if first & 0x70 != 0 {
+            return Err(Error::Network(
+                "WebSocket frame uses reserved bits".to_string(),
+            ));
+        }
+        let opcode = Opcode::from_u8(first & 0x0f)
+            .ok_or_else(|| Error::Network(format!("unknown WebSocket opcode {}", first & 0x0f)))?;
+
+        let (extra, payload_len) = match second & 0x7f {
+            126 => {
+                if self.buffer.len() < 4 {
+                    return Ok(None);
+                }
+                (
+                    2,
+                    u16::from_be_bytes([self.buffer[2], self.buffer[3]]) as u64,
+                )
+            }
+            127 => {
+                if self.buffer.len() < 10 {
+                    return Ok(None);
+                }
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&self.buffer[2..10]);
+                let len = u64::from_be_bytes(bytes);
+                if len >> 63 != 0 {
+                    return Err(Error::Network(
+                        "WebSocket frame length is out of range".to_string(),
+                    ));
+                }
+                (8, len)
+            }
+            len => (0, len as u64),
+        };
+
+        if opcode.is_control() && (first & 0x80 == 0 || payload_len > 125) {
+            return Err(Error::Network(
+                "WebSocket control frames must be short and unfragmented".to_string(),
+            ));
+        }
+        let mask_len = if second & 0x80 != 0 { 4 } else { 0 };
+        Ok(Some(FrameHeader {
+            opcode,
+            header_len: 2 + extra + mask_len,
+            payload_len,
+        }))
+    }
+
+    // The payload length announced by the frame at the front of the buffer, once its header has
+    // arrived. Callers use it to refuse oversized frames before buffering them.
+    pub fn declared_len(&self) -> Result<Option<u64>, Error> {
+        Ok(self.header()?.map(|header| header.payload_len))
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let Some(header) = self.header()? else {
+            return Ok(None);
+        };
+        let total = header.header_len as u64 + header.payload_len;
+        if (self.buffer.len() as u64) < total {
+            return Ok(None);
+        }
+
+        let fin = self.buffer[0] & 0x80 != 0;
+        let mask = if self.buffer[1] & 0x80 != 0 {
+            let mut mask = [0u8; 4];
+            mask.copy_from_slice(&self.buffer[header.header_len - 4..header.header_len]);
+            Some(mask)
+        } else {
+            None
+        };
+        let mut payload: Vec<u8> = self.buffer[header.header_len..total as usize].to_vec();
+        if let Some(mask) = mask {
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+        }
+        self.buffer.drain(..total as usize);
+
+        Ok(Some(Frame {
+            fin,
+            opcode: header.opcode,
+            mask,
+            payload,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    // A close frame. The code is None when the peer sent no status.
+    Close { code: Option<u16>, reason: String },
+}
+
+// The value the server must return in Sec-WebSocket-Accept for a given Sec-WebSocket-Key.
+pub fn accept_key(key: &str) -> String {
+    let mut input = key.as_bytes().to_vec();
+    input.extend_from_slice(ACCEPT_GUID.as_bytes());
+    base64::encode(&sha1(&input))
+}
+
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1011 | 3000..=4999)
+}
+
+// The codes an endpoint may send. The others are reserved or only reported locally.
+fn check_close_code(code: u16) -> Result<(), Error> {
+    if !is_valid_close_code(code) {
+        return Err(Error::Network(format!(
+            "invalid WebSocket close code {}",
+            code
+        )));
+    }
+    Ok(())
+}
+
+pub struct WebSocket<R: RandomSource> {
+    stream: Box<dyn Stream>,
+    random: R,
+    reader: FrameReader,
+    protocol: Option<String>,
+    max_frame_size: usize,
+    max_message_size: usize,
+    // The opcode and data of a fragmented message still being received.
+    fragments: Option<(Opcode, Vec<u8>)>,
+    close_sent: bool,
+    closed: bool,
+}
+
+impl<R: RandomSource> WebSocket<R> {
+    // Opens a connection to a ws or wss URL through the fetcher's transport and proxy settings.
+    pub fn connect<T: Transport>(
+        fetcher: &mut HttpFetcher<T>,
+        url: &Url,
+        protocols: &[&str],
+        random: R,
+    ) -> Result<Self, Error> {
+        match url.scheme().as_str() {
+            "ws" | "wss" => {}
+            scheme => {
+                return Err(Error::Network(format!(
+                    "unsupported WebSocket scheme: {}",
+                    scheme
+                )))
+            }
+        }
+        let stream = fetcher.open_stream(url)?;
+        Self::handshake(stream, url, protocols, random)
+    }
+
+    // Runs the opening handshake over an already connected stream.
+    pub fn handshake(
+        mut stream: Box<dyn Stream>,
+        url: &Url,
+        protocols: &[&str],
+        mut random: R,
+    ) -> Result<Self, Error> {
+        let mut nonce = [0u8; 16];
+        random.fill(&mut nonce)?;
+        let key = base64::encode(&nonce);
+
+        let mut request = HttpRequest::new(Method::Get, url.clone());
+        request.add_header("Upgrade", "websocket")?;
+        request.add_header("Connection", "Upgrade")?;
+        request.add_header("Sec-WebSocket-Key", &key)?;
+        request.add_header("Sec-WebSocket-Version", "13")?;
+        if !protocols.is_empty() {
+            request.add_header("Sec-WebSocket-Protocol", &protocols.join(", "))?;
+        }
+        stream.write_all(&request.serialize())?;
+
+        // Read just the response head; anything after it already belongs to the frame stream.
+        let mut head = Vec::new();
+        let end = loop {
+            if let Some(position) = head.windows(4).position(|w| w == b"\r\n\r\n") {
+                break position + 4;
+            }
+            let mut buf = [0u8; 1024];
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                return Err(Error::Network(
+                    "connection closed during the WebSocket handshake".to_string(),
+                ));
+            }
+            head.extend_from_slice(&buf[..n]);
+        };
+        let response = HttpResponse::new(&head[..end])?;
+        let mut reader = FrameReader::new();
+        reader.feed(&head[end..]);
+
+        if response.status_code() != 101 {
+            return Err(Error::Network(format!(
+                "the server refused the WebSocket upgrade with status {}",
+                response.status_code()
+            )));
+        }
+        let header = |name: &str| response.header_value(name).unwrap_or_default();
+        let connection_upgrades = header("Connection")
+            .split(',')
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+        if !header("Upgrade").trim().eq_ignore_ascii_case("websocket") || !connection_upgrades {
+            return Err(Error::Network(
+                "the server did not upgrade to WebSocket".to_string(),
+            ));
+        }
+        if header("Sec-WebSocket-Accept").trim() != accept_key(&key) {
+            return Err(Error::Network(
+                "the server sent a wrong Sec-WebSocket-Accept".to_string(),
+            ));
+        }
+        if response.header_value("Sec-WebSocket-Extensions").is_some() {
+            return Err(Error::Network(
+                "the server enabled a WebSocket extension that was not offered".to_string(),
+            ));
+        }
+        let protocol = response
+            .header_value("Sec-WebSocket-Protocol")
+            .map(|p| p.trim().to_string());
+        if let Some(protocol) = &protocol {
+            if !protocols.contains(&protocol.as_str()) {
+                return Err(Error::Network(format!(
+                    "the server chose a subprotocol that was not offered: {}",
+                    protocol
+                )));
+            }
+        }
+
+        Ok(Self {
+            stream,
+            random,
+            reader,
+            protocol,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            fragments: None,
+            close_sent: false,
+            closed: false,
+        })
+    }
+
+    pub fn protocol(&self) -> Option<String> {
+        self.protocol.clone()
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    // Outgoing messages longer than this are split into continuation frames.
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = core::cmp::max(max_frame_size, 1);
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn write_frame(&mut self, mut frame: Frame) -> Result<(), Error> {
+        // Every frame a client sends is masked with a fresh key.
+        let mut mask = [0u8; 4];
+        self.random.fill(&mut mask)?;
+        frame.set_mask(Some(mask));
+        self.stream.write_all(&frame.encode())
+    }
+
+    fn send_data(&mut self, opcode: Opcode, data: &[u8]) -> Result<(), Error> {
+        if self.close_sent || self.closed {
+            return Err(Error::Network(
+                "the WebSocket connection is closing".to_string(),
+            ));
+        }
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            Vec::from([data])
+        } else {
+            data.chunks(self.max_frame_size).collect()
+        };
+        let last = chunks.len() - 1;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let opcode = if i == 0 { opcode } else { Opcode::Continuation };
+            self.write_frame(Frame::new(i == last, opcode, chunk.to_vec()))?;
+        }
+        Ok(())
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), Error> {
+        self.send_data(Opcode::Text, text.as_bytes())
+    }
+
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.send_data(Opcode::Binary, data)
+    }
+
+    pub fn ping(&mut self, payload: &[u8]) -> Result<(), Error> {
+        self.send_control(Opcode::Ping, payload)
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), Error> {
+        match message {
+            Message::Text(text) => self.send_text(text),
+            Message::Binary(data) => self.send_binary(data),
+            Message::Ping(payload) => self.ping(payload),
+            Message::Pong(payload) => self.send_control(Opcode::Pong, payload),
+            Message::Close { code, reason } => {
+                let code = code.unwrap_or(CLOSE_NORMAL);
+                check_close_code(code)?;
+                self.send_close(code, reason)
+            }
+        }
+    }
+
+    fn send_control(&mut self, opcode: Opcode, payload: &[u8]) -> Result<(), Error> {
+        if payload.len() > 125 {
+            return Err(Error::Network(
+                "WebSocket control frame payloads are limited to 125 bytes".to_string(),
+            ));
+        }
+        self.write_frame(Frame::new(true, opcode, payload.to_vec()))
+    }
+
+    fn send_close(&mut self, code: u16, reason: &str) -> Result<(), Error> {
+        if self.close_sent {
+            return Ok(());
+        }
+        let mut payload = code.to_be_bytes().to_vec();
+        // Keep the whole payload within the 125 bytes allowed for control frames.
+        let mut end = core::cmp::min(reason.len(), 123);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.extend_from_slice(&reason.as_bytes()[..end]);
+        self.close_sent = true;
+        self.write_frame(Frame::new(true, Opcode::Close, payload))
+    }
+
+    // Starts the closing handshake and waits for the server to confirm it.
+    pub fn close(&mut self, code: u16, reason: &str) -> Result<(), Error> {
+        check_close_code(code)?;
+        self.send_close(code, reason)?;
+        while !self.closed {
+            match self.receive() {
+                Ok(Message::Close { .. }) => break,
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // Gives up on the connection after a protocol violation by the server.
+    fn fail(&mut self, code: u16, message: &str) -> Error {
+        let _ = self.send_close(code, "");
+        self.closed = true;
+        Error::Network(message.to_string())
+    }
+
+    fn next_frame(&mut self) -> Result<Frame, Error> {
+        loop {
+            match self.reader.declared_len() {
+                Ok(Some(len)) if len > self.max_message_size as u64 => {
+                    return Err(self.fail(CLOSE_MESSAGE_TOO_BIG, "WebSocket frame is too large"))
+                }
+                Ok(_) => {}
+                Err(e) => return Err(self.fail(CLOSE_PROTOCOL_ERROR, &format!("{:?}", e))),
+            }
+            match self.reader.next_frame() {
+                Ok(Some(frame)) => return Ok(frame),
+                Ok(None) => {}
+                Err(e) => return Err(self.fail(CLOSE_PROTOCOL_ERROR, &format!("{:?}", e))),
+            }
+
+            let mut buf = [0u8; 4096];
+            let n = self.stream.read(&mut buf)?;
+            if n == 0 {
+                self.closed = true;
+                return Err(Error::Network(
+                    "the WebSocket connection closed without a close frame".to_string(),
+                ));
+            }
+            self.reader.feed(&buf[..n]);
+        }
+    }
+
+    fn complete_message(&mut self, opcode: Opcode, data: Vec<u8>) -> Result<Message, Error> {
+        if opcode == Opcode::Binary {
+            return Ok(Message::Binary(data));
+        }
+        String::from_utf8(data)
+            .map(Message::Text)
+            .map_err(|_| self.fail(CLOSE_INVALID_PAYLOAD, "WebSocket text is not UTF-8"))
+    }
+
+    // Waits for the next message. Pings are answered automatically but still reported, and a
+    // close from the server is confirmed before it is returned.
+    pub fn receive(&mut self) -> Result<Message, Error> {
+        loop {
+            if self.closed {
+                return Err(Error::Network(
+                    "the WebSocket connection is closed".to_string(),
+                ));
+            }
+            let frame = self.next_frame()?;
+            if frame.mask().is_some() {
+                return Err(self.fail(CLOSE_PROTOCOL_ERROR, "the server masked a frame"));
+            }
+
+            match frame.opcode() {
+                Opcode::Ping => {
+                    if !self.close_sent {
+                        self.write_frame(Frame::new(true, Opcode::Pong, frame.payload()))?;
+                    }
+                    return Ok(Message::Ping(frame.payload()));
+                }
+                Opcode::Pong => return Ok(Message::Pong(frame.payload())),
+                Opcode::Close => {
+                    let payload = frame.payload();
+                    let (code, reason) = match payload.len() {
+                        0 => (None, String::new()),
+                        1 => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "malformed close frame")),
+                        _ => {
+                            let code = u16::from_be_bytes([payload[0], payload[1]]);
+                            if !is_valid_close_code(code) {
+                                return Err(self.fail(CLOSE_PROTOCOL_ERROR, "invalid close code"));
+                            }
+                            match String::from_utf8(payload[2..].to_vec()) {
+                                Ok(reason) => (Some(code), reason),
+                                Err(_) => {
+                                    return Err(self
+                                        .fail(CLOSE_INVALID_PAYLOAD, "close reason is not UTF-8"))
+                                }
+                            }
+                        }
+                    };
+                    // Echo the status back to complete the closing handshake.
+                    let _ = self.send_close(code.unwrap_or(CLOSE_NORMAL), "");
+                    self.closed = true;
+                    return Ok(Message::Close { code, reason });
+                }
+                Opcode::Text | Opcode::Binary => {
+                    if self.fragments.is_some() {
+                        return Err(self.fail(
+                            CLOSE_PROTOCOL_ERROR,
+                            "a new message started inside a fragmented one",
+                        ));
+                    }
+                    if frame.fin() {
+                        return self.complete_message(frame.opcode(), frame.payload);
+                    }
+                    self.fragments = Some((frame.opcode(), frame.payload));
+                }
+                Opcode::Continuation => {
+                    let Some((opcode, mut data)) = self.fragments.take() else {
+                        return Err(
+                            self.fail(CLOSE_PROTOCOL_ERROR, "continuation frame without a message")
+                        );
+                    };
+                    data.extend_from_slice(&frame.payload);
+                    if data.len() > self.max_message_size {
+                        return Err(
+                            self.fail(CLOSE_MESSAGE_TOO_BIG, "WebSocket message is too large")
+                        );
+                    }
+                    if frame.fin() {
+                        return self.complete_message(opcode, data);
+                    }
+                    self.fragments = Some((opcode, data));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::RefCell;
+
+    struct ScriptedStream {
+        input: Vec<u8>,
+        written: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Stream for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let n = core::cmp::min(buf.len(), self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input.drain(..n);
+            Ok(n)
+        }
+
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.written.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    // Returns the RFC 6455 sample nonce, then zero masks.
+    struct SampleRandom {
+        calls: usize,
+    }
+
+    impl RandomSource for SampleRandom {
+        fn fill(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+            if self.calls == 0 {
+                buf.copy_from_slice(b"the sample nonce");
+            } else {
+                buf.fill(0);
+            }
+            self.calls += 1;
+            Ok(())
+        }
+    }
+
+    fn server_frames(frames: &[Frame]) -> Vec<u8> {
+        let mut input = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n"
+            .to_vec();
+        for frame in frames {
+            input.extend_from_slice(&frame.encode());
+        }
+        input
+    }
+
+    type Opened = (Result<WebSocket<SampleRandom>, Error>, Rc<RefCell<Vec<u8>>>);
+
+    fn open(input: Vec<u8>) -> Opened {
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let stream = ScriptedStream {
+            input,
+            written: written.clone(),
+        };
+        let url = Url::new("ws://example.com/chat".to_string())
+            .parse()
+            .unwrap();
+        let socket = WebSocket::handshake(Box::new(stream), &url, &[], SampleRandom { calls: 0 });
+        (socket, written)
+    }
+
+    // The frames the client wrote after its handshake request.
+    fn client_frames(written: &[u8]) -> Vec<Frame> {
+        let end = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let mut reader = FrameReader::new();
+        reader.feed(&written[end..]);
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame().unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn test_encode_frames() {
+        // The single-frame examples from RFC 6455 section 5.7.
+        let mut frame = Frame::new(true, Opcode::Text, b"Hello".to_vec());
+        assert_eq!(
+            frame.encode(),
+            vec![0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]
+        );
+        frame.set_mask(Some([0x37, 0xfa, 0x21, 0x3d]));
+        assert_eq!(
+            frame.encode(),
+            vec![0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]
+        );
+
+        let medium = Frame::new(true, Opcode::Binary, vec![0; 256]).encode();
+        assert_eq!(medium[..4], [0x82, 0x7e, 0x01, 0x00]);
+        let large = Frame::new(true, Opcode::Binary, vec![0; 65536]).encode();
+        assert_eq!(large[..10], [0x82, 0x7f, 0, 0, 0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_frame_reader() {
+        let mut masked = Frame::new(false, Opcode::Binary, vec![7; 300]);
+        masked.set_mask(Some([1, 2, 3, 4]));
+        let bytes = masked.encode();
+
+        let mut reader = FrameReader::new();
+        reader.feed(&bytes[..3]);
+        assert_eq!(reader.next_frame().unwrap(), None);
+        reader.feed(&bytes[3..]);
+        assert_eq!(reader.declared_len().unwrap(), Some(300));
+        assert_eq!(reader.next_frame().unwrap(), Some(masked));
+        assert_eq!(reader.next_frame().unwrap(), None);
+
+        let mut reserved = FrameReader::new();
+        reserved.feed(&[0xc1, 0x00]);
+        assert!(reserved.next_frame().is_err());
+        let mut long_ping = FrameReader::new();
+        long_ping.feed(&[0x89, 0x7e, 0x00, 0x80]);
+        assert!(long_ping.next_frame().is_err());
+        let mut fragmented_close = FrameReader::new();
+        fragmented_close.feed(&[0x08, 0x00]);
+        assert!(fragmented_close.next_frame().is_err());
+        let mut unknown = FrameReader::new();
+        unknown.feed(&[0x83, 0x00]);
+        assert!(unknown.next_frame().is_err());
+    }
+
+    #[test]
+    fn test_handshake() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string()
+        );
+
+        let (socket, written) = open(server_frames(&[]));
+        assert!(socket.is_ok());
+        let request = String::from_utf8(written.borrow().clone()).unwrap();
+        assert!(request.starts_with("GET /chat HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(request.contains("Upgrade: websocket\r\n"));
+        assert!(request.contains("Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"));
+        assert!(request.contains("Sec-WebSocket-Version: 13\r\n"));
+
+        let wrong_accept = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+Connection: Upgrade\r\nSec-WebSocket-Accept: bm9wZQ==\r\n\r\n";
+        assert!(open(wrong_accept.to_vec()).0.is_err());
+        let refused = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert!(open(refused.to_vec()).0.is_err());
+    }
+
+    #[test]
+    fn test_receive_messages() {
+        let (socket, written) = open(server_frames(&[
+            Frame::new(true, Opcode::Text, b"hi".to_vec()),
+            Frame::new(false, Opcode::Text, b"frag".to_vec()),
+            Frame::new(true, Opcode::Ping, b"p".to_vec()),
+            Frame::new(true, Opcode::Continuation, b"ment".to_vec()),
+            Frame::new(true, Opcode::Binary, vec![1, 2]),
+            Frame::new(true, Opcode::Close, vec![0x03, 0xe8, b'o', b'k']),
+        ]));
+        let mut socket = socket.unwrap();
+        assert_eq!(socket.receive().unwrap(), Message::Text("hi".to_string()));
+        assert_eq!(socket.receive().unwrap(), Message::Ping(b"p".to_vec()));
+        assert_eq!(
+            socket.receive().unwrap(),
+            Message::Text("fragment".to_string())
+        );
+        assert_eq!(socket.receive().unwrap(), Message::Binary(vec![1, 2]));
+        assert_eq!(
+            socket.receive().unwrap(),
+            Message::Close {
+                code: Some(CLOSE_NORMAL),
+                reason: "ok".to_string()
+            }
+        );
+        assert!(socket.is_closed());
+        assert!(socket.receive().is_err());
+
+        // The ping is answered and the close echoed, both masked.
+        let frames = client_frames(&written.borrow());
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].opcode(), Opcode::Pong);
+        assert_eq!(frames[0].payload(), b"p".to_vec());
+        assert_eq!(frames[1].opcode(), Opcode::Close);
+        assert_eq!(frames[1].payload(), vec![0x03, 0xe8]);
+        assert!(frames.iter().all(|f| f.mask().is_some()));
+    }
+
+    #[test]
+    fn test_send_fragments_large_messages() {
+        let (socket, written) = open(server_frames(&[Frame::new(
+            true,
+            Opcode::Close,
+            vec![0x03, 0xe8],
+        )]));
+        let mut socket = socket.unwrap();
+        socket.set_max_frame_size(4);
+        socket.send_text("hello world").unwrap();
+        socket.send_binary(&[]).unwrap();
+        socket.close(CLOSE_NORMAL, "bye").unwrap();
+        assert!(socket.send_text("late").is_err());
+
+        let frames = client_frames(&written.borrow());
+        let shape: Vec<(bool, Opcode, Vec<u8>)> = frames
+            .iter()
+            .map(|f| (f.fin(), f.opcode(), f.payload()))
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                (false, Opcode::Text, b"hell".to_vec()),
+                (false, Opcode::Continuation, b"o wo".to_vec()),
+                (true, Opcode::Continuation, b"rld".to_vec()),
+                (true, Opcode::Binary, Vec::new()),
+                (true, Opcode::Close, b"\x03\xe8bye".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_checks_control_frames() {
+        let (socket, written) = open(server_frames(&[]));
+        let mut socket = socket.unwrap();
+        assert!(socket.send(&Message::Ping(vec![0; 126])).is_err());
+        assert!(socket.send(&Message::Pong(vec![0; 126])).is_err());
+        for code in [999, 1005, 1006, 1015, 2999, 5000] {
+            let close = Message::Close {
+                code: Some(code),
+                reason: String::new(),
+            };
+            assert!(socket.send(&close).is_err(), "{}", code);
+        }
+        assert!(client_frames(&written.borrow()).is_empty());
+
+        socket.send(&Message::Pong(vec![0; 125])).unwrap();
+        socket
+            .send(&Message::Close {
+                code: Some(4000),
+                reason: String::new(),
+            })
+            .unwrap();
+        let frames = client_frames(&written.borrow());
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].payload(), vec![0x0f, 0xa0]);
+    }
+
+    #[test]
+    fn test_protocol_violations_close_the_connection() {
+        let cases: [(Frame, u16); 3] = [
+            (
+                Frame::new(true, Opcode::Text, vec![0xff, 0xfe]),
+                CLOSE_INVALID_PAYLOAD,
+            ),
+            (
+                Frame::new(true, Opcode::Continuation, b"x".to_vec()),
+                CLOSE_PROTOCOL_ERROR,
+            ),
+            (
+                Frame::new(true, Opcode::Close, vec![0x03, 0xed]),
+                CLOSE_PROTOCOL_ERROR,
+            ),
+        ];
+        for (frame, code) in cases {
+            let (socket, written) = open(server_frames(&[frame]));
+            let mut socket = socket.unwrap();
+            assert!(socket.receive().is_err());
+            assert!(socket.is_closed());
+            let frames = client_frames(&written.borrow());
+            assert_eq!(frames[0].payload(), code.to_be_bytes().to_vec());
+        }
+
+        let mut masked = Frame::new(true, Opcode::Text, b"x".to_vec());
+        masked.set_mask(Some([1, 2, 3, 4]));
+        let (socket, _) = open(server_frames(&[masked]));
+        assert!(socket.unwrap().receive().is_err());
+
+        let (socket, written) = open(server_frames(&[Frame::new(
+            true,
+            Opcode::Binary,
+            vec![0; 64],
+        )]));
+        let mut socket = socket.unwrap();
+        socket.set_max_message_size(32);
+        assert!(socket.receive().is_err());
+        let frames = client_frames(&written.borrow());
+        assert_eq!(
+            frames[0].payload(),
+            CLOSE_MESSAGE_TOO_BIG.to_be_bytes().to_vec()
+        );
+    }
+}
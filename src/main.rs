@@ -3,7 +3,6 @@
 
 extern crate alloc;
 
-use alloc::string::ToString;
 use net_wasabi::http::HttpClient;
 use noli::prelude::*;
 use saba_nogtk_core::{browser::Browser, http::HttpResponse};
@@ -29,7 +28,7 @@ Data: xx xx xx
 fn main() -> u64 {
     let browser = Browser::new();
     let response =
-        HttpResponse::new(TEST_HTTP_RESPONSE.to_string()).expect("failed to parse http response");
+        HttpResponse::new(TEST_HTTP_RESPONSE.as_bytes()).expect("failed to parse http response");
     let page = browser.borrow_mut().current_page();
     page.borrow_mut().receive_response(response);
 